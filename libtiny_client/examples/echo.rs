@@ -38,6 +38,12 @@ fn main() {
         auto_join: chans.to_owned(),
         nickserv_ident: None,
         sasl_auth: None,
+        encoding: None,
+        chan_encodings: vec![],
+        outgoing_encoding: None,
+        ping_interval: None,
+        websocket: None,
+        unix_socket: None,
     };
 
     println!("{:?}", server_info);
@@ -62,34 +68,37 @@ async fn echo_bot_task(server_info: ServerInfo) {
         println!("Client event: {:?}", ev);
         if let Event::Msg(Msg {
             pfx: Some(Pfx::User { nick, .. }),
-            cmd: Cmd::PRIVMSG { target, msg, .. },
+            cmd: Cmd::PRIVMSG { targets, msg, .. },
+            ..
         }) = ev
         {
-            let echo_msg = match target {
-                MsgTarget::User(_) => {
-                    // Message is a PRIVMSG to us, just echo the whole message to the sender
-                    Some((nick, msg))
-                }
-                MsgTarget::Chan(chan) => {
-                    // Message was sent to a channel. Only echo if it's directed at us
-                    let our_nick = client.get_nick();
-                    if msg.starts_with(&our_nick) {
-                        let mut msg = &msg[our_nick.len()..];
-                        for nick_sep in NICK_SEP.iter() {
-                            if msg.starts_with(nick_sep) {
-                                msg = &msg[nick_sep.len()..];
-                                break;
+            for target in targets {
+                let echo_msg = match target {
+                    MsgTarget::User(_) => {
+                        // Message is a PRIVMSG to us, just echo the whole message to the sender
+                        Some((nick.clone(), msg.clone()))
+                    }
+                    MsgTarget::Chan(chan) => {
+                        // Message was sent to a channel. Only echo if it's directed at us
+                        let our_nick = client.get_nick();
+                        if msg.starts_with(&our_nick) {
+                            let mut msg = &msg[our_nick.len()..];
+                            for nick_sep in NICK_SEP.iter() {
+                                if msg.starts_with(nick_sep) {
+                                    msg = &msg[nick_sep.len()..];
+                                    break;
+                                }
                             }
+                            Some((chan, msg.to_owned()))
+                        } else {
+                            None
                         }
-                        Some((chan, msg.to_owned()))
-                    } else {
-                        None
                     }
-                }
-            };
+                };
 
-            if let Some((target, msg)) = echo_msg {
-                client.privmsg(&target, &msg, false);
+                if let Some((target, msg)) = echo_msg {
+                    client.privmsg(&target, &msg, false).unwrap();
+                }
             }
         }
     }
@@ -0,0 +1,100 @@
+//! Channel lists (LIST command). See `Client::list`.
+
+use libtiny_wire::wildcard_match;
+
+/// Filters for `Client::list`. Filters are sent to the server when it supports them (advertised
+/// in the `ELIST` ISUPPORT token), and applied by the client otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListFilter {
+    /// Channel name masks, with `*` and `?` wildcards (e.g. `#rust*`). Channels that match any of
+    /// the masks are listed. All channels are listed when empty.
+    pub masks: Vec<String>,
+
+    /// Only list channels with more users than this.
+    pub min_users: Option<usize>,
+
+    /// Only list channels with fewer users than this.
+    pub max_users: Option<usize>,
+}
+
+/// A channel in a LIST reply (322 RPL_LIST).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChanListEntry {
+    pub chan: String,
+    pub n_users: usize,
+    pub topic: String,
+}
+
+impl ListFilter {
+    /// Parameters of the LIST command for this filter. `elist` is the value of the `ELIST`
+    /// ISUPPORT token: `M` means the server supports masks, `U` means it supports user count
+    /// filters. Filters not supported by the server are applied by the client in `matches`.
+    pub(crate) fn list_params(&self, elist: &str) -> Vec<String> {
+        let mut params = vec![];
+        let has_wildcards = self.masks.iter().any(|mask| mask.contains(&['*', '?'][..]));
+        // Without wildcards masks are just channel names, which all servers support
+        if !has_wildcards || elist.contains('M') {
+            params.extend(self.masks.iter().cloned());
+        }
+        if elist.contains('U') {
+            if let Some(min_users) = self.min_users {
+                params.push(format!(">{}", min_users));
+            }
+            if let Some(max_users) = self.max_users {
+                params.push(format!("<{}", max_users));
+            }
+        }
+        params
+    }
+
+    /// Does the channel pass the filter? Masks are matched case-insensitively (ASCII).
+    pub(crate) fn matches(&self, entry: &ChanListEntry) -> bool {
+        let chan = entry.chan.to_ascii_lowercase();
+        (self.masks.is_empty()
+            || self
+                .masks
+                .iter()
+                .any(|mask| wildcard_match(&mask.to_ascii_lowercase(), &chan)))
+            && self.min_users.map(|n| entry.n_users > n).unwrap_or(true)
+            && self.max_users.map(|n| entry.n_users < n).unwrap_or(true)
+    }
+}
+
+/// Parse a 322 RPL_LIST reply: `<client> <channel> <client count> :<topic>`
+pub(crate) fn parse_list_reply(params: &[String]) -> Option<ChanListEntry> {
+    let chan = params.get(1)?;
+    let n_users = params.get(2)?.parse::<usize>().ok()?;
+    let topic = params.get(3).map(String::as_str).unwrap_or("");
+    Some(ChanListEntry {
+        chan: chan.to_owned(),
+        n_users,
+        topic: topic.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_filter() {
+        let filter = ListFilter {
+            masks: vec!["#rust*".to_owned()],
+            min_users: Some(10),
+            max_users: None,
+        };
+        assert_eq!(filter.list_params("CMNTU"), vec!["#rust*", ">10"]);
+        assert_eq!(filter.list_params("U"), vec![">10"]);
+        assert_eq!(filter.list_params(""), Vec::<String>::new());
+
+        let entry = |chan: &str, n_users| ChanListEntry {
+            chan: chan.to_owned(),
+            n_users,
+            topic: String::new(),
+        };
+        assert!(filter.matches(&entry("#rust-beginners", 100)));
+        assert!(filter.matches(&entry("#Rust-Beginners", 100)));
+        assert!(!filter.matches(&entry("#rust", 10)));
+        assert!(!filter.matches(&entry("#haskell", 100)));
+    }
+}
@@ -0,0 +1,146 @@
+//! Decoding incoming messages in legacy (non-UTF-8) encodings, and encoding outgoing messages.
+
+use crate::ServerInfo;
+use libtiny_wire::MsgRef;
+
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+
+/// Encodings of a server, from `ServerInfo`.
+#[derive(Debug, Clone)]
+pub(crate) struct Encodings {
+    /// Fallback encoding for messages that are not valid UTF-8.
+    serv: Option<&'static Encoding>,
+
+    /// Fallback encodings for channels, overriding `serv`.
+    chans: Vec<(String, &'static Encoding)>,
+
+    /// Encoding of outgoing messages. `None` means UTF-8.
+    outgoing: Option<&'static Encoding>,
+}
+
+impl Encodings {
+    pub(crate) fn new(server_info: &ServerInfo) -> Encodings {
+        Encodings {
+            serv: server_info.encoding,
+            chans: server_info.chan_encodings.clone(),
+            outgoing: server_info.outgoing_encoding,
+        }
+    }
+
+    /// Decode a message, without the trailing "\r\n". Messages that are valid UTF-8 are not
+    /// decoded with the fallback encodings. Invalid sequences are replaced with U+FFFD.
+    pub(crate) fn decode<'a>(&self, line: &'a [u8]) -> Cow<'a, str> {
+        if let Ok(line) = std::str::from_utf8(line) {
+            return Cow::Borrowed(line);
+        }
+
+        match self.find_encoding(line) {
+            None => String::from_utf8_lossy(line),
+            Some(encoding) => encoding.decode_without_bom_handling(line).0,
+        }
+    }
+
+    /// Find fallback encoding of a message. To find the channel we parse the message with
+    /// invalid sequences replaced, and look for a parameter that is one of the channels with an
+    /// encoding. Channel names are case insensitive.
+    fn find_encoding(&self, line: &[u8]) -> Option<&'static Encoding> {
+        if !self.chans.is_empty() {
+            let line = String::from_utf8_lossy(line);
+            if let Some(msg) = MsgRef::parse(&line) {
+                for param in msg.params() {
+                    for (chan, encoding) in &self.chans {
+                        if chan.eq_ignore_ascii_case(param) {
+                            return Some(encoding);
+                        }
+                    }
+                }
+            }
+        }
+        self.serv
+    }
+
+    /// Encode an outgoing message. Characters that can't be encoded are replaced with HTML
+    /// numeric character references (e.g. `&#1046;`).
+    pub(crate) fn encode<'a>(&self, msg: &'a str) -> Cow<'a, [u8]> {
+        match self.outgoing {
+            None => Cow::Borrowed(msg.as_bytes()),
+            Some(encoding) => encoding.encode(msg).0,
+        }
+    }
+
+    /// Length of a message in bytes when encoded with `encode`.
+    pub(crate) fn encoded_len(&self, msg: &str) -> usize {
+        self.encode(msg).len()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{ISO_8859_15, WINDOWS_1251, WINDOWS_1252};
+
+    fn encodings(
+        serv: Option<&'static Encoding>,
+        chans: &[(&str, &'static Encoding)],
+        outgoing: Option<&'static Encoding>,
+    ) -> Encodings {
+        Encodings {
+            serv,
+            chans: chans
+                .iter()
+                .map(|(chan, encoding)| ((*chan).to_owned(), *encoding))
+                .collect(),
+            outgoing,
+        }
+    }
+
+    #[test]
+    fn test_decode_fallback() {
+        let encs = encodings(Some(WINDOWS_1252), &[], None);
+        // Valid UTF-8 is not decoded with the fallback encoding
+        assert_eq!(encs.decode("PRIVMSG #a :é".as_bytes()), "PRIVMSG #a :é");
+        assert_eq!(encs.decode(b"PRIVMSG #a :\xe9"), "PRIVMSG #a :é");
+
+        let encs = encodings(None, &[], None);
+        assert_eq!(encs.decode(b"PRIVMSG #a :\xe9"), "PRIVMSG #a :\u{FFFD}");
+    }
+
+    #[test]
+    fn test_decode_chan() {
+        let encs = encodings(Some(ISO_8859_15), &[("#Russian", WINDOWS_1251)], None);
+        assert_eq!(
+            encs.decode(b":nick!~u@h PRIVMSG #russian :\xcf\xf0\xe8\xe2\xe5\xf2"),
+            ":nick!~u@h PRIVMSG #russian :Привет"
+        );
+        assert_eq!(
+            encs.decode(b":nick!~u@h PRIVMSG #other :\xa4"),
+            ":nick!~u@h PRIVMSG #other :€"
+        );
+        // RPL_TOPIC: channel is the second parameter
+        assert_eq!(
+            encs.decode(b":serv 332 tiny #Russian :\xcf\xf0\xe8\xe2\xe5\xf2"),
+            ":serv 332 tiny #Russian :Привет"
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let encs = encodings(None, &[], None);
+        assert_eq!(
+            &*encs.encode("PRIVMSG #a :é\r\n"),
+            "PRIVMSG #a :é\r\n".as_bytes()
+        );
+
+        let encs = encodings(None, &[], Some(WINDOWS_1252));
+        assert_eq!(&*encs.encode("PRIVMSG #a :é\r\n"), b"PRIVMSG #a :\xe9\r\n");
+        assert_eq!(
+            &*encs.encode("PRIVMSG #a :Ж\r\n"),
+            b"PRIVMSG #a :&#1046;\r\n"
+        );
+        assert_eq!(encs.encoded_len("é"), 1);
+        assert_eq!(encs.encoded_len("Ж"), 7);
+    }
+}
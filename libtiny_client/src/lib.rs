@@ -3,13 +3,21 @@
 #![allow(clippy::unneeded_field_pattern)]
 #![allow(clippy::cognitive_complexity)]
 
+mod chan_list;
+mod encoding;
+mod mode_list;
 mod pinger;
 mod state;
 mod stream;
 mod utils;
+mod websocket;
 
+pub use chan_list::{ChanListEntry, ListFilter};
+pub use encoding_rs::Encoding;
 pub use libtiny_wire as wire;
+pub use mode_list::{ListMode, ModeListEntry};
 
+use encoding::Encodings;
 use pinger::Pinger;
 use state::State;
 use stream::{Stream, StreamError};
@@ -19,6 +27,7 @@ use futures::stream::StreamExt;
 use futures::{pin_mut, select};
 use futures_util::stream::Fuse;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc;
@@ -33,6 +42,9 @@ extern crate log;
 /// `Client` tries to reconnect on error after this many seconds.
 pub const RECONNECT_SECS: u64 = 30;
 
+/// Default `ServerInfo::ping_interval`, in seconds.
+pub const PING_SECS: u64 = 60;
+
 #[derive(Debug, Clone)]
 pub struct ServerInfo {
     /// Server address
@@ -61,6 +73,29 @@ pub struct ServerInfo {
 
     /// SASL authentication credentials,
     pub sasl_auth: Option<SASLAuth>,
+
+    /// Encoding to decode messages from the server with when they are not valid UTF-8. When not
+    /// set, invalid UTF-8 sequences are replaced with U+FFFD.
+    pub encoding: Option<&'static Encoding>,
+
+    /// Per-channel `encoding`s. Overrides `encoding` for messages to/about the channel.
+    pub chan_encodings: Vec<(String, &'static Encoding)>,
+
+    /// Encoding of messages sent to the server. When not set messages are sent in UTF-8.
+    pub outgoing_encoding: Option<&'static Encoding>,
+
+    /// `Client` sends a PING after not hearing from the server for this long, and disconnects
+    /// when the server doesn't respond in the same amount of time. `PING_SECS` seconds when not
+    /// set.
+    pub ping_interval: Option<Duration>,
+
+    /// Connect over WebSocket, with the given resource path (e.g. `/webirc`). Uses `wss://` when
+    /// `tls` is set, `ws://` otherwise.
+    pub websocket: Option<String>,
+
+    /// Connect to a Unix domain socket at this path instead of `addr` and `port`. No DNS lookups
+    /// are done, and `tls` and `websocket` are not used. `addr` is still used as the server name.
+    pub unix_socket: Option<PathBuf>,
 }
 
 /// SASL authentication credentials
@@ -82,6 +117,8 @@ pub enum Event {
     ResolvingHost,
     /// Domain name resolved, client trying to connect to the given IP address
     Connecting(SocketAddr),
+    /// Client trying to connect to the given Unix domain socket (`ServerInfo::unix_socket`)
+    ConnectingUnix(PathBuf),
     /// TCP connection established *and* the introduction sequence with the IRC server started.
     Connected,
     /// Disconnected from the server. Usually sent right after an `Event::IoErr`. Client tries to
@@ -97,6 +134,20 @@ pub enum Event {
     CantResolveAddr,
     /// Nick changed.
     NickChange(String),
+    /// Channel list, sent after receiving all replies to a LIST command (on 323 RPL_LISTEND).
+    /// Channels are filtered with the filter passed to `Client::list`.
+    ChanList(Vec<ChanListEntry>),
+    /// A channel mode list (e.g. ban list), sent after receiving all items of the list. See
+    /// `Client::get_mode_list`.
+    ModeList {
+        chan: String,
+        mode: ListMode,
+        entries: Vec<ModeListEntry>,
+    },
+    /// A message sent to or received from the server, as sent over the wire (without the trailing
+    /// "\r\n"). Only sent when enabled with `Client::set_raw_msg_events`. Received messages are
+    /// sent before the `Msg` events for them.
+    RawMsg { msg: String, outgoing: bool },
     /// A message from the server
     Msg(wire::Msg),
 
@@ -114,7 +165,7 @@ impl From<StreamError> for Event {
     }
 }
 
-/// IRC client.
+/// IRC client. `Client` is `Send` and `Sync`, and can be cloned to be used in multiple threads.
 #[derive(Clone)]
 pub struct Client {
     /// Channel to the send commands to the main loop. Usually just for sending messages to the
@@ -128,6 +179,10 @@ pub struct Client {
     /// Reference to the state, to be able to provide methods like `get_nick` and
     /// `is_nick_accepted`.
     state: State,
+
+    /// Encodings of the server, to check lengths of outgoing messages
+    encodings: Encodings,
+
     // We can't have a channel to the sender task directly here, because when the sender task
     // returns we lose the receiving end of the channel and there's no way to avoid this except
     // with annoying hacks like wrapping it with an `Arc<Mutex<..>>` or something.
@@ -135,8 +190,9 @@ pub struct Client {
 }
 
 impl Client {
-    /// Create a new client. Spawns two `tokio` tasks on the given `runtime`. If not given, tasks
-    /// are created on the default executor using `tokio::spawn`.
+    /// Create a new client. Spawns the client tasks on the default executor using `tokio::spawn`,
+    /// so this needs to be called in a `tokio` runtime. Both the single-threaded (`current_thread`)
+    /// and the threaded runtimes are supported.
     pub fn new(server_info: ServerInfo) -> (Client, mpsc::Receiver<Event>) {
         connect(server_info)
     }
@@ -165,6 +221,11 @@ impl Client {
         self.state.is_nick_accepted()
     }
 
+    /// Is SASL authentication configured for this server?
+    pub fn has_sasl_auth(&self) -> bool {
+        self.state.has_sasl_auth()
+    }
+
     /// Send a message directly to the server. "\r\n" suffix is added by this method.
     pub fn raw_msg(&mut self, msg: &str) {
         self.msg_chan
@@ -172,6 +233,17 @@ impl Client {
             .unwrap();
     }
 
+    /// Enable or disable `Event::RawMsg` events for messages sent to and received from the
+    /// server. Disabled by default.
+    pub fn set_raw_msg_events(&mut self, enabled: bool) {
+        self.state.set_raw_msg_events(enabled);
+    }
+
+    /// Are `Event::RawMsg` events enabled?
+    pub fn raw_msg_events(&self) -> bool {
+        self.state.raw_msg_events()
+    }
+
     /// Split a privmsg to multiple messages so that each message is, when the hostname and nick
     /// prefix added by the server, fits in one IRC message.
     ///
@@ -202,46 +274,187 @@ impl Client {
 
         assert!(max > 0);
 
-        utils::split_iterator(msg, max)
+        // Split by length in the outgoing encoding
+        let encodings = self.encodings.clone();
+        utils::split_iterator(msg, max, move |s| encodings.encoded_len(s))
     }
 
     /// Send a privmsg. Note that this method does not split long messages into smaller messages;
-    /// use `split_privmsg` for that.
-    pub fn privmsg(&mut self, target: &str, msg: &str, is_action: bool) {
+    /// use `split_privmsg` for that. Fails when the message is too long or can't be sent in a
+    /// single IRC message (e.g. has a newline).
+    pub fn privmsg(
+        &mut self,
+        target: &str,
+        msg: &str,
+        is_action: bool,
+    ) -> Result<(), wire::EncodeError> {
         let wire_fn = if is_action {
             wire::action
         } else {
             wire::privmsg
         };
-        self.msg_chan
-            .try_send(Cmd::Msg(wire_fn(target, msg)))
-            .unwrap();
+        self.send_msg(wire_fn(target, msg)?)
+    }
+
+    /// Send a reply (IRCv3 `+draft/reply`) to the message with the given `msgid`. Like `privmsg`,
+    /// this method does not split long messages.
+    pub fn reply(&mut self, target: &str, msgid: &str, msg: &str) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::reply(target, msgid, msg)?)
+    }
+
+    /// React (IRCv3 `+draft/react`) to the message with the given `msgid`.
+    pub fn react(
+        &mut self,
+        target: &str,
+        msgid: &str,
+        reaction: &str,
+    ) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::react(target, msgid, reaction)?)
+    }
+
+    /// Get `msgid` of the last message sent by `nick` in the given channel or privmsg
+    /// conversation (`target` is the nick in privmsg conversations). Only available when the
+    /// server supports the `message-tags` capability.
+    pub fn get_last_msgid(&self, target: &str, nick: &str) -> Option<String> {
+        self.state.get_last_msgid(target, nick)
     }
 
     /// Join the given list of channels.
-    pub fn join(&mut self, chans: &[&str]) {
-        self.msg_chan
-            .try_send(Cmd::Msg(wire::join(&chans)))
-            .unwrap()
+    pub fn join(&mut self, chans: &[&str]) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::join(&chans)?)
+    }
+
+    /// Request a channel list. The list is sent as an `Event::ChanList` when all channels are
+    /// received. Filters supported by the server are sent to the server, others are applied by
+    /// the client.
+    pub fn list(&mut self, filter: &ListFilter) -> Result<(), wire::EncodeError> {
+        let msg = self.state.list(filter)?;
+        self.send_msg(msg)
+    }
+
+    /// Get value of an ISUPPORT (005 RPL_ISUPPORT) token, e.g. `get_isupport("CHANTYPES")`. Tokens
+    /// without a value return an empty string.
+    pub fn get_isupport(&self, token: &str) -> Option<String> {
+        self.state.get_isupport(token)
+    }
+
+    /// Request a channel mode list (e.g. ban list). The list is sent as an `Event::ModeList` when
+    /// all items are received.
+    pub fn get_mode_list(&mut self, chan: &str, mode: ListMode) -> Result<(), wire::EncodeError> {
+        let modes = format!("+{}", mode.mode_char());
+        self.send_msg(wire::mode(chan, &modes, &[])?)
+    }
+
+    /// Set channel or user modes, e.g. `mode("#tiny", "+b", &["*!*@example.com"])`. Requests
+    /// current modes of the target when `modes` is empty.
+    pub fn mode(
+        &mut self,
+        target: &str,
+        modes: &str,
+        params: &[&str],
+    ) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::mode(target, modes, params)?)
+    }
+
+    /// Add or remove a channel mode with a parameter for each of the parameters, e.g.
+    /// `set_modes("#tiny", true, 'o', &["nick1", "nick2"])` gives ops to `nick1` and `nick2`.
+    /// Modes are batched into as few MODE messages as the server allows (MODES ISUPPORT token).
+    pub fn set_modes(
+        &mut self,
+        chan: &str,
+        add: bool,
+        mode: char,
+        params: &[&str],
+    ) -> Result<(), wire::EncodeError> {
+        let max_modes = ::std::cmp::max(self.state.max_modes(), 1);
+        // Encode all messages first so that nothing is sent when one of the parameters is invalid
+        let mut msgs = vec![];
+        for params in params.chunks(max_modes) {
+            let mut modes = String::with_capacity(params.len() + 1);
+            modes.push(if add { '+' } else { '-' });
+            modes.extend(params.iter().map(|_| mode));
+            let msg = wire::mode(chan, &modes, params)?;
+            self.check_encoded_len(&msg)?;
+            msgs.push(msg);
+        }
+        for msg in msgs {
+            self.msg_chan.try_send(Cmd::Msg(msg)).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Set topic of a channel. When `topic` is `None` the current topic is requested from the
+    /// server.
+    pub fn topic(&mut self, chan: &str, topic: Option<&str>) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::topic(chan, topic)?)
+    }
+
+    /// Invite a user to a channel.
+    pub fn invite(&mut self, nick: &str, chan: &str) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::invite(nick, chan)?)
+    }
+
+    /// Kick a user from a channel.
+    pub fn kick(
+        &mut self,
+        chan: &str,
+        nick: &str,
+        reason: Option<&str>,
+    ) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::kick(chan, nick, reason)?)
+    }
+
+    /// Get `user@host` of a nick, if known. `user@host`s are collected from messages of the user,
+    /// and WHO and WHOIS replies.
+    pub fn get_user_host(&self, nick: &str) -> Option<String> {
+        self.state.get_user_host(nick)
     }
 
     /// Leave a channel.
-    pub fn part(&mut self, chan: &str) {
-        self.msg_chan.try_send(Cmd::Msg(wire::part(chan))).unwrap();
+    pub fn part(&mut self, chan: &str) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::part(chan)?)
     }
 
     /// Set away status. `None` means not away.
-    pub fn away(&mut self, msg: Option<&str>) {
+    pub fn away(&mut self, msg: Option<&str>) -> Result<(), wire::EncodeError> {
+        let away_msg = wire::away(msg)?;
+        self.check_encoded_len(&away_msg)?;
         self.state.set_away(msg);
-        self.msg_chan.try_send(Cmd::Msg(wire::away(msg))).unwrap()
+        self.msg_chan.try_send(Cmd::Msg(away_msg)).unwrap();
+        Ok(())
+    }
+
+    /// Send a message generated with `wire`, after checking its length in the outgoing encoding.
+    fn send_msg(&mut self, msg: String) -> Result<(), wire::EncodeError> {
+        self.check_encoded_len(&msg)?;
+        self.msg_chan.try_send(Cmd::Msg(msg)).unwrap();
+        Ok(())
+    }
+
+    /// `wire` checks length of a message in UTF-8, but characters may be longer in the outgoing
+    /// encoding, e.g. when they're sent as HTML character references. Tags are not included in
+    /// the limit.
+    fn check_encoded_len(&self, msg: &str) -> Result<(), wire::EncodeError> {
+        let msg = if msg.starts_with('@') {
+            msg.split_once(' ').map_or("", |(_, msg)| msg)
+        } else {
+            msg
+        };
+        let len = self.encodings.encoded_len(msg);
+        if len > wire::MAX_MSG_LEN {
+            Err(wire::EncodeError::TooLong {
+                len,
+                max: wire::MAX_MSG_LEN,
+            })
+        } else {
+            Ok(())
+        }
     }
 
     /// Change nick. This may fail (ERR_NICKNAMEINUSE) so wait for confirmation (a NICK message
     /// back from the server, with the old nick as prefix).
-    pub fn nick(&mut self, new_nick: &str) {
-        self.msg_chan
-            .try_send(Cmd::Msg(wire::nick(new_nick)))
-            .unwrap()
+    pub fn nick(&mut self, new_nick: &str) -> Result<(), wire::EncodeError> {
+        self.send_msg(wire::nick(new_nick)?)
     }
 
     /// Send a QUIT message to the server, with optional "reason". This stops the client; so the
@@ -257,6 +470,12 @@ impl Client {
     pub fn get_chan_nicks(&self, chan: &str) -> Vec<String> {
         self.state.get_chan_nicks(chan)
     }
+
+    /// Are we in the given channel? Channels in `ServerInfo::auto_join` are considered joined
+    /// before joining.
+    pub fn is_on_chan(&self, chan: &str) -> bool {
+        self.state.is_on_chan(chan)
+    }
 }
 
 //
@@ -293,17 +512,19 @@ fn connect(server_info: ServerInfo) -> (Client, mpsc::Receiver<Event>) {
     // Create the main loop task
     //
 
+    let encodings = Encodings::new(&server_info);
     let irc_state = State::new(server_info.clone());
     let irc_state_clone = irc_state.clone();
 
     let task = main_loop(server_info, irc_state_clone, snd_ev, rcv_cmd);
-    tokio::runtime::current_thread::spawn(task);
+    tokio::spawn(task);
 
     (
         Client {
             msg_chan: snd_cmd,
             serv_name,
             state: irc_state,
+            encodings,
             snd_ev: snd_ev_clone,
         },
         rcv_ev,
@@ -326,6 +547,8 @@ async fn main_loop(
     // Whether to wait before trying to (re)connect
     let mut wait = false;
 
+    let encodings = Encodings::new(&server_info);
+
     // Main loop just tries to (re)connect
     'connect: loop {
         if wait {
@@ -353,65 +576,92 @@ async fn main_loop(
         // trailing "\r\n") and the task directly sends them to the server.
         let (mut snd_msg, mut rcv_msg) = mpsc::channel::<String>(100);
 
-        //
-        // Resolve IP address
-        //
-
-        snd_ev.send(Event::ResolvingHost).await.unwrap();
-
-        let serv_name = server_info.addr.clone();
+        let stream = if let Some(path) = &server_info.unix_socket {
+            // Unix domain sockets don't need resolving
+            snd_ev
+                .send(Event::ConnectingUnix(path.clone()))
+                .await
+                .unwrap();
+            match Stream::new_unix(path).await {
+                Err(err) => {
+                    snd_ev.send(Event::from(err)).await.unwrap();
+                    snd_ev.send(Event::Disconnected).await.unwrap();
+                    wait = true;
+                    continue;
+                }
+                Ok(stream) => stream,
+            }
+        } else {
+            //
+            // Resolve IP address
+            //
 
-        debug!("Resolving address");
+            snd_ev.send(Event::ResolvingHost).await.unwrap();
 
-        let serv_name_clone = serv_name.clone();
+            let serv_name = server_info.addr.clone();
 
-        let addr_iter = match resolve_addr(serv_name_clone, port, &mut rcv_cmd, &mut snd_ev).await {
-            Done(addr_iter) => {
-                debug!("resolve_addr: done");
-                addr_iter
-            }
-            TryWithPort(new_port) => {
-                debug!("resolve_addr: try new port");
-                port = new_port;
-                wait = false;
-                continue;
-            }
-            TryReconnect => {
-                debug!("resolve_addr: try again");
-                wait = false;
-                continue;
-            }
-            TryAfterDelay => {
-                debug!("resolve_addr: try after delay");
-                wait = true;
-                continue;
-            }
-            Return => {
-                debug!("resolve_addr: return");
-                return;
-            }
-        };
+            debug!("Resolving address");
 
-        let addrs = addr_iter.collect::<Vec<_>>();
+            let serv_name_clone = serv_name.clone();
 
-        if addrs.is_empty() {
-            snd_ev.send(Event::CantResolveAddr).await.unwrap();
-            break; // returns
-        }
+            let addr_iter =
+                match resolve_addr(serv_name_clone, port, &mut rcv_cmd, &mut snd_ev).await {
+                    Done(addr_iter) => {
+                        debug!("resolve_addr: done");
+                        addr_iter
+                    }
+                    TryWithPort(new_port) => {
+                        debug!("resolve_addr: try new port");
+                        port = new_port;
+                        wait = false;
+                        continue;
+                    }
+                    TryReconnect => {
+                        debug!("resolve_addr: try again");
+                        wait = false;
+                        continue;
+                    }
+                    TryAfterDelay => {
+                        debug!("resolve_addr: try after delay");
+                        wait = true;
+                        continue;
+                    }
+                    Return => {
+                        debug!("resolve_addr: return");
+                        return;
+                    }
+                };
 
-        debug!("Address resolved: {:?}", addrs);
+            let addrs = addr_iter.collect::<Vec<_>>();
 
-        //
-        // Establish TCP connection to the server
-        //
+            if addrs.is_empty() {
+                snd_ev.send(Event::CantResolveAddr).await.unwrap();
+                break; // returns
+            }
 
-        let stream = match try_connect(addrs, &serv_name, server_info.tls, &mut snd_ev).await {
-            None => {
-                snd_ev.send(Event::Disconnected).await.unwrap();
-                wait = true;
-                continue;
+            debug!("Address resolved: {:?}", addrs);
+
+            //
+            // Establish TCP connection to the server
+            //
+
+            match try_connect(
+                addrs,
+                &serv_name,
+                port,
+                server_info.tls,
+                server_info.websocket.as_deref(),
+                &mut snd_ev,
+            )
+            .await
+            {
+                None => {
+                    snd_ev.send(Event::Disconnected).await.unwrap();
+                    wait = true;
+                    continue;
+                }
+                Some(stream) => stream,
             }
-            Some(stream) => stream,
         };
 
         let (mut read_half, mut write_half) = tokio::io::split(stream);
@@ -424,21 +674,35 @@ async fn main_loop(
 
         // Reset the connection state
         irc_state.reset();
-        // Introduce self
-        if server_info.sasl_auth.is_some() {
-            // Will introduce self after getting a response to this LS command.
-            // This is to avoid getting stuck during nick registration. See the
-            // discussion in #91.
-            snd_msg.try_send(wire::cap_ls()).unwrap();
-        } else {
+        // Introduce self. Capabilities (message-tags, sasl) are requested after getting a
+        // response to this LS command.
+        snd_msg.try_send(wire::cap_ls()).unwrap();
+        if server_info.sasl_auth.is_none() {
+            // With SASL we introduce self after getting the LS response. This is to avoid getting
+            // stuck during nick registration. See the discussion in #91.
             irc_state.introduce(&mut snd_msg);
         }
 
         // Spawn a task for outgoing messages.
         let mut snd_ev_clone = snd_ev.clone();
-        tokio::runtime::current_thread::spawn(async move {
+        let encodings_clone = encodings.clone();
+        let irc_state_clone = irc_state.clone();
+        tokio::spawn(async move {
             while let Some(msg) = rcv_msg.next().await {
-                if let Err(io_err) = write_half.write_all(msg.as_str().as_bytes()).await {
+                if irc_state_clone.raw_msg_events() {
+                    let raw_msg = Event::RawMsg {
+                        msg: msg.trim_end_matches(&['\r', '\n'][..]).to_owned(),
+                        outgoing: true,
+                    };
+                    snd_ev_clone.send(raw_msg).await.unwrap();
+                }
+                let msg = encodings_clone.encode(&msg);
+                // Flushing is needed for WebSocket streams, which buffer frames
+                let write_res = match write_half.write_all(&msg).await {
+                    Ok(()) => write_half.flush().await,
+                    Err(err) => Err(err),
+                };
+                if let Err(io_err) = write_res {
                     debug!("IO error when writing: {:?}", io_err);
                     snd_ev_clone.send(Event::IoErr(io_err)).await.unwrap();
                     return;
@@ -447,7 +711,11 @@ async fn main_loop(
         });
 
         // Spawn pinger task
-        let (mut pinger, rcv_ping_evs) = Pinger::new();
+        let (mut pinger, rcv_ping_evs) = Pinger::new(
+            server_info
+                .ping_interval
+                .unwrap_or_else(|| Duration::from_secs(PING_SECS)),
+        );
         let mut rcv_ping_evs = rcv_ping_evs.fuse();
 
         let mut parse_buf: Vec<u8> = Vec::with_capacity(1024);
@@ -473,7 +741,10 @@ async fn main_loop(
                             continue 'connect;
                         }
                         Some(Cmd::Quit(reason)) => {
-                            snd_msg.try_send(wire::quit(reason)).unwrap();
+                            // Quit without a reason when the reason can't be sent
+                            let msg = wire::quit(reason.as_deref())
+                                .unwrap_or_else(|_| wire::quit(None).unwrap());
+                            snd_msg.try_send(msg).unwrap();
                             // This drops the sender end of the channel that the sender task
                             // uses, which in turn causes the sender task to return. Somewhat
                             // hacky?
@@ -502,7 +773,16 @@ async fn main_loop(
                         }
                         Ok(bytes) => {
                             parse_buf.extend_from_slice(&read_buf[0..bytes]);
-                            while let Some(mut msg) = wire::parse_irc_msg(&mut parse_buf) {
+                            if irc_state.raw_msg_events() {
+                                for msg in raw_msgs(&parse_buf, &encodings) {
+                                    let raw_msg = Event::RawMsg { msg, outgoing: false };
+                                    snd_ev.send(raw_msg).await.unwrap();
+                                }
+                            }
+                            while let Some(mut msg) = wire::parse_irc_msg_with(
+                                &mut parse_buf,
+                                |line| encodings.decode(line),
+                            ) {
                                 debug!("parsed msg: {:?}", msg);
                                 pinger.reset();
                                 irc_state.update(&mut msg, &mut snd_ev, &mut snd_msg);
@@ -533,6 +813,17 @@ async fn main_loop(
     }
 }
 
+/// Complete messages in a read buffer, decoded, without the trailing "\r\n"s.
+fn raw_msgs(buf: &[u8], encodings: &Encodings) -> Vec<String> {
+    let mut msgs = vec![];
+    let mut buf = buf;
+    while let Some(crlf_idx) = buf.windows(2).position(|sub| sub == b"\r\n") {
+        msgs.push(encodings.decode(&buf[..crlf_idx]).into_owned());
+        buf = &buf[crlf_idx + 2..];
+    }
+    msgs
+}
+
 enum TaskResult<A> {
     Done(A),
     TryWithPort(u16),
@@ -637,12 +928,16 @@ async fn resolve_addr(
 async fn try_connect(
     addrs: Vec<SocketAddr>,
     serv_name: &str,
+    port: u16,
     use_tls: bool,
+    websocket: Option<&str>,
     snd_ev: &mut mpsc::Sender<Event>,
 ) -> Option<Stream> {
     for addr in addrs {
         snd_ev.send(Event::Connecting(addr)).await.unwrap();
-        let mb_stream = if use_tls {
+        let mb_stream = if let Some(path) = websocket {
+            Stream::new_ws(addr, serv_name, port, path, use_tls).await
+        } else if use_tls {
             Stream::new_tls(addr, &serv_name).await
         } else {
             Stream::new_tcp(addr).await
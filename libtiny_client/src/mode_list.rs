@@ -0,0 +1,153 @@
+//! Channel mode lists: bans (+b), quiets (+q), ban exceptions (+e) and invite exceptions (+I).
+//! See `Client::get_mode_list`.
+
+/// A channel mode with a list of masks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ListMode {
+    /// +b, replies 367 RPL_BANLIST and 368 RPL_ENDOFBANLIST
+    Ban,
+    /// +q, replies 728 RPL_QUIETLIST and 729 RPL_ENDOFQUIETLIST
+    Quiet,
+    /// +e, replies 348 RPL_EXCEPTLIST and 349 RPL_ENDOFEXCEPTLIST
+    Except,
+    /// +I, replies 346 RPL_INVEXLIST and 347 RPL_ENDOFINVEXLIST
+    Invex,
+}
+
+impl ListMode {
+    pub fn mode_char(self) -> char {
+        match self {
+            ListMode::Ban => 'b',
+            ListMode::Quiet => 'q',
+            ListMode::Except => 'e',
+            ListMode::Invex => 'I',
+        }
+    }
+
+    pub fn from_mode_char(c: char) -> Option<ListMode> {
+        match c {
+            'b' => Some(ListMode::Ban),
+            'q' => Some(ListMode::Quiet),
+            'e' => Some(ListMode::Except),
+            'I' => Some(ListMode::Invex),
+            _ => None,
+        }
+    }
+
+    /// Human readable name of the list, e.g. "ban list".
+    pub fn name(self) -> &'static str {
+        match self {
+            ListMode::Ban => "ban list",
+            ListMode::Quiet => "quiet list",
+            ListMode::Except => "ban exception list",
+            ListMode::Invex => "invite exception list",
+        }
+    }
+
+    /// The list item and end of list replies of the mode.
+    fn replies(self) -> (u16, u16) {
+        match self {
+            ListMode::Ban => (367, 368),
+            ListMode::Quiet => (728, 729),
+            ListMode::Except => (348, 349),
+            ListMode::Invex => (346, 347),
+        }
+    }
+
+    /// Returns the mode and whether the reply is an item (`true`) or end of the list (`false`).
+    pub(crate) fn from_reply(num: u16) -> Option<(ListMode, bool)> {
+        [
+            ListMode::Ban,
+            ListMode::Quiet,
+            ListMode::Except,
+            ListMode::Invex,
+        ]
+        .iter()
+        .find_map(|mode| {
+            let (item, end) = mode.replies();
+            if num == item {
+                Some((*mode, true))
+            } else if num == end {
+                Some((*mode, false))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// An item in a channel mode list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeListEntry {
+    pub mask: String,
+    /// Who added the mask, when sent by the server
+    pub set_by: Option<String>,
+    /// When the mask was added (seconds since the Unix epoch), when sent by the server
+    pub set_at: Option<i64>,
+}
+
+/// Parse a mode list item reply:
+///
+/// - `<client> <channel> <mask> [<who> <set-ts>]` (367, 348, 346)
+/// - `<client> <channel> <mode char> <mask> [<who> <set-ts>]` (728)
+pub(crate) fn parse_mode_list_reply(mode: ListMode, params: &[String]) -> Option<ModeListEntry> {
+    let params = if mode == ListMode::Quiet {
+        params.get(3..)?
+    } else {
+        params.get(2..)?
+    };
+    Some(ModeListEntry {
+        mask: params.first()?.to_owned(),
+        set_by: params.get(1).cloned(),
+        set_at: params.get(2).and_then(|ts| ts.parse().ok()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(params: &[&str]) -> Vec<String> {
+        params.iter().map(|s| (*s).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_parse_mode_list_reply() {
+        assert_eq!(ListMode::from_reply(728), Some((ListMode::Quiet, true)));
+        assert_eq!(ListMode::from_reply(347), Some((ListMode::Invex, false)));
+        assert_eq!(ListMode::from_reply(366), None);
+
+        assert_eq!(
+            parse_mode_list_reply(
+                ListMode::Ban,
+                &params(&[
+                    "tiny",
+                    "#tiny",
+                    "*!*@spam.example.com",
+                    "osa1",
+                    "1571300000"
+                ])
+            ),
+            Some(ModeListEntry {
+                mask: "*!*@spam.example.com".to_owned(),
+                set_by: Some("osa1".to_owned()),
+                set_at: Some(1571300000),
+            })
+        );
+        assert_eq!(
+            parse_mode_list_reply(
+                ListMode::Quiet,
+                &params(&["tiny", "#tiny", "q", "troll!*@*"])
+            ),
+            Some(ModeListEntry {
+                mask: "troll!*@*".to_owned(),
+                set_by: None,
+                set_at: None,
+            })
+        );
+        assert_eq!(
+            parse_mode_list_reply(ListMode::Quiet, &params(&["tiny", "#tiny", "q"])),
+            None
+        );
+    }
+}
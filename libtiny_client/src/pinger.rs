@@ -24,7 +24,11 @@ enum PingerState {
     ExpectPong,
 }
 
-async fn pinger_task(rcv_rst: mpsc::Receiver<()>, mut snd_ev: mpsc::Sender<Event>) {
+async fn pinger_task(
+    interval: Duration,
+    rcv_rst: mpsc::Receiver<()>,
+    mut snd_ev: mpsc::Sender<Event>,
+) {
     let mut rcv_rst_fused = rcv_rst.fuse();
     let mut state = PingerState::SendPing;
     loop {
@@ -32,7 +36,7 @@ async fn pinger_task(rcv_rst: mpsc::Receiver<()>, mut snd_ev: mpsc::Sender<Event
         // let mut delay = delay_for(Duration::from_secs(30));
         // Instead I need this weird code below. Not sure if this is a bug or not.
         let delay = async {
-            delay_for(Duration::from_secs(60)).await;
+            delay_for(interval).await;
         }
         .fuse();
         pin_mut!(delay);
@@ -65,11 +69,11 @@ async fn pinger_task(rcv_rst: mpsc::Receiver<()>, mut snd_ev: mpsc::Sender<Event
 }
 
 impl Pinger {
-    pub(crate) fn new() -> (Pinger, mpsc::Receiver<Event>) {
+    pub(crate) fn new(interval: Duration) -> (Pinger, mpsc::Receiver<Event>) {
         let (snd_ev, rcv_ev) = mpsc::channel(1);
         // No need for sending another "reset" when there's already one waiting to be processed
         let (snd_rst, rcv_rst) = mpsc::channel(1);
-        tokio::runtime::current_thread::spawn(pinger_task(rcv_rst, snd_ev));
+        tokio::spawn(pinger_task(interval, rcv_rst, snd_ev));
         (Pinger { snd_rst }, rcv_ev)
     }
 
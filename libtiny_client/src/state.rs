@@ -1,33 +1,40 @@
 #![allow(clippy::zero_prefixed_literal)]
 
+use crate::chan_list::{self, ChanListEntry, ListFilter};
+use crate::mode_list::{self, ListMode, ModeListEntry};
 use crate::utils;
 use crate::{Event, ServerInfo};
 use libtiny_wire as wire;
 use libtiny_wire::{find_byte, Msg, Pfx};
 
-use std::cell::RefCell;
-use std::collections::HashSet;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
 use tokio::sync::mpsc::Sender;
 
+/// Connection state, shared by the main loop and `Client`s. `Client` is `Send` and `Sync` so this
+/// is behind a `Mutex`. The lock is never held across `await`s.
 #[derive(Clone)]
 pub struct State {
-    inner: Rc<RefCell<StateInner>>,
+    inner: Arc<Mutex<StateInner>>,
 }
 
 impl State {
     pub(crate) fn new(server_info: ServerInfo) -> State {
         State {
-            inner: Rc::new(RefCell::new(StateInner::new(server_info))),
+            inner: Arc::new(Mutex::new(StateInner::new(server_info))),
         }
     }
 
+    fn lock(&self) -> MutexGuard<'_, StateInner> {
+        self.inner.lock().unwrap()
+    }
+
     pub(crate) fn reset(&self) {
-        self.inner.borrow_mut().reset()
+        self.lock().reset()
     }
 
     pub(crate) fn send_ping(&self, snd_irc_msg: &mut Sender<String>) {
-        self.inner.borrow_mut().send_ping(snd_irc_msg)
+        self.lock().send_ping(snd_irc_msg)
     }
 
     pub(crate) fn update(
@@ -36,33 +43,89 @@ impl State {
         snd_ev: &mut Sender<Event>,
         snd_irc_msg: &mut Sender<String>,
     ) {
-        self.inner.borrow_mut().update(msg, snd_ev, snd_irc_msg);
+        self.lock().update(msg, snd_ev, snd_irc_msg);
     }
 
     pub(crate) fn introduce(&self, snd_irc_msg: &mut Sender<String>) {
-        self.inner.borrow_mut().introduce(snd_irc_msg)
+        self.lock().introduce(snd_irc_msg)
     }
 
     // FIXME: This allocates a new String
     pub(crate) fn get_nick(&self) -> String {
-        self.inner.borrow().current_nick.clone()
+        self.lock().current_nick.clone()
     }
 
     // FIXME: Maybe use RwLock instead of Mutex
     pub(crate) fn is_nick_accepted(&self) -> bool {
-        self.inner.borrow().nick_accepted
+        self.lock().nick_accepted
     }
 
     pub(crate) fn get_usermask(&self) -> Option<String> {
-        self.inner.borrow().usermask.clone()
+        self.lock().usermask.clone()
     }
 
     pub(crate) fn set_away(&self, msg: Option<&str>) {
-        self.inner.borrow_mut().away_status = msg.map(str::to_owned);
+        self.lock().away_status = msg.map(str::to_owned);
     }
 
     pub(crate) fn get_chan_nicks(&self, chan: &str) -> Vec<String> {
-        self.inner.borrow().get_chan_nicks(chan)
+        self.lock().get_chan_nicks(chan)
+    }
+
+    pub(crate) fn is_on_chan(&self, chan: &str) -> bool {
+        self.lock().chans.iter().any(|(chan_, _)| chan_ == chan)
+    }
+
+    pub(crate) fn get_isupport(&self, token: &str) -> Option<String> {
+        self.lock().isupport.get(token).cloned()
+    }
+
+    /// Max. number of modes with a parameter in a MODE command (MODES ISUPPORT token).
+    pub(crate) fn max_modes(&self) -> usize {
+        match self.lock().isupport.get("MODES") {
+            // RFC 1459 limit
+            None => 3,
+            // No limit
+            Some(n) if n.is_empty() => usize::MAX,
+            Some(n) => n.parse().unwrap_or(3),
+        }
+    }
+
+    /// Start a channel list. Returns the LIST message to send.
+    pub(crate) fn list(&self, filter: &ListFilter) -> Result<String, wire::EncodeError> {
+        let mut inner = self.lock();
+        let elist = inner
+            .isupport
+            .get("ELIST")
+            .map(String::as_str)
+            .unwrap_or("");
+        let params = filter.list_params(elist);
+        let msg = wire::list(&params.iter().map(String::as_str).collect::<Vec<_>>())?;
+        inner.chan_list = Some((filter.clone(), vec![]));
+        Ok(msg)
+    }
+
+    pub(crate) fn get_user_host(&self, nick: &str) -> Option<String> {
+        self.lock().user_hosts.get(nick).cloned()
+    }
+
+    pub(crate) fn raw_msg_events(&self) -> bool {
+        self.lock().raw_msg_events
+    }
+
+    pub(crate) fn set_raw_msg_events(&self, enabled: bool) {
+        self.lock().raw_msg_events = enabled;
+    }
+
+    pub(crate) fn has_sasl_auth(&self) -> bool {
+        self.lock().server_info.sasl_auth.is_some()
+    }
+
+    pub(crate) fn get_last_msgid(&self, target: &str, nick: &str) -> Option<String> {
+        self.lock()
+            .last_msgids
+            .get(&(target.to_owned(), nick.to_owned()))
+            .cloned()
     }
 }
 
@@ -106,6 +169,30 @@ struct StateInner {
     /// Do we have a nick yet? Try another nick on ERR_NICKNAMEINUSE (433) until we've got a nick.
     nick_accepted: bool,
 
+    /// `msgid` of the last message of a nick in a channel or privmsg conversation, to be able to
+    /// reply and react to it. Keys are (channel or nick, sender nick). Only populated when the
+    /// server supports the `message-tags` capability.
+    last_msgids: HashMap<(String, String), String>,
+
+    /// ISUPPORT (005 RPL_ISUPPORT) tokens. Tokens without a value are mapped to an empty string.
+    isupport: HashMap<String, String>,
+
+    /// Filter of the last LIST command and the channels in the LIST replies received so far.
+    /// `None` when not receiving LIST replies.
+    chan_list: Option<(ListFilter, Vec<ChanListEntry>)>,
+
+    /// `user@host`s of nicks, collected from message prefixes and WHO and WHOIS replies. Used
+    /// to generate ban masks. Nicks are not removed on QUIT or PART, to be able to ban users who
+    /// left.
+    user_hosts: HashMap<String, String>,
+
+    /// Channel mode lists (e.g. ban lists) being received. Sent as `Event::ModeList` on the end
+    /// of list reply.
+    mode_lists: HashMap<(String, ListMode), Vec<ModeListEntry>>,
+
+    /// Send `Event::RawMsg`s for incoming and outgoing messages? Not reset on reconnect.
+    raw_msg_events: bool,
+
     /// Server information
     server_info: ServerInfo,
 }
@@ -128,6 +215,12 @@ impl StateInner {
             servername: None,
             usermask: None,
             nick_accepted: false,
+            last_msgids: HashMap::new(),
+            isupport: HashMap::new(),
+            chan_list: None,
+            user_hosts: HashMap::new(),
+            mode_lists: HashMap::new(),
+            raw_msg_events: false,
             server_info,
         }
     }
@@ -143,24 +236,28 @@ impl StateInner {
         }
         self.servername = None;
         self.usermask = None;
+        self.last_msgids.clear();
+        self.isupport.clear();
+        self.chan_list = None;
+        self.user_hosts.clear();
+        self.mode_lists.clear();
     }
 
     fn send_ping(&mut self, snd_irc_msg: &mut Sender<String>) {
         if let Some(ref servername) = self.servername {
-            snd_irc_msg.try_send(wire::ping(servername)).unwrap();
+            send_msg(snd_irc_msg, wire::ping(servername));
         }
     }
 
     fn introduce(&mut self, snd_irc_msg: &mut Sender<String>) {
         if let Some(ref pass) = self.server_info.pass {
-            snd_irc_msg.try_send(wire::pass(pass)).unwrap();
+            send_msg(snd_irc_msg, wire::pass(pass));
         }
-        snd_irc_msg
-            .try_send(wire::nick(&self.current_nick))
-            .unwrap();
-        snd_irc_msg
-            .try_send(wire::user(&self.nicks[0], &self.server_info.realname))
-            .unwrap();
+        send_msg(snd_irc_msg, wire::nick(&self.current_nick));
+        send_msg(
+            snd_irc_msg,
+            wire::user(&self.nicks[0], &self.server_info.realname),
+        );
     }
 
     fn get_next_nick(&mut self) -> &str {
@@ -186,47 +283,77 @@ impl StateInner {
         snd_irc_msg: &mut Sender<String>,
     ) {
         let Msg {
+            ref tags,
             ref pfx,
             ref mut cmd,
         } = msg;
 
+        if let Some(Pfx::User { nick, user }) = pfx {
+            if user.contains('@') {
+                self.user_hosts.insert(nick.to_owned(), user.to_owned());
+            }
+        }
+
         use wire::Cmd::*;
         match cmd {
             PING { server } => {
-                snd_irc_msg.try_send(wire::pong(server)).unwrap();
+                send_msg(snd_irc_msg, wire::pong(server));
+            }
+
+            //
+            // Remember msgids of messages to be able to reply and react to them
+            //
+            PRIVMSG { targets, .. } => {
+                if let (Some(Pfx::User { nick, .. }), Some(msgid)) =
+                    (pfx, wire::get_tag(tags, "msgid"))
+                {
+                    for target in targets {
+                        let target = match target {
+                            wire::MsgTarget::Chan(chan) => chan,
+                            wire::MsgTarget::User(_) => nick,
+                        };
+                        self.last_msgids
+                            .insert((target.to_owned(), nick.to_owned()), msgid.to_owned());
+                    }
+                }
             }
 
             //
             // Setting usermask using JOIN, RPL_USERHOST and 396 (?)
             // Also initialize the channel state on JOIN
             //
-            JOIN { chan } => {
+            JOIN { chans, .. } => {
                 if let Some(Pfx::User { nick, user }) = pfx {
                     if nick == &self.current_nick {
                         // Set usermask
                         let usermask = format!("{}!{}", nick, user);
                         self.usermask = Some(usermask);
 
-                        // Initialize channel state
-                        match utils::find_idx(&self.chans, |(s, _)| s == chan) {
-                            None => {
-                                self.chans.push((chan.to_owned(), HashSet::new()));
-                            }
-                            Some(chan_idx) => {
-                                // This happens because we initialize channel states for channels
-                                // that we will join on connection when the client is first created
-                                self.chans[chan_idx].1.clear();
+                        // Initialize channel states
+                        for chan in chans.iter() {
+                            match utils::find_idx(&self.chans, |(s, _)| s == chan) {
+                                None => {
+                                    self.chans.push((chan.to_owned(), HashSet::new()));
+                                }
+                                Some(chan_idx) => {
+                                    // This happens because we initialize channel states for
+                                    // channels that we will join on connection when the client is
+                                    // first created
+                                    self.chans[chan_idx].1.clear();
+                                }
                             }
                         }
                     } else {
-                        match utils::find_idx(&self.chans, |(s, _)| s == chan) {
-                            Some(chan_idx) => {
-                                self.chans[chan_idx]
-                                    .1
-                                    .insert(wire::drop_nick_prefix(nick).to_owned());
-                            }
-                            None => {
-                                debug!("Can't find channel state for JOIN: {:?}", cmd);
+                        for chan in chans.iter() {
+                            match utils::find_idx(&self.chans, |(s, _)| s == chan) {
+                                Some(chan_idx) => {
+                                    self.chans[chan_idx]
+                                        .1
+                                        .insert(wire::drop_nick_prefix(nick).to_owned());
+                                }
+                                None => {
+                                    debug!("Can't find channel state for JOIN: {}", chan);
+                                }
                             }
                         }
                     }
@@ -272,24 +399,19 @@ impl StateInner {
             //
             // Remove channel state on PART
             //
-            PART { chan, .. } => {
+            PART { chans, .. } => {
                 if let Some(Pfx::User { nick, .. }) = pfx {
-                    if nick == &self.current_nick {
+                    for chan in chans.iter() {
                         match utils::find_idx(&self.chans, |(s, _)| s == chan) {
                             None => {
-                                debug!("Can't find channel state: {}", chan);
+                                debug!("Can't find channel state for PART: {}", chan);
                             }
                             Some(chan_idx) => {
-                                self.chans.remove(chan_idx);
-                            }
-                        }
-                    } else {
-                        match utils::find_idx(&self.chans, |(s, _)| s == chan) {
-                            Some(chan_idx) => {
-                                self.chans[chan_idx].1.remove(wire::drop_nick_prefix(nick));
-                            }
-                            None => {
-                                debug!("Can't find channel state for PART: {:?}", cmd);
+                                if nick == &self.current_nick {
+                                    self.chans.remove(chan_idx);
+                                } else {
+                                    self.chans[chan_idx].1.remove(wire::drop_nick_prefix(nick));
+                                }
                             }
                         }
                     }
@@ -306,9 +428,10 @@ impl StateInner {
                     .unwrap();
                 self.nick_accepted = true;
                 if let Some(ref pwd) = self.nickserv_ident {
-                    snd_irc_msg
-                        .try_send(wire::privmsg("NickServ", &format!("identify {}", pwd)))
-                        .unwrap();
+                    send_msg(
+                        snd_irc_msg,
+                        wire::privmsg("NickServ", &format!("identify {}", pwd)),
+                    );
                 }
             }
 
@@ -342,7 +465,7 @@ impl StateInner {
                     snd_ev
                         .try_send(Event::NickChange(new_nick.to_owned()))
                         .unwrap();
-                    snd_irc_msg.try_send(wire::nick(new_nick)).unwrap();
+                    send_msg(snd_irc_msg, wire::nick(new_nick));
                 }
             }
 
@@ -372,6 +495,10 @@ impl StateInner {
                         self.current_nick = new_nick.to_owned();
                     }
 
+                    if let Some(user_host) = self.user_hosts.remove(old_nick) {
+                        self.user_hosts.insert(new_nick.to_owned(), user_host);
+                    }
+
                     // Rename the nick in channel states, also populate the chan list
                     for (chan, nicks) in &mut self.chans {
                         if nicks.remove(old_nick) {
@@ -388,7 +515,7 @@ impl StateInner {
             Reply { num: 376, .. } => {
                 let chans: Vec<&str> = self.chans.iter().map(|(s, _)| s.as_str()).collect();
                 if !chans.is_empty() {
-                    snd_irc_msg.try_send(wire::join(&chans)).unwrap();
+                    send_msg(snd_irc_msg, wire::join(&chans));
                 }
             }
 
@@ -434,7 +561,7 @@ impl StateInner {
             }
 
             //
-            // SASL authentication
+            // Capability negotiation and SASL authentication
             //
             CAP {
                 client: _,
@@ -444,16 +571,35 @@ impl StateInner {
                 match subcommand.as_ref() {
                     "ACK" => {
                         if params.iter().any(|cap| cap.as_str() == "sasl") {
-                            snd_irc_msg.try_send(wire::authenticate("PLAIN")).unwrap();
+                            snd_irc_msg
+                                .try_send(wire::authenticate("PLAIN").unwrap())
+                                .unwrap();
+                        } else {
+                            snd_irc_msg.try_send(wire::cap_end()).unwrap();
                         }
                     }
                     "NAK" => {
                         snd_irc_msg.try_send(wire::cap_end()).unwrap();
                     }
                     "LS" => {
-                        self.introduce(snd_irc_msg);
-                        if params.iter().any(|cap| cap == "sasl") {
-                            snd_irc_msg.try_send(wire::cap_req(&["sasl"])).unwrap();
+                        let sasl = self.server_info.sasl_auth.is_some();
+                        if sasl {
+                            self.introduce(snd_irc_msg);
+                        }
+                        let reqs: Vec<&str> = params
+                            .iter()
+                            .map(String::as_str)
+                            .filter(|cap| {
+                                *cap == "message-tags"
+                                    || *cap == "server-time"
+                                    || (sasl && *cap == "sasl")
+                            })
+                            .collect();
+                        if reqs.is_empty() {
+                            snd_irc_msg.try_send(wire::cap_end()).unwrap();
+                        } else {
+                            // Only known capabilities are requested so this can't fail
+                            snd_irc_msg.try_send(wire::cap_req(&reqs).unwrap()).unwrap();
                             // Will wait for CAP ... ACK from server before authentication.
                         }
                     }
@@ -470,13 +616,95 @@ impl StateInner {
                             "{}\x00{}\x00{}",
                             auth.username, auth.username, auth.password
                         );
-                        snd_irc_msg
-                            .try_send(wire::authenticate(&base64::encode(&msg)))
-                            .unwrap();
+                        send_msg(snd_irc_msg, wire::authenticate(&base64::encode(&msg)));
+                    }
+                }
+            }
+
+            //
+            // RPL_ISUPPORT: `<client> <token>[=<value>]... :are supported by this server`
+            //
+            Reply { num: 005, params } if params.len() > 2 => {
+                for token in &params[1..params.len() - 1] {
+                    if let Some(token) = token.strip_prefix('-') {
+                        self.isupport.remove(token);
+                    } else {
+                        let mut token = token.splitn(2, '=');
+                        let key = token.next().unwrap();
+                        let value = token.next().unwrap_or("");
+                        self.isupport.insert(key.to_owned(), value.to_owned());
                     }
                 }
             }
 
+            //
+            // LIST replies: collect channels, send the list on RPL_LISTEND. Replies to LIST
+            // commands not sent with `Client::list` (e.g. sent as raw messages) are collected too.
+            //
+            Reply { num: 321, .. } => {
+                if self.chan_list.is_none() {
+                    self.chan_list = Some((ListFilter::default(), vec![]));
+                }
+            }
+
+            Reply { num: 322, params } => {
+                let (filter, chans) = self
+                    .chan_list
+                    .get_or_insert_with(|| (ListFilter::default(), vec![]));
+                if let Some(entry) = chan_list::parse_list_reply(params) {
+                    if filter.matches(&entry) {
+                        chans.push(entry);
+                    }
+                }
+            }
+
+            Reply { num: 323, .. } => {
+                let chans = self
+                    .chan_list
+                    .take()
+                    .map(|(_, chans)| chans)
+                    .unwrap_or_default();
+                snd_ev.try_send(Event::ChanList(chans)).unwrap();
+            }
+
+            //
+            // RPL_WHOISUSER: `<client> <nick> <username> <host> * :<realname>`
+            //
+            Reply { num: 311, params } if params.len() >= 4 => {
+                let user_host = format!("{}@{}", params[2], params[3]);
+                self.user_hosts.insert(params[1].to_owned(), user_host);
+            }
+
+            //
+            // RPL_WHOREPLY: `<client> <channel> <username> <host> <server> <nick> <flags> :...`
+            //
+            Reply { num: 352, params } if params.len() >= 6 => {
+                let user_host = format!("{}@{}", params[2], params[3]);
+                self.user_hosts.insert(params[5].to_owned(), user_host);
+            }
+
+            //
+            // Channel mode lists: collect items, send the list on the end of list reply
+            //
+            Reply { num, params } if params.len() >= 2 && ListMode::from_reply(*num).is_some() => {
+                let (mode, is_item) = ListMode::from_reply(*num).unwrap();
+                let key = (params[1].to_owned(), mode);
+                if is_item {
+                    if let Some(entry) = mode_list::parse_mode_list_reply(mode, params) {
+                        self.mode_lists.entry(key).or_default().push(entry);
+                    }
+                } else {
+                    let entries = self.mode_lists.remove(&key).unwrap_or_default();
+                    snd_ev
+                        .try_send(Event::ModeList {
+                            chan: key.0,
+                            mode,
+                            entries,
+                        })
+                        .unwrap();
+                }
+            }
+
             Reply { num: 903, .. } | Reply { num: 904, .. } => {
                 // 903: RPL_SASLSUCCESS, 904: ERR_SASLFAIL
                 snd_irc_msg.try_send(wire::cap_end()).unwrap();
@@ -498,6 +726,15 @@ impl StateInner {
 }
 
 /// Try to parse servername in a 002 RPL_YOURHOST reply
+/// Send a message generated by the client. Messages that can't be encoded (e.g. because of a nick
+/// or channel name in the config with a space in it) are logged and not sent.
+fn send_msg(snd_irc_msg: &mut Sender<String>, msg: Result<String, wire::EncodeError>) {
+    match msg {
+        Ok(msg) => snd_irc_msg.try_send(msg).unwrap(),
+        Err(err) => error!("Can't send message: {}", err),
+    }
+}
+
 fn parse_servername(params: &[String]) -> Option<String> {
     let msg = params.get(1).or_else(|| params.get(0))?;
     let slice1 = &msg[13..];
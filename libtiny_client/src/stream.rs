@@ -1,12 +1,15 @@
+use crate::websocket::{self, WsStream};
+
 use native_tls;
 use std::{
     net::SocketAddr,
+    path::Path,
     pin::Pin,
     task::{Context, Poll},
 };
 use tokio::{
     io::{AsyncRead, AsyncWrite},
-    net::TcpStream,
+    net::{TcpStream, UnixStream},
 };
 use tokio_tls::TlsStream;
 
@@ -14,6 +17,9 @@ use tokio_tls::TlsStream;
 pub(crate) enum Stream {
     TcpStream(TcpStream),
     TlsStream(TlsStream<TcpStream>),
+    /// IRC over WebSocket, over a `TcpStream` (`ws://`) or `TlsStream` (`wss://`)
+    WebSocket(Box<WsStream<Stream>>),
+    UnixStream(UnixStream),
 }
 
 pub(crate) enum StreamError {
@@ -38,6 +44,10 @@ impl Stream {
         Ok(Stream::TcpStream(TcpStream::connect(addr).await?))
     }
 
+    pub(crate) async fn new_unix(path: &Path) -> Result<Stream, StreamError> {
+        Ok(Stream::UnixStream(UnixStream::connect(path).await?))
+    }
+
     pub(crate) async fn new_tls(addr: SocketAddr, host_name: &str) -> Result<Stream, StreamError> {
         let tcp_stream = TcpStream::connect(addr).await?;
         let tls_connector =
@@ -45,6 +55,22 @@ impl Stream {
         let tls_stream = tls_connector.connect(host_name, tcp_stream).await?;
         Ok(Stream::TlsStream(tls_stream))
     }
+
+    pub(crate) async fn new_ws(
+        addr: SocketAddr,
+        host_name: &str,
+        port: u16,
+        path: &str,
+        tls: bool,
+    ) -> Result<Stream, StreamError> {
+        let stream = if tls {
+            Stream::new_tls(addr, host_name).await?
+        } else {
+            Stream::new_tcp(addr).await?
+        };
+        let ws_stream = websocket::connect(stream, host_name, port, path, tls).await?;
+        Ok(Stream::WebSocket(Box::new(ws_stream)))
+    }
 }
 
 //
@@ -60,6 +86,8 @@ impl AsyncRead for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_read(cx, buf),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_read(cx, buf),
+            Stream::WebSocket(ref mut ws_stream) => Pin::new(ws_stream).poll_read(cx, buf),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_read(cx, buf),
         }
     }
 }
@@ -73,6 +101,8 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_write(cx, buf),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_write(cx, buf),
+            Stream::WebSocket(ref mut ws_stream) => Pin::new(ws_stream).poll_write(cx, buf),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_write(cx, buf),
         }
     }
 
@@ -80,6 +110,8 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_flush(cx),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_flush(cx),
+            Stream::WebSocket(ref mut ws_stream) => Pin::new(ws_stream).poll_flush(cx),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_flush(cx),
         }
     }
 
@@ -90,6 +122,8 @@ impl AsyncWrite for Stream {
         match *self {
             Stream::TcpStream(ref mut tcp_stream) => Pin::new(tcp_stream).poll_shutdown(cx),
             Stream::TlsStream(ref mut tls_stream) => Pin::new(tls_stream).poll_shutdown(cx),
+            Stream::WebSocket(ref mut ws_stream) => Pin::new(ws_stream).poll_shutdown(cx),
+            Stream::UnixStream(ref mut unix_stream) => Pin::new(unix_stream).poll_shutdown(cx),
         }
     }
 }
@@ -1,15 +1,25 @@
-pub(crate) struct SplitIterator<'a> {
+pub(crate) struct SplitIterator<'a, F> {
     s: Option<&'a str>,
     max: usize,
+    len: F,
 }
 
-/// Iterate over subslices that are at most `max` long (in bytes). Splits are
-/// made on whitespace characters when possible.
-pub(crate) fn split_iterator(s: &str, max: usize) -> SplitIterator {
-    SplitIterator { s: Some(s), max }
+/// Iterate over subslices that are at most `max` long. Lengths of subslices are calculated with
+/// `len`, e.g. `str::len` for length in bytes. Splits are made on whitespace characters when
+/// possible.
+pub(crate) fn split_iterator<F: Fn(&str) -> usize>(
+    s: &str,
+    max: usize,
+    len: F,
+) -> SplitIterator<F> {
+    SplitIterator {
+        s: Some(s),
+        max,
+        len,
+    }
 }
 
-impl<'a> Iterator for SplitIterator<'a> {
+impl<'a, F: Fn(&str) -> usize> Iterator for SplitIterator<'a, F> {
     type Item = &'a str;
 
     fn next(&mut self) -> Option<&'a str> {
@@ -20,18 +30,28 @@ impl<'a> Iterator for SplitIterator<'a> {
         match self.s {
             None => None,
             Some(s) => {
-                if s.len() <= self.max {
+                if (self.len)(s) <= self.max {
                     let ret = Some(s);
                     self.s = None;
                     ret
                 } else {
+                    // Longest prefix that fits
+                    let mut max_split = 0;
+                    for (char_idx, char) in s.char_indices() {
+                        let end = char_idx + char.len_utf8();
+                        if (self.len)(&s[..end]) > self.max {
+                            break;
+                        }
+                        max_split = end;
+                    }
+
                     let mut split = 0;
 
                     // try to split at a whitespace character
                     for (ws_idx, ws_char) in s.rmatch_indices(char::is_whitespace) {
-                        if ws_idx <= self.max {
+                        if ws_idx <= max_split {
                             // should we include ws char?
-                            if ws_idx + ws_char.len() <= self.max {
+                            if ws_idx + ws_char.len() <= max_split {
                                 split = ws_idx + ws_char.len();
                             } else {
                                 split = ws_idx;
@@ -42,12 +62,7 @@ impl<'a> Iterator for SplitIterator<'a> {
 
                     if split == 0 {
                         // couldn't split at a whitespace, just split at any char
-                        for i in 0..4 {
-                            if s.is_char_boundary(self.max - i) {
-                                split = self.max - i;
-                                break;
-                            }
-                        }
+                        split = max_split;
                     }
 
                     if split == 0 {
@@ -81,13 +96,13 @@ mod tests {
 
     #[test]
     fn test_split_iterator_1() {
-        let iter = split_iterator("yada yada yada", 5);
+        let iter = split_iterator("yada yada yada", 5, str::len);
         assert_eq!(iter.collect::<Vec<&str>>(), vec!["yada ", "yada ", "yada"]);
     }
 
     #[test]
     fn test_split_iterator_2() {
-        let iter = split_iterator("yada yada yada", 4);
+        let iter = split_iterator("yada yada yada", 4, str::len);
         assert_eq!(
             iter.collect::<Vec<&str>>(),
             // weird but OK
@@ -97,7 +112,7 @@ mod tests {
 
     #[test]
     fn test_split_iterator_3() {
-        let iter = split_iterator("yada yada yada", 3);
+        let iter = split_iterator("yada yada yada", 3, str::len);
         assert_eq!(
             iter.collect::<Vec<&str>>(),
             vec!["yad", "a ", "yad", "a ", "yad", "a"]
@@ -106,7 +121,7 @@ mod tests {
 
     #[test]
     fn test_split_iterator_4() {
-        let iter = split_iterator("longwordislong", 3);
+        let iter = split_iterator("longwordislong", 3, str::len);
         assert_eq!(
             iter.collect::<Vec<&str>>(),
             vec!["lon", "gwo", "rdi", "slo", "ng"]
@@ -115,14 +130,23 @@ mod tests {
 
     #[test]
     fn test_split_iterator_5() {
-        let iter = split_iterator("", 3);
+        let iter = split_iterator("", 3, str::len);
         assert_eq!(iter.collect::<Vec<&str>>(), vec![""]);
     }
 
     #[test]
     fn test_split_iterator_6() {
-        let iter = split_iterator("", 0);
+        let iter = split_iterator("", 0, str::len);
         let ret: Vec<&str> = vec![];
         assert_eq!(iter.collect::<Vec<&str>>(), ret);
     }
+
+    #[test]
+    fn test_split_iterator_7() {
+        // Split by number of chars instead of bytes
+        let iter = split_iterator("ééé ééé", 4, |s| s.chars().count());
+        assert_eq!(iter.collect::<Vec<&str>>(), vec!["ééé ", "ééé"]);
+        let iter = split_iterator("éééé", 3, |s| s.chars().count());
+        assert_eq!(iter.collect::<Vec<&str>>(), vec!["ééé", "é"]);
+    }
 }
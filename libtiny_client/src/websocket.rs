@@ -0,0 +1,494 @@
+//! IRC over WebSocket (https://ircv3.net/specs/extensions/websocket). `WsStream` wraps a TCP or
+//! TLS stream and implements the opening handshake and framing, so that the rest of the client
+//! can read and write "\r\n"-terminated IRC messages as with plain TCP: each message is sent in a
+//! text frame of its own (without the "\r\n"), and "\r\n" is added to the payloads of the frames
+//! received.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Subprotocol for UTF-8 messages in text frames.
+const SUBPROTOCOL: &str = "text.ircv3.net";
+
+/// Appended to the `Sec-WebSocket-Key` when generating `Sec-WebSocket-Accept`.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Max. size of the handshake response.
+const MAX_RESPONSE_LEN: usize = 8192;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Debug)]
+pub(crate) struct WsStream<S> {
+    stream: S,
+
+    /// Bytes read from `stream` that don't make a complete frame yet.
+    read_buf: Vec<u8>,
+
+    /// Messages received, with "\r\n" added, not yet returned by `poll_read`.
+    msgs: Vec<u8>,
+
+    /// Payloads of a fragmented message received so far.
+    fragments: Vec<u8>,
+
+    /// Bytes written by the user that don't make a complete message yet.
+    line_buf: Vec<u8>,
+
+    /// Frames not yet written to `stream`.
+    write_buf: Vec<u8>,
+
+    /// Did we get a close frame?
+    closed: bool,
+}
+
+/// Run the opening handshake over `stream`. `host` and `port` are used in the `Host` header,
+/// `path` is the resource name (e.g. `/webirc`).
+pub(crate) async fn connect<S>(
+    mut stream: S,
+    host: &str,
+    port: u16,
+    path: &str,
+    tls: bool,
+) -> io::Result<WsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let key = base64::encode(&rand::random::<[u8; 16]>());
+    let default_port = if tls { 443 } else { 80 };
+    let host = if port == default_port {
+        host.to_owned()
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\n\
+         Host: {}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\n\
+         Sec-WebSocket-Version: 13\r\n\
+         Sec-WebSocket-Protocol: {}\r\n\
+         \r\n",
+        if path.is_empty() { "/" } else { path },
+        host,
+        key,
+        SUBPROTOCOL
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut buf: Vec<u8> = Vec::with_capacity(1024);
+    let header_len = loop {
+        if let Some(idx) = find_bytes(&buf, b"\r\n\r\n") {
+            break idx + 4;
+        }
+        if buf.len() > MAX_RESPONSE_LEN {
+            return Err(handshake_err("response too long".to_owned()));
+        }
+        let mut read_buf: [u8; 1024] = [0; 1024];
+        match stream.read(&mut read_buf).await? {
+            0 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "WebSocket handshake failed: connection closed",
+                ));
+            }
+            n => buf.extend_from_slice(&read_buf[..n]),
+        }
+    };
+
+    check_response(&String::from_utf8_lossy(&buf[..header_len]), &key)?;
+
+    // Frames sent right after the response
+    buf.drain(..header_len);
+
+    Ok(WsStream {
+        stream,
+        read_buf: buf,
+        msgs: vec![],
+        fragments: vec![],
+        line_buf: vec![],
+        write_buf: vec![],
+        closed: false,
+    })
+}
+
+fn handshake_err(msg: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("WebSocket handshake failed: {}", msg),
+    )
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn accept_key(key: &str) -> String {
+    let mut sha1 = sha1::Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    base64::encode(&sha1.digest().bytes())
+}
+
+/// Check the handshake response, including the status line and the final empty line.
+fn check_response(response: &str, key: &str) -> io::Result<()> {
+    let mut lines = response.split("\r\n");
+
+    let status_line = lines.next().unwrap_or("");
+    let mut status_words = status_line.split(' ');
+    if !status_words.next().unwrap_or("").starts_with("HTTP/") || status_words.next() != Some("101")
+    {
+        return Err(handshake_err(format!(
+            "unexpected response: {}",
+            status_line
+        )));
+    }
+
+    let mut upgrade = false;
+    let mut accept = false;
+    for line in lines {
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim();
+        let value = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("Upgrade") {
+            upgrade = value.eq_ignore_ascii_case("websocket");
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Accept") {
+            accept = value == accept_key(key);
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Protocol") && value != SUBPROTOCOL {
+            // No subprotocol is fine, server should be using text frames in that case too
+            return Err(handshake_err(format!("unexpected subprotocol: {}", value)));
+        }
+    }
+
+    if !upgrade {
+        return Err(handshake_err("missing Upgrade header".to_owned()));
+    }
+    if !accept {
+        return Err(handshake_err("invalid Sec-WebSocket-Accept".to_owned()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Decode a frame at the beginning of `buf`. Returns the frame and its length in `buf`, or `None`
+/// if `buf` doesn't have a complete frame yet.
+fn decode_frame(buf: &[u8]) -> io::Result<Option<(Frame, usize)>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0F;
+    let masked = buf[1] & 0x80 != 0;
+    let mut idx = 2;
+
+    let payload_len = match buf[1] & 0x7F {
+        126 => {
+            if buf.len() < idx + 2 {
+                return Ok(None);
+            }
+            let len = u16::from_be_bytes([buf[idx], buf[idx + 1]]) as u64;
+            idx += 2;
+            len
+        }
+        127 => {
+            if buf.len() < idx + 8 {
+                return Ok(None);
+            }
+            let mut len_bytes = [0; 8];
+            len_bytes.copy_from_slice(&buf[idx..idx + 8]);
+            idx += 8;
+            u64::from_be_bytes(len_bytes)
+        }
+        len => u64::from(len),
+    };
+
+    // IRC messages are short, this is most likely garbage
+    if payload_len > 1024 * 1024 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("WebSocket frame too large ({} bytes)", payload_len),
+        ));
+    }
+    let payload_len = payload_len as usize;
+
+    let mut mask = None;
+    if masked {
+        if buf.len() < idx + 4 {
+            return Ok(None);
+        }
+        mask = Some([buf[idx], buf[idx + 1], buf[idx + 2], buf[idx + 3]]);
+        idx += 4;
+    }
+
+    if buf.len() < idx + payload_len {
+        return Ok(None);
+    }
+
+    let mut payload = buf[idx..idx + payload_len].to_vec();
+    if let Some(mask) = mask {
+        apply_mask(&mut payload, mask);
+    }
+
+    Ok(Some((
+        Frame {
+            fin,
+            opcode,
+            payload,
+        },
+        idx + payload_len,
+    )))
+}
+
+/// Encode a masked frame (as frames sent by clients need to be masked) to `buf`.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4], buf: &mut Vec<u8>) {
+    buf.push(0x80 | opcode);
+    let len = payload.len();
+    if len < 126 {
+        buf.push(0x80 | len as u8);
+    } else if len <= usize::from(u16::MAX) {
+        buf.push(0x80 | 126);
+        buf.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        buf.push(0x80 | 127);
+        buf.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    buf.extend_from_slice(&mask);
+    let payload_start = buf.len();
+    buf.extend_from_slice(payload);
+    apply_mask(&mut buf[payload_start..], mask);
+}
+
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+}
+
+/// Remove the "\r\n" (or just "\n") at the end of a message.
+fn trim_crlf(mut msg: &[u8]) -> &[u8] {
+    while let Some((&b'\r', rest)) | Some((&b'\n', rest)) = msg.split_last() {
+        msg = rest;
+    }
+    msg
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WsStream<S> {
+    /// Decode frames in `read_buf`. Data frames are added to `msgs`, control frames are handled.
+    fn decode_frames(&mut self) -> io::Result<()> {
+        while let Some((frame, frame_len)) = decode_frame(&self.read_buf)? {
+            self.read_buf.drain(..frame_len);
+            match frame.opcode {
+                OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                    self.fragments.extend_from_slice(&frame.payload);
+                    if frame.fin {
+                        self.msgs.extend_from_slice(trim_crlf(&self.fragments));
+                        self.msgs.extend_from_slice(b"\r\n");
+                        self.fragments.clear();
+                    }
+                }
+                OP_CLOSE => {
+                    // Echo the status code (first two bytes of the payload) back
+                    let status_len = std::cmp::min(frame.payload.len(), 2);
+                    encode_frame(
+                        OP_CLOSE,
+                        &frame.payload[..status_len],
+                        rand::random(),
+                        &mut self.write_buf,
+                    );
+                    self.closed = true;
+                    return Ok(());
+                }
+                OP_PING => {
+                    encode_frame(OP_PONG, &frame.payload, rand::random(), &mut self.write_buf);
+                }
+                OP_PONG => {}
+                opcode => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Unknown WebSocket opcode: {}", opcode),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `write_buf` to the underlying stream.
+    fn poll_write_buf(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.stream).poll_write(cx, &self.write_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Ready(Ok(0)) => {
+                    return Poll::Ready(Err(io::Error::from(io::ErrorKind::WriteZero)));
+                }
+                Poll::Ready(Ok(n)) => {
+                    self.write_buf.drain(..n);
+                }
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for WsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            // Pongs and close frames are sent as we read, without waiting for a write or flush
+            if let Poll::Ready(Err(err)) = this.poll_write_buf(cx) {
+                return Poll::Ready(Err(err));
+            }
+
+            if !this.msgs.is_empty() {
+                let n = std::cmp::min(buf.len(), this.msgs.len());
+                buf[..n].copy_from_slice(&this.msgs[..n]);
+                this.msgs.drain(..n);
+                return Poll::Ready(Ok(n));
+            }
+
+            if this.closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            let mut read_buf: [u8; 1024] = [0; 1024];
+            match Pin::new(&mut this.stream).poll_read(cx, &mut read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Ready(Ok(0)) => return Poll::Ready(Ok(0)),
+                Poll::Ready(Ok(n)) => {
+                    this.read_buf.extend_from_slice(&read_buf[..n]);
+                    this.decode_frames()?;
+                }
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for WsStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Don't buffer more frames until the previous ones are written
+        if this.poll_write_buf(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+
+        this.line_buf.extend_from_slice(buf);
+        while let Some(idx) = this.line_buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = this.line_buf.drain(..=idx).collect();
+            let msg = trim_crlf(&line);
+            if !msg.is_empty() {
+                encode_frame(OP_TEXT, msg, rand::random(), &mut this.write_buf);
+            }
+        }
+
+        // Start writing the frames. What's left is written on next write or flush.
+        if let Poll::Ready(Err(err)) = this.poll_write_buf(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.poll_write_buf(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut this.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.poll_write_buf(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut this.stream).poll_shutdown(cx)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example in RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_check_response() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let response = "HTTP/1.1 101 Switching Protocols\r\n\
+                        Upgrade: websocket\r\n\
+                        Connection: Upgrade\r\n\
+                        Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\
+                        Sec-WebSocket-Protocol: text.ircv3.net\r\n\
+                        \r\n";
+        assert!(check_response(response, key).is_ok());
+        assert!(check_response(&response.replace("101", "404"), key).is_err());
+        assert!(check_response(&response.replace("s3pP", "s4pP"), key).is_err());
+        assert!(check_response(&response.replace("text.", "binary."), key).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut buf = vec![];
+        encode_frame(OP_TEXT, b"PING :x", [1, 2, 3, 4], &mut buf);
+        // Masked payload
+        assert_eq!(&buf[..6], &[0x81, 0x80 | 7, 1, 2, 3, 4]);
+        assert_ne!(&buf[6..], b"PING :x");
+
+        let long_msg = vec![b'a'; 300];
+        encode_frame(OP_TEXT, &long_msg, [5, 6, 7, 8], &mut buf);
+
+        let (frame, len) = decode_frame(&buf).unwrap().unwrap();
+        assert_eq!(
+            frame,
+            Frame {
+                fin: true,
+                opcode: OP_TEXT,
+                payload: b"PING :x".to_vec()
+            }
+        );
+        let (frame, len_) = decode_frame(&buf[len..]).unwrap().unwrap();
+        assert_eq!(frame.payload, long_msg);
+        assert_eq!(len + len_, buf.len());
+
+        // Incomplete frames
+        for i in 0..len {
+            assert_eq!(decode_frame(&buf[..i]).unwrap(), None);
+        }
+
+        // Unmasked frame, as sent by servers
+        let (frame, _) = decode_frame(b"\x81\x03abc").unwrap().unwrap();
+        assert_eq!(frame.payload, b"abc");
+    }
+}
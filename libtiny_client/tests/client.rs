@@ -0,0 +1,511 @@
+//! Tests for `Client`, using a mock IRC server.
+
+use libtiny_client::wire::Cmd;
+use libtiny_client::{
+    ChanListEntry, Client, Event, ListFilter, ListMode, ModeListEntry, SASLAuth, ServerInfo,
+};
+use libtiny_mock_server::{MockServer, Step};
+
+use futures::stream::StreamExt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::future::FutureExt;
+use tokio::runtime::current_thread::Runtime;
+use tokio::sync::mpsc;
+
+fn server_info(port: u16) -> ServerInfo {
+    ServerInfo {
+        addr: "127.0.0.1".to_owned(),
+        port,
+        tls: false,
+        pass: None,
+        realname: "tiny user".to_owned(),
+        nicks: vec!["tiny".to_owned()],
+        auto_join: vec![],
+        nickserv_ident: None,
+        sasl_auth: None,
+        encoding: None,
+        chan_encodings: vec![],
+        outgoing_encoding: None,
+        ping_interval: None,
+        websocket: None,
+        unix_socket: None,
+    }
+}
+
+fn run<F: Future<Output = ()>>(test: F) {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.block_on(test);
+}
+
+/// Skip events until one that satisfies the predicate.
+async fn wait_for_event<F: Fn(&Event) -> bool>(rcv_ev: &mut mpsc::Receiver<Event>, pred: F) {
+    loop {
+        match rcv_ev.next().timeout(Duration::from_secs(10)).await {
+            Err(_) => panic!("Timed out waiting for an event"),
+            Ok(None) => panic!("Event channel closed"),
+            Ok(Some(ev)) => {
+                if pred(&ev) {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+// Registration without SASL: `CAP LS`, `NICK` and `USER` are sent right away.
+static REGISTER: [Step; 6] = [
+    Step::Expect("CAP LS"),
+    Step::Expect("NICK tiny"),
+    Step::Expect("USER tiny 8 * :tiny user"),
+    Step::Send(":irc.example.com CAP * LS :multi-prefix"),
+    Step::Expect("CAP END"),
+    Step::Send(":irc.example.com 001 tiny :Welcome to the network tiny"),
+];
+
+#[test]
+fn test_registration() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let mut info = server_info(server.port());
+        info.auto_join = vec!["#tiny".to_owned(), "#rust".to_owned()];
+        let (client, mut rcv_ev) = Client::new(info);
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        conn.run(&[
+            Step::Send(":irc.example.com 376 tiny :End of /MOTD command."),
+            Step::Expect("JOIN #tiny,#rust"),
+        ])
+        .await;
+
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+        assert!(client.is_nick_accepted());
+        assert_eq!(client.get_nick(), "tiny");
+    });
+}
+
+#[test]
+fn test_threaded_runtime() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Client>();
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let mut server = MockServer::new().await;
+        let (client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+
+        // Use the client in another thread
+        let mut client_clone = client.clone();
+        std::thread::spawn(move || client_clone.join(&["#tiny"]))
+            .join()
+            .unwrap()
+            .unwrap();
+        conn.expect("JOIN #tiny").await;
+        assert_eq!(client.get_nick(), "tiny");
+    });
+}
+
+#[test]
+fn test_nick_fallback() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let mut info = server_info(server.port());
+        info.nicks = vec!["tiny".to_owned(), "tiny_user".to_owned()];
+        let (client, mut rcv_ev) = Client::new(info);
+
+        let mut conn = server.accept().await;
+        conn.run(&[
+            Step::Expect("CAP LS"),
+            Step::Expect("NICK tiny"),
+            Step::Expect("USER tiny 8 * :tiny user"),
+            Step::Send(":irc.example.com 433 * tiny :Nickname is already in use"),
+            Step::Expect("NICK tiny_user"),
+            Step::Send(":irc.example.com 433 * tiny_user :Nickname is already in use"),
+            // Underscores are added to the last nick
+            Step::Expect("NICK tiny_user_"),
+            Step::Send(":irc.example.com 433 * tiny_user_ :Nickname is already in use"),
+            Step::Expect("NICK tiny_user__"),
+            Step::Send(":irc.example.com 001 tiny_user__ :Welcome to the network tiny_user__"),
+        ])
+        .await;
+
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+        assert_eq!(client.get_nick(), "tiny_user__");
+
+        // 433 after registration (e.g. a failed `/nick`) doesn't change the nick
+        conn.send(":irc.example.com 433 tiny_user__ tiny :Nickname is already in use")
+            .await;
+        conn.send("PING :irc.example.com").await;
+        conn.expect("PONG irc.example.com").await;
+        assert_eq!(client.get_nick(), "tiny_user__");
+    });
+}
+
+#[test]
+fn test_reconnect_rejoin() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        client.join(&["#tiny"]).unwrap();
+        conn.run(&[
+            Step::Expect("JOIN #tiny"),
+            Step::Send(":tiny!~tiny@127.0.0.1 JOIN #tiny"),
+        ])
+        .await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Msg(_))).await;
+
+        // Close the connection, client should rejoin the channel after reconnecting
+        drop(conn);
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Disconnected)).await;
+        client.reconnect(None);
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        conn.run(&[
+            Step::Send(":irc.example.com 376 tiny :End of /MOTD command."),
+            Step::Expect("JOIN #tiny"),
+        ])
+        .await;
+    });
+}
+
+#[test]
+fn test_websocket() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let mut info = server_info(server.port());
+        info.websocket = Some("/webirc".to_owned());
+        info.auto_join = vec!["#tiny".to_owned()];
+        let (client, mut rcv_ev) = Client::new(info);
+
+        // `accept_ws` checks the handshake, and that each frame has one message
+        let mut conn = server.accept_ws("/webirc").await;
+        conn.run(&REGISTER).await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+
+        // Control frames in between messages are answered and skipped
+        conn.send_ws_ping().await;
+        conn.run(&[
+            Step::Send(":irc.example.com 376 tiny :End of /MOTD command."),
+            Step::Expect("JOIN #tiny"),
+            Step::Send("PING :irc.example.com"),
+            Step::Expect("PONG irc.example.com"),
+        ])
+        .await;
+        assert_eq!(client.get_nick(), "tiny");
+    });
+}
+
+#[test]
+fn test_unix_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("irc.sock");
+
+    run(async {
+        let mut server = MockServer::new_unix(&path);
+        let mut info = server_info(0);
+        // Not resolved or connected to
+        info.addr = "irc.invalid".to_owned();
+        info.unix_socket = Some(path.clone());
+        let (mut client, mut rcv_ev) = Client::new(info);
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+
+        // Reconnecting also skips DNS lookup
+        drop(conn);
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Disconnected)).await;
+        client.reconnect(None);
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::ConnectingUnix(unix_path) => unix_path == &path,
+            Event::ResolvingHost => panic!("Client tried to resolve host"),
+            _ => false,
+        })
+        .await;
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+    });
+}
+
+fn sasl_server_info(port: u16) -> ServerInfo {
+    let mut info = server_info(port);
+    info.sasl_auth = Some(SASLAuth {
+        username: "tiny".to_owned(),
+        password: "hunter2".to_owned(),
+    });
+    info
+}
+
+// With SASL, `NICK` and `USER` are sent after `CAP LS` reply.
+static SASL_AUTH: [Step; 9] = [
+    Step::Expect("CAP LS"),
+    Step::Send(":irc.example.com CAP * LS :sasl message-tags server-time account-tag"),
+    Step::Expect("NICK tiny"),
+    Step::Expect("USER tiny 8 * :tiny user"),
+    Step::Expect("CAP REQ :sasl message-tags server-time"),
+    Step::Send(":irc.example.com CAP tiny ACK :sasl message-tags server-time"),
+    Step::Expect("AUTHENTICATE PLAIN"),
+    Step::Send("AUTHENTICATE +"),
+    // base64("tiny\0tiny\0hunter2")
+    Step::Expect("AUTHENTICATE dGlueQB0aW55AGh1bnRlcjI="),
+];
+
+#[test]
+fn test_sasl_success() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (client, mut rcv_ev) = Client::new(sasl_server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&SASL_AUTH).await;
+        conn.run(&[
+            Step::Send(":irc.example.com 903 tiny :SASL authentication successful"),
+            Step::Expect("CAP END"),
+            Step::Send(":irc.example.com 001 tiny :Welcome to the network tiny"),
+        ])
+        .await;
+
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+        assert!(client.is_nick_accepted());
+    });
+}
+
+#[test]
+fn test_sasl_failure() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (_client, mut rcv_ev) = Client::new(sasl_server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&SASL_AUTH).await;
+        // Registration continues without authentication
+        conn.run(&[
+            Step::Send(":irc.example.com 904 tiny :SASL authentication failed"),
+            Step::Expect("CAP END"),
+            Step::Send(":irc.example.com 001 tiny :Welcome to the network tiny"),
+        ])
+        .await;
+
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::Msg(msg) => matches!(msg.cmd, Cmd::Reply { num: 904, .. }),
+            _ => false,
+        })
+        .await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+    });
+}
+
+#[test]
+fn test_ping_timeout() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let mut info = server_info(server.port());
+        info.ping_interval = Some(Duration::from_millis(200));
+        let (_client, mut rcv_ev) = Client::new(info);
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        conn.run(&[
+            // Client needs the server name to send PINGs
+            Step::Send(
+                ":irc.example.com 002 tiny :Your host is irc.example.com, running version x",
+            ),
+            Step::Expect("PING irc.example.com"),
+            // Answering the PING keeps the connection alive
+            Step::Send(":irc.example.com PONG irc.example.com :irc.example.com"),
+            Step::Expect("PING irc.example.com"),
+        ])
+        .await;
+
+        // Don't answer the second PING, client should disconnect
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Disconnected)).await;
+    });
+}
+
+#[test]
+fn test_list() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        conn.send(":irc.example.com 005 tiny ELIST=MU SAFELIST :are supported by this server")
+            .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::Msg(msg) => matches!(msg.cmd, Cmd::Reply { num: 5, .. }),
+            _ => false,
+        })
+        .await;
+        assert_eq!(client.get_isupport("ELIST"), Some("MU".to_owned()));
+        assert_eq!(client.get_isupport("SAFELIST"), Some("".to_owned()));
+
+        // Server supports the filters, but client filters the replies too
+        client
+            .list(&ListFilter {
+                masks: vec!["#rust*".to_owned()],
+                min_users: Some(10),
+                max_users: None,
+            })
+            .unwrap();
+        conn.run(&[
+            Step::Expect("LIST #rust*,>10"),
+            Step::Send(":irc.example.com 321 tiny Channel :Users  Name"),
+            Step::Send(":irc.example.com 322 tiny #rust 1000 :Rust programming language"),
+            Step::Send(":irc.example.com 322 tiny #rust-offtopic 5 :"),
+            Step::Send(":irc.example.com 323 tiny :End of /LIST"),
+        ])
+        .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::ChanList(chans) => {
+                assert_eq!(
+                    chans,
+                    &vec![ChanListEntry {
+                        chan: "#rust".to_owned(),
+                        n_users: 1000,
+                        topic: "Rust programming language".to_owned(),
+                    }]
+                );
+                true
+            }
+            _ => false,
+        })
+        .await;
+    });
+}
+
+#[test]
+fn test_mode_list() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+
+        // user@host of nicks are collected from message prefixes
+        conn.send(":spammer!~spam@spam.example.com PRIVMSG tiny :hi")
+            .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::Msg(msg) => matches!(msg.cmd, Cmd::PRIVMSG { .. }),
+            _ => false,
+        })
+        .await;
+        assert_eq!(
+            client.get_user_host("spammer"),
+            Some("~spam@spam.example.com".to_owned())
+        );
+
+        client.get_mode_list("#tiny", ListMode::Quiet).unwrap();
+        conn.run(&[
+            Step::Expect("MODE #tiny +q"),
+            Step::Send(":irc.example.com 728 tiny #tiny q *!*@spam.example.com osa1 1571300000"),
+            Step::Send(":irc.example.com 729 tiny #tiny q :End of Channel Quiet List"),
+        ])
+        .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::ModeList {
+                chan,
+                mode,
+                entries,
+            } => {
+                assert_eq!(chan, "#tiny");
+                assert_eq!(*mode, ListMode::Quiet);
+                assert_eq!(
+                    entries,
+                    &vec![ModeListEntry {
+                        mask: "*!*@spam.example.com".to_owned(),
+                        set_by: Some("osa1".to_owned()),
+                        set_at: Some(1571300000),
+                    }]
+                );
+                true
+            }
+            _ => false,
+        })
+        .await;
+    });
+}
+
+#[test]
+fn test_set_modes() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+
+        // RFC 1459 limit of 3 modes per message when the server doesn't advertise MODES
+        client
+            .set_modes("#tiny", true, 'o', &["a", "b", "c", "d"])
+            .unwrap();
+        conn.run(&[
+            Step::Expect("MODE #tiny +ooo a b c"),
+            Step::Expect("MODE #tiny +o d"),
+        ])
+        .await;
+
+        conn.send(":irc.example.com 005 tiny MODES=2 :are supported by this server")
+            .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::Msg(msg) => matches!(msg.cmd, Cmd::Reply { num: 5, .. }),
+            _ => false,
+        })
+        .await;
+        client
+            .set_modes("#tiny", false, 'v', &["a", "b", "c"])
+            .unwrap();
+        conn.run(&[
+            Step::Expect("MODE #tiny -vv a b"),
+            Step::Expect("MODE #tiny -v c"),
+        ])
+        .await;
+    });
+}
+
+#[test]
+fn test_raw_msg_events() {
+    run(async {
+        let mut server = MockServer::new().await;
+        let (mut client, mut rcv_ev) = Client::new(server_info(server.port()));
+
+        let mut conn = server.accept().await;
+        conn.run(&REGISTER).await;
+        wait_for_event(&mut rcv_ev, |ev| matches!(ev, Event::Connected)).await;
+
+        client.set_raw_msg_events(true);
+        client.raw_msg("VERSION");
+        conn.expect("VERSION").await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::RawMsg { msg, outgoing } => {
+                assert_eq!((msg.as_str(), *outgoing), ("VERSION", true));
+                true
+            }
+            _ => false,
+        })
+        .await;
+
+        conn.send(":irc.example.com 351 tiny ircd-1.0 irc.example.com :")
+            .await;
+        wait_for_event(&mut rcv_ev, |ev| match ev {
+            Event::RawMsg { msg, outgoing } => {
+                assert_eq!(msg, ":irc.example.com 351 tiny ircd-1.0 irc.example.com :");
+                assert!(!outgoing);
+                true
+            }
+            Event::Msg(_) => panic!("Msg event before RawMsg"),
+            _ => false,
+        })
+        .await;
+    });
+}
@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+use crate::encryption::{Encryption, LogDecrypter, Passphrase};
+use crate::format::{EventType, Formatter, JsonFormatter, TextFormatter};
+use crate::reader::ReadLimit;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// How log files are split
+    pub rotation: Rotation,
+
+    /// Format of log files
+    pub format: Format,
+
+    /// Use full ISO 8601 timestamps (`2019-10-17T18:30:00+03:00`) instead of `18:30:00` in text
+    /// logs
+    pub iso_timestamps: bool,
+
+    /// Show last lines of channel and user tabs from the logs when the tabs are created
+    pub restore_scrollback: Option<ReadLimit>,
+
+    /// Events that are not logged, e.g. `[join, part, mode]`. Everything is logged by default.
+    pub ignore: Vec<EventType>,
+
+    /// Encrypt logs. Logs are not encrypted by default.
+    pub encryption: Option<Encryption>,
+
+    /// Passphrase of `Encryption::Passphrase`, also used to decrypt logs when reading them. Not
+    /// read from the config file.
+    #[serde(skip)]
+    pub passphrase: Option<Passphrase>,
+}
+
+impl Config {
+    /// Formatter of the configured format.
+    pub fn formatter(&self) -> Box<dyn Formatter> {
+        match self.format {
+            Format::Text => Box::new(TextFormatter {
+                iso_timestamps: self.iso_timestamps,
+            }),
+            Format::Jsonl => Box::new(JsonFormatter),
+        }
+    }
+
+    /// Decrypter for reading logs encrypted with the passphrase. `None` when the passphrase is not
+    /// set, encrypted logs can't be read then.
+    pub fn decrypter(&self) -> Option<LogDecrypter> {
+        self.passphrase
+            .as_ref()
+            .map(|passphrase| LogDecrypter::with_passphrase(&passphrase.0))
+    }
+}
+
+/// How log files are split.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+    /// One file per tab: `{serv}.txt`, `{serv}_{chan}.txt`
+    #[default]
+    None,
+    /// One file per tab per day: `{serv}/2019-10-17.log`, `{serv}/{chan}/2019-10-17.log`
+    Daily,
+    /// One file per tab per month: `{serv}/2019-10.log`, `{serv}/{chan}/2019-10.log`
+    Monthly,
+}
+
+/// Format of log files.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Plain text: `[18:30:00] osa1: hi`. See `TextFormatter`.
+    #[default]
+    Text,
+    /// JSON Lines: one JSON object per event, in `.jsonl` files. See `JsonFormatter`. When logs
+    /// are encrypted the files are only valid JSON Lines after decrypting (e.g. with
+    /// `tiny-logcat`).
+    Jsonl,
+}
@@ -0,0 +1,317 @@
+//! Encrypted logs. See `Encryption`.
+//!
+//! Lines of log files are encrypted one by one, so that log files can still be appended to. An
+//! encrypted log file has these lines:
+//!
+//! - `age-key: <base64>`: A random key, encrypted with [age] to the passphrase or recipients.
+//!   Written every time the file is opened. Following lines are encrypted with this key.
+//!
+//! - `enc: <base64>`: A line encrypted with XChaCha20-Poly1305: a 24-byte random nonce followed by
+//!   the ciphertext.
+//!
+//! Other lines are not encrypted, e.g. lines written before encryption was enabled. File names
+//! don't change, so encrypted JSON logs are in `.jsonl` files but are only JSON Lines after
+//! decrypting.
+//!
+//! [age]: https://age-encryption.org
+
+use age::secrecy::SecretString;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const KEY_PREFIX: &str = "age-key: ";
+const LINE_PREFIX: &str = "enc: ";
+const NONCE_LEN: usize = 24;
+
+/// How to encrypt logs. Encrypted logs can be read with `tiny-logcat`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Encryption {
+    /// Encrypt with a key derived from a passphrase. The passphrase is not a part of the config
+    /// file (which would have the key of the logs in plain text), it's set in `Config::passphrase`.
+    Passphrase,
+
+    /// Encrypt to age X25519 public keys (`age1...`). The logs can only be decrypted with the
+    /// private keys, which tiny doesn't need to know.
+    Recipients(Vec<String>),
+}
+
+/// Passphrase of `Encryption::Passphrase`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(pub String);
+
+impl fmt::Debug for Passphrase {
+    // Don't show passphrases in debug output
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Passphrase(..)")
+    }
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_recipient(recipient: &str) -> io::Result<Box<dyn age::Recipient>> {
+    match recipient.parse::<age::x25519::Recipient>() {
+        Err(err) => Err(invalid_input(format!(
+            "Invalid recipient {}: {}",
+            recipient, err
+        ))),
+        Ok(recipient) => Ok(Box::new(recipient)),
+    }
+}
+
+/// Encrypts lines of log files. Generates one key, used in all files.
+pub(crate) struct LogCipher {
+    cipher: XChaCha20Poly1305,
+
+    /// The `age-key:` line with the encrypted key
+    key_line: String,
+}
+
+impl LogCipher {
+    pub(crate) fn new(
+        encryption: &Encryption,
+        passphrase: Option<&Passphrase>,
+    ) -> io::Result<LogCipher> {
+        let recipients: Vec<Box<dyn age::Recipient>> = match (encryption, passphrase) {
+            (Encryption::Passphrase, None) => {
+                return Err(invalid_input(
+                    "Log encryption passphrase is not set".to_owned(),
+                ));
+            }
+            (Encryption::Passphrase, Some(passphrase)) => vec![Box::new(
+                age::scrypt::Recipient::new(SecretString::from(passphrase.0.clone())),
+            )],
+            (Encryption::Recipients(recipients), _) => recipients
+                .iter()
+                .map(|recipient| parse_recipient(recipient))
+                .collect::<io::Result<_>>()?,
+        };
+        let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| &**r))
+            .map_err(|err| invalid_input(err.to_string()))?;
+
+        let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+        let mut encrypted_key = vec![];
+        let mut writer = encryptor.wrap_output(&mut encrypted_key)?;
+        writer.write_all(&key)?;
+        writer.finish()?;
+
+        Ok(LogCipher {
+            cipher: XChaCha20Poly1305::new(&key),
+            key_line: format!("{}{}", KEY_PREFIX, BASE64.encode(&encrypted_key)),
+        })
+    }
+
+    /// The line to write before encrypted lines when opening a file.
+    pub(crate) fn key_line(&self) -> &str {
+        &self.key_line
+    }
+
+    /// Encrypt a line. `line` can have newlines, those are preserved after decryption.
+    pub(crate) fn encrypt(&self, line: &str) -> String {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut bytes = nonce.to_vec();
+        // Only fails when the line is larger than 256 GiB
+        bytes.extend(self.cipher.encrypt(&nonce, line.as_bytes()).unwrap());
+        format!("{}{}", LINE_PREFIX, BASE64.encode(&bytes))
+    }
+}
+
+/// Decrypts encrypted log files. Decrypted keys are cached, so one `LogDecrypter` should be used
+/// for all files.
+pub struct LogDecrypter {
+    identities: Vec<Box<dyn age::Identity>>,
+
+    /// Maps `age-key:` lines to the decrypted keys
+    keys: HashMap<String, XChaCha20Poly1305>,
+}
+
+impl LogDecrypter {
+    /// Decrypt logs encrypted with `Encryption::Passphrase`.
+    pub fn with_passphrase(passphrase: &str) -> LogDecrypter {
+        let identity = age::scrypt::Identity::new(SecretString::from(passphrase.to_owned()));
+        LogDecrypter {
+            identities: vec![Box::new(identity)],
+            keys: HashMap::new(),
+        }
+    }
+
+    /// Decrypt logs encrypted with `Encryption::Recipients`, using the private keys in an age
+    /// identity file (as generated by `age-keygen`).
+    pub fn with_identity_file(path: &Path) -> io::Result<LogDecrypter> {
+        let identities = age::IdentityFile::from_file(path.to_string_lossy().into_owned())?
+            .into_identities()
+            .map_err(|err| invalid_input(err.to_string()))?;
+        Ok(LogDecrypter {
+            identities,
+            keys: HashMap::new(),
+        })
+    }
+
+    /// Decrypt a log file. Lines that are not encrypted are returned as they are.
+    pub fn decrypt_file(&mut self, path: &Path) -> io::Result<String> {
+        self.decrypt(&fs::read_to_string(path)?)
+    }
+
+    /// Decrypt contents of a log file. Lines that are not encrypted are returned as they are.
+    pub fn decrypt(&mut self, log: &str) -> io::Result<String> {
+        let mut ret = String::with_capacity(log.len());
+        // Key of the last `age-key:` line
+        let mut key: Option<&str> = None;
+        for (line_idx, line) in log.lines().enumerate() {
+            if let Some(encrypted_key) = line.strip_prefix(KEY_PREFIX) {
+                if !self.keys.contains_key(line) {
+                    let cipher = self.decrypt_key(encrypted_key)?;
+                    self.keys.insert(line.to_owned(), cipher);
+                }
+                key = Some(line);
+            } else if let Some(encrypted) = line.strip_prefix(LINE_PREFIX) {
+                let cipher = match key {
+                    None => {
+                        return Err(invalid_data(format!(
+                            "Line {}: encrypted line without a key",
+                            line_idx + 1
+                        )));
+                    }
+                    Some(key) => &self.keys[key],
+                };
+                let decrypted = decrypt_line(cipher, encrypted).ok_or_else(|| {
+                    invalid_data(format!("Line {}: can't decrypt line", line_idx + 1))
+                })?;
+                ret.push_str(&decrypted);
+                ret.push('\n');
+            } else {
+                ret.push_str(line);
+                ret.push('\n');
+            }
+        }
+        Ok(ret)
+    }
+
+    fn decrypt_key(&self, encrypted_key: &str) -> io::Result<XChaCha20Poly1305> {
+        let encrypted_key = BASE64
+            .decode(encrypted_key)
+            .map_err(|err| invalid_data(format!("Can't decode key: {}", err)))?;
+        let decryptor = age::Decryptor::new(&encrypted_key[..])
+            .map_err(|err| invalid_data(format!("Can't decrypt key: {}", err)))?;
+        let mut reader = decryptor
+            .decrypt(self.identities.iter().map(|identity| &**identity))
+            .map_err(|err| invalid_data(format!("Can't decrypt key: {}", err)))?;
+        let mut key = vec![];
+        reader.read_to_end(&mut key)?;
+        XChaCha20Poly1305::new_from_slice(&key)
+            .map_err(|_| invalid_data("Invalid key length".to_owned()))
+    }
+}
+
+/// Is the line written by an encrypting logger? Files with these lines need to be decrypted with a
+/// `LogDecrypter` to be read.
+pub(crate) fn is_encrypted_line(line: &str) -> bool {
+    line.starts_with(KEY_PREFIX) || line.starts_with(LINE_PREFIX)
+}
+
+fn decrypt_line(cipher: &XChaCha20Poly1305, encrypted: &str) -> Option<String> {
+    let bytes = BASE64.decode(encrypted).ok()?;
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let line = cipher.decrypt(XNonce::from_slice(nonce), ciphertext).ok()?;
+    String::from_utf8(line).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::reader::{read_log, read_log_file, LogLine, ReadLimit};
+    use age::secrecy::ExposeSecret;
+
+    #[test]
+    fn test_encryption() {
+        let identity = age::x25519::Identity::generate();
+        let cipher = LogCipher::new(
+            &Encryption::Recipients(vec![identity.to_public().to_string()]),
+            None,
+        )
+        .unwrap();
+
+        let lines = [
+            "",
+            "*** Logging started at 2019-10-17 18:30:00",
+            "[18:30:00] osa1: hi",
+        ];
+        let mut log = String::from("[18:29:00] osa1: not encrypted\n");
+        log.push_str(cipher.key_line());
+        log.push('\n');
+        for line in &lines {
+            log.push_str(&cipher.encrypt(line));
+            log.push('\n');
+        }
+        assert!(!log.contains("osa1: hi"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let identity_path = dir.path().join("key.txt");
+        fs::write(&identity_path, identity.to_string().expose_secret()).unwrap();
+
+        let mut decrypter = LogDecrypter::with_identity_file(&identity_path).unwrap();
+        assert_eq!(
+            decrypter.decrypt(&log).unwrap(),
+            "[18:29:00] osa1: not encrypted\n\
+             \n\
+             *** Logging started at 2019-10-17 18:30:00\n\
+             [18:30:00] osa1: hi\n"
+        );
+
+        // Reading encrypted logs
+        let log_path = dir.path().join("serv.txt");
+        fs::write(&log_path, &log).unwrap();
+        let err = read_log_file(&log_path, None).unwrap_err();
+        assert!(err.to_string().contains("is encrypted"));
+        let lines = read_log_file(&log_path, Some(&mut decrypter)).unwrap();
+        assert_eq!(lines.len(), 2);
+        let config = Config::default();
+        let limit = ReadLimit::Lines(1);
+        assert!(read_log(dir.path(), &config, "serv", None, limit, None, None).is_err());
+        let lines = read_log(
+            dir.path(),
+            &config,
+            "serv",
+            None,
+            limit,
+            None,
+            Some(&mut decrypter),
+        );
+        let lines: Vec<String> = lines.unwrap().iter().map(LogLine::to_string).collect();
+        assert_eq!(lines, vec!["osa1: hi"]);
+
+        // Wrong key
+        let other_identity = age::x25519::Identity::generate();
+        fs::write(&identity_path, other_identity.to_string().expose_secret()).unwrap();
+        let mut decrypter = LogDecrypter::with_identity_file(&identity_path).unwrap();
+        assert!(decrypter.decrypt(&log).is_err());
+
+        // Encrypted line without a key
+        let mut decrypter = LogDecrypter::with_identity_file(&identity_path).unwrap();
+        assert!(decrypter.decrypt(&cipher.encrypt("hi")).is_err());
+
+        assert!(LogCipher::new(&Encryption::Recipients(vec!["age1xyz".to_owned()]), None).is_err());
+        assert!(LogCipher::new(&Encryption::Passphrase, None).is_err());
+        assert!(is_encrypted_line(cipher.key_line()));
+        assert!(!is_encrypted_line("[18:29:00] osa1: not encrypted"));
+    }
+}
@@ -0,0 +1,250 @@
+//! Log formatters. A formatter renders logged events as lines in log files.
+
+use serde::{Deserialize, Serialize};
+use time::Tm;
+
+use crate::config::Rotation;
+
+/// Type of a logged event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventType {
+    /// A message generated by the client
+    Client,
+    /// A message from the server
+    Msg,
+    /// PRIVMSG
+    Privmsg,
+    /// IRCv3 reaction. `text` is the reaction.
+    Reaction,
+    /// `sender` joined the channel
+    Join,
+    /// `sender` left the channel or the server
+    Part,
+    /// `sender` changed nick. `text` is the new nick.
+    Nick,
+    /// Channel topic. `text` is the topic.
+    Topic,
+    /// NOTICE
+    Notice,
+    /// An IRC error, e.g. "Nickname is already in use"
+    Error,
+    /// An error generated by the client, e.g. "Unsupported command"
+    ClientError,
+    /// `sender` kicked a user. `text` is the kicked nick, followed by the reason in parens when
+    /// there's one.
+    Kick,
+    /// `sender` changed channel modes. `text` is the modes, e.g. `+o osa1`.
+    Mode,
+    /// `sender` set the channel topic. `text` is when the topic was set, empty when not known.
+    TopicSetBy,
+}
+
+/// A logged event.
+pub struct Event<'a> {
+    pub ty: EventType,
+    pub sender: Option<&'a str>,
+    pub text: &'a str,
+    pub ts: Tm,
+    pub is_action: bool,
+    pub highlight: bool,
+    /// Unique id of the message (IRCv3 `msgid` tag), if known
+    pub msgid: Option<&'a str>,
+}
+
+impl<'a> Event<'a> {
+    pub(crate) fn new(ty: EventType, text: &'a str, ts: Tm) -> Event<'a> {
+        Event {
+            ty,
+            sender: None,
+            text,
+            ts,
+            is_action: false,
+            highlight: false,
+            msgid: None,
+        }
+    }
+
+    pub(crate) fn sender(self, sender: &'a str) -> Event<'a> {
+        Event {
+            sender: Some(sender),
+            ..self
+        }
+    }
+}
+
+pub trait Formatter {
+    /// Extension of log files, without the dot.
+    fn file_extension(&self, rotation: Rotation) -> &'static str;
+
+    /// Render an event, without a trailing newline. `serv` is the server of the log file, `target`
+    /// is the channel or nick, `None` for server logs.
+    fn format(&self, serv: &str, target: Option<&str>, event: &Event) -> String;
+
+    /// Lines written when a log file is opened.
+    fn header(&self, now: &Tm) -> Option<String>;
+
+    /// Lines written when the date changes during a session.
+    fn date_change(&self, now: &Tm) -> Option<String>;
+}
+
+/// Text of an event in text logs, without the timestamp.
+pub(crate) fn event_text(ty: EventType, sender: &str, text: &str, is_action: bool) -> String {
+    match ty {
+        EventType::Client => format!("[client] {}", text),
+        EventType::Msg => text.to_owned(),
+        EventType::Privmsg => {
+            if is_action {
+                format!("{} {}", sender, text)
+            } else {
+                format!("{}: {}", sender, text)
+            }
+        }
+        EventType::Reaction => format!("{} reacted with {}", sender, text),
+        EventType::Join => format!("{} joined the channel.", sender),
+        EventType::Part => format!("{} left.", sender),
+        EventType::Nick => format!("{} is now known as {}.", sender, text),
+        EventType::Topic => format!("Channel topic: {}.", text),
+        EventType::Notice => format!("-{}- {}", sender, text),
+        EventType::Error => format!("[error] {}", text),
+        EventType::ClientError => format!("[client error] {}", text),
+        EventType::Kick => format!("{} kicked {}", sender, text),
+        EventType::Mode => format!("{} sets mode {}", sender, text),
+        EventType::TopicSetBy => {
+            if text.is_empty() {
+                format!("Topic set by {}", sender)
+            } else {
+                format!("Topic set by {} on {}", sender, text)
+            }
+        }
+    }
+}
+
+/// The plain text format: `[18:30:00] osa1: hi`.
+pub struct TextFormatter {
+    /// Use full ISO 8601 timestamps instead of `%H:%M:%S`
+    pub iso_timestamps: bool,
+}
+
+impl Formatter for TextFormatter {
+    fn file_extension(&self, rotation: Rotation) -> &'static str {
+        match rotation {
+            Rotation::None => "txt",
+            Rotation::Daily | Rotation::Monthly => "log",
+        }
+    }
+
+    fn format(&self, _serv: &str, _target: Option<&str>, event: &Event) -> String {
+        let ts = if self.iso_timestamps {
+            event.ts.rfc3339().to_string()
+        } else {
+            time::strftime("%H:%M:%S", &event.ts).unwrap()
+        };
+        let sender = event.sender.unwrap_or("");
+        format!(
+            "[{}] {}",
+            ts,
+            event_text(event.ty, sender, event.text, event.is_action)
+        )
+    }
+
+    fn header(&self, now: &Tm) -> Option<String> {
+        Some(format!(
+            "\n*** Logging started at {}\n",
+            time::strftime("%Y-%m-%d %H:%M:%S", now).unwrap()
+        ))
+    }
+
+    fn date_change(&self, now: &Tm) -> Option<String> {
+        Some(format!(
+            "\n*** Date changed to {}\n",
+            time::strftime("%Y-%m-%d", now).unwrap()
+        ))
+    }
+}
+
+/// JSON Lines format: one JSON object per event. Timestamps are in ISO 8601.
+pub struct JsonFormatter;
+
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    #[serde(rename = "type")]
+    ty: EventType,
+    server: &'a str,
+    target: Option<&'a str>,
+    sender: Option<&'a str>,
+    text: &'a str,
+    timestamp: String,
+    is_action: bool,
+    highlight: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msgid: Option<&'a str>,
+}
+
+impl Formatter for JsonFormatter {
+    fn file_extension(&self, _rotation: Rotation) -> &'static str {
+        "jsonl"
+    }
+
+    fn format(&self, serv: &str, target: Option<&str>, event: &Event) -> String {
+        serde_json::to_string(&JsonEvent {
+            ty: event.ty,
+            server: serv,
+            target,
+            sender: event.sender,
+            text: event.text,
+            timestamp: event.ts.rfc3339().to_string(),
+            is_action: event.is_action,
+            highlight: event.highlight,
+            msgid: event.msgid,
+        })
+        .unwrap()
+    }
+
+    fn header(&self, _now: &Tm) -> Option<String> {
+        None
+    }
+
+    fn date_change(&self, _now: &Tm) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatters() {
+        let ts = time::at_utc(time::Timespec::new(1571337000, 0));
+        let event = Event {
+            is_action: true,
+            msgid: Some("abc"),
+            ..Event::new(EventType::Privmsg, "waves \"hi\"", ts).sender("osa1")
+        };
+
+        let text = TextFormatter {
+            iso_timestamps: false,
+        };
+        assert_eq!(
+            text.format("irc.freenode.net", Some("#tiny"), &event),
+            "[18:30:00] osa1 waves \"hi\""
+        );
+
+        assert_eq!(
+            JsonFormatter.format("irc.freenode.net", Some("#tiny"), &event),
+            "{\"type\":\"privmsg\",\"server\":\"irc.freenode.net\",\"target\":\"#tiny\",\
+             \"sender\":\"osa1\",\"text\":\"waves \\\"hi\\\"\",\
+             \"timestamp\":\"2019-10-17T18:30:00Z\",\"is_action\":true,\"highlight\":false,\
+             \"msgid\":\"abc\"}"
+        );
+
+        let event = Event::new(EventType::Topic, "tiny IRC client", ts);
+        assert_eq!(
+            JsonFormatter.format("irc.freenode.net", Some("#tiny"), &event),
+            "{\"type\":\"topic\",\"server\":\"irc.freenode.net\",\"target\":\"#tiny\",\
+             \"sender\":null,\"text\":\"tiny IRC client\",\
+             \"timestamp\":\"2019-10-17T18:30:00Z\",\"is_action\":false,\"highlight\":false}"
+        );
+    }
+}
@@ -1,3 +1,24 @@
+#![allow(clippy::too_many_arguments)]
+
+mod config;
+mod encryption;
+mod format;
+mod paths;
+mod reader;
+mod rules;
+mod search;
+
+pub use config::{Config, Format, Rotation};
+pub use encryption::{Encryption, LogDecrypter, Passphrase};
+pub use format::{Event, EventType, Formatter, JsonFormatter, TextFormatter};
+pub use paths::{casemap, escape_name, parse_log_path, unescape_name, LogFileInfo};
+pub use reader::{log_end, read_log, read_log_file, LogEnd, LogLine, ReadLimit};
+pub use rules::LogRules;
+pub use search::{read_context, search_logs, SearchQuery, SearchResult};
+
+use encryption::LogCipher;
+use paths::{log_file_path, log_path};
+
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
@@ -5,7 +26,7 @@ use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::Result;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use time;
 use time::Tm;
@@ -21,11 +42,42 @@ pub struct Logger {
 }
 
 impl Logger {
-    pub fn new(log_dir: PathBuf, report_err: Box<dyn Fn(String)>) -> Result<Logger> {
+    pub fn new(
+        log_dir: PathBuf,
+        config: Config,
+        report_err: Box<dyn Fn(String)>,
+    ) -> Result<Logger> {
+        let formatter = config.formatter();
+        let logger = Logger::with_formatter(log_dir, config.rotation, formatter, report_err)?;
+        {
+            let mut inner = logger.inner.borrow_mut();
+            inner.ignore = config.ignore;
+            if let Some(ref encryption) = config.encryption {
+                inner.cipher = Some(LogCipher::new(encryption, config.passphrase.as_ref())?);
+            }
+        }
+        Ok(logger)
+    }
+
+    /// Create a logger with a custom formatter.
+    pub fn with_formatter(
+        log_dir: PathBuf,
+        rotation: Rotation,
+        formatter: Box<dyn Formatter>,
+        report_err: Box<dyn Fn(String)>,
+    ) -> Result<Logger> {
         Ok(Logger {
-            inner: Rc::new(RefCell::new(LoggerInner::new(log_dir, report_err)?)),
+            inner: Rc::new(RefCell::new(LoggerInner::new(
+                log_dir, rotation, formatter, report_err,
+            )?)),
         })
     }
+
+    /// Set what to log in a server. Everything is logged in servers without rules. Rules are
+    /// checked when tabs are created, so this should be called before creating the server's tabs.
+    pub fn set_rules(&self, serv: &str, rules: LogRules) {
+        self.inner.borrow_mut().rules.insert(serv.to_owned(), rules);
+    }
 }
 
 macro_rules! delegate {
@@ -42,6 +94,7 @@ impl UI for Logger {
     delegate!(close_server_tab(serv: &str,));
     delegate!(new_chan_tab(serv: &str, chan: &str,));
     delegate!(close_chan_tab(serv: &str, chan: &str,));
+    delegate!(new_user_tab(serv: &str, nick: &str,));
     delegate!(close_user_tab(serv: &str, nick: &str,));
     delegate!(add_client_msg(msg: &str, target: &MsgTarget,));
     delegate!(add_msg(msg: &str, ts: Tm, target: &MsgTarget,));
@@ -56,6 +109,21 @@ impl UI for Logger {
         target: &MsgTarget,
         highlight: bool,
         is_action: bool,
+        tags: MsgTags,
+    ));
+    delegate!(add_reaction(
+        sender: &str,
+        reaction: &str,
+        msgid: &str,
+        ts: Tm,
+        target: &MsgTarget,
+    ));
+    delegate!(add_mention(
+        sender: &str,
+        msg: &str,
+        ts: Tm,
+        serv: &str,
+        chan: &str,
     ));
     delegate!(add_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
     delegate!(remove_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
@@ -66,11 +134,18 @@ impl UI for Logger {
         target: &MsgTarget,
     ));
     delegate!(set_topic(topic: &str, ts: Tm, serv: &str, chan: &str,));
+    delegate!(add_chan_event(
+        event: ChanEvent,
+        ts: Tm,
+        serv: &str,
+        chan: &str,
+    ));
     delegate!(set_tab_style(style: TabStyle, target: &MsgTarget,));
+    delegate!(show_chan_list(serv: &str, chans: &[ChanListEntry],));
+    delegate!(add_raw_msg(serv: &str, msg: &str, ts: Tm, outgoing: bool,));
 
-    // TODO: Maybe just return true?
-    fn user_tab_exists(&self, _serv: &str, _nick: &str) -> bool {
-        false
+    fn user_tab_exists(&self, serv: &str, nick: &str) -> bool {
+        self.inner.borrow().user_tab_exists(serv, nick)
     }
 }
 
@@ -78,6 +153,19 @@ struct LoggerInner {
     /// Log file directory
     log_dir: PathBuf,
 
+    rotation: Rotation,
+
+    formatter: Box<dyn Formatter>,
+
+    /// Encrypts log files when encryption is enabled
+    cipher: Option<LogCipher>,
+
+    /// Events that are not logged
+    ignore: Vec<EventType>,
+
+    /// Maps server names to their rules. See `Logger::set_rules`.
+    rules: HashMap<String, LogRules>,
+
     /// Maps server names to their fds
     servers: HashMap<String, ServerLogs>,
 
@@ -85,22 +173,6 @@ struct LoggerInner {
     report_err: Box<dyn Fn(String)>,
 }
 
-struct ServerLogs {
-    fd: File,
-    chans: HashMap<String, File>,
-    users: HashMap<String, File>,
-}
-
-fn print_header(fd: &mut File) -> Result<()> {
-    writeln!(fd)?;
-    writeln!(
-        fd,
-        "*** Logging started at {}",
-        time::strftime("%Y-%m-%d %H:%M:%S", &time::now()).unwrap()
-    )?;
-    writeln!(fd)
-}
-
 macro_rules! report_io_err {
     ( $f:expr, $e:expr ) => {
         match $e {
@@ -113,8 +185,135 @@ macro_rules! report_io_err {
     };
 }
 
+struct ServerLogs {
+    fd: LogFile,
+    /// Keys are casemapped channel names, as `#tiny` and `#Tiny` are the same channel
+    chans: HashMap<String, LogFile>,
+    /// Keys are casemapped nicks
+    users: HashMap<String, LogFile>,
+}
+
+/// A log file of a tab. When logs are rotated the file is reopened on the first write after the
+/// date changes.
+struct LogFile {
+    fd: File,
+
+    /// Server of the tab
+    serv: String,
+
+    /// Channel or nick of the tab, `None` for server tabs
+    target: Option<String>,
+
+    /// Path of the log file when logs are not rotated, directory of the log files otherwise. See
+    /// `log_path`.
+    path: PathBuf,
+
+    /// Path of the currently open file
+    file_path: PathBuf,
+
+    /// Date of the last write, as (year, day of year). Used to print date change markers.
+    date: (i32, i32),
+}
+
+impl LogFile {
+    fn open(
+        log_dir: &Path,
+        serv: &str,
+        target: Option<&str>,
+        rotation: Rotation,
+        formatter: &dyn Formatter,
+        cipher: Option<&LogCipher>,
+    ) -> Result<LogFile> {
+        let now = time::now();
+        let ext = formatter.file_extension(rotation);
+        let path = log_path(log_dir, rotation, ext, serv, target);
+        let file_path = log_file_path(&path, rotation, ext, &now);
+        let mut fd = open_append(&file_path, cipher)?;
+        if let Some(header) = formatter.header(&now) {
+            write_line(&mut fd, cipher, &header)?;
+        }
+        Ok(LogFile {
+            fd,
+            serv: serv.to_owned(),
+            target: target.map(str::to_owned),
+            path,
+            file_path,
+            date: (now.tm_year, now.tm_yday),
+        })
+    }
+
+    /// Called before writing to the file. When the date changed since the last write prints a
+    /// date change marker, or opens a new file with a header if the log should be rotated.
+    fn check_date(
+        &mut self,
+        rotation: Rotation,
+        formatter: &dyn Formatter,
+        cipher: Option<&LogCipher>,
+    ) -> Result<()> {
+        let now = time::now();
+        let date = (now.tm_year, now.tm_yday);
+        if date == self.date {
+            return Ok(());
+        }
+        self.date = date;
+
+        let ext = formatter.file_extension(rotation);
+        let file_path = log_file_path(&self.path, rotation, ext, &now);
+        let marker = if file_path != self.file_path {
+            self.fd = open_append(&file_path, cipher)?;
+            self.file_path = file_path;
+            formatter.header(&now)
+        } else {
+            formatter.date_change(&now)
+        };
+
+        match marker {
+            None => Ok(()),
+            Some(marker) => write_line(&mut self.fd, cipher, &marker),
+        }
+    }
+
+    /// Writes an event to the file, rotating the file first if necessary.
+    fn write(
+        &mut self,
+        rotation: Rotation,
+        formatter: &dyn Formatter,
+        cipher: Option<&LogCipher>,
+        event: &Event,
+    ) -> Result<()> {
+        self.check_date(rotation, formatter, cipher)?;
+        let line = formatter.format(&self.serv, self.target.as_deref(), event);
+        write_line(&mut self.fd, cipher, &line)
+    }
+}
+
+/// Opens a log file for appending. When encrypting, writes the key of the following lines.
+fn open_append(path: &Path, cipher: Option<&LogCipher>) -> Result<File> {
+    debug!("Trying to open log file: {:?}", path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut fd = OpenOptions::new().create(true).append(true).open(path)?;
+    if let Some(cipher) = cipher {
+        writeln!(fd, "{}", cipher.key_line())?;
+    }
+    Ok(fd)
+}
+
+fn write_line(fd: &mut File, cipher: Option<&LogCipher>, line: &str) -> Result<()> {
+    match cipher {
+        None => writeln!(fd, "{}", line),
+        Some(cipher) => writeln!(fd, "{}", cipher.encrypt(line)),
+    }
+}
+
 impl LoggerInner {
-    fn new(log_dir: PathBuf, report_err: Box<dyn Fn(String)>) -> Result<LoggerInner> {
+    fn new(
+        log_dir: PathBuf,
+        rotation: Rotation,
+        formatter: Box<dyn Formatter>,
+        report_err: Box<dyn Fn(String)>,
+    ) -> Result<LoggerInner> {
         if let Err(err) = fs::create_dir(&log_dir) {
             if err.kind() != io::ErrorKind::AlreadyExists {
                 return Err(err);
@@ -123,24 +322,44 @@ impl LoggerInner {
 
         Ok(LoggerInner {
             log_dir,
+            rotation,
+            formatter,
+            cipher: None,
+            ignore: vec![],
+            rules: HashMap::new(),
             servers: HashMap::new(),
             report_err,
         })
     }
 
+    fn open(&self, serv: &str, target: Option<&str>) -> Result<LogFile> {
+        LogFile::open(
+            &self.log_dir,
+            serv,
+            target,
+            self.rotation,
+            &*self.formatter,
+            self.cipher.as_ref(),
+        )
+    }
+
+    /// Should the tab be logged? `target` is the channel or nick, `None` for server tabs. Raw
+    /// protocol tabs follow the rules of their servers.
+    fn is_logged(&self, serv: &str, target: Option<&str>) -> bool {
+        let serv = raw_tab_serv(serv).unwrap_or(serv);
+        match self.rules.get(serv) {
+            None => true,
+            Some(rules) => rules.logs(target),
+        }
+    }
+
     fn new_server_tab(&mut self, serv: &str) {
-        if self.servers.contains_key(serv) {
+        if serv == LOG_SEARCH_TAB || !self.is_logged(serv, None) || self.servers.contains_key(serv)
+        {
             return;
         }
 
-        let mut path = self.log_dir.clone();
-        path.push(&format!("{}.txt", serv));
-        debug!("Trying to open log file: {:?}", path);
-        let mut fd = report_io_err!(
-            self.report_err,
-            OpenOptions::new().create(true).append(true).open(path)
-        );
-        report_io_err!(self.report_err, print_header(&mut fd));
+        let fd = report_io_err!(self.report_err, self.open(serv, None));
 
         self.servers.insert(
             serv.to_string(),
@@ -157,24 +376,27 @@ impl LoggerInner {
     }
 
     fn new_chan_tab(&mut self, serv: &str, chan: &str) {
+        if !self.is_logged(serv, Some(chan)) {
+            return;
+        }
         if !self.servers.contains_key(serv) {
             (self.report_err)(format!("Logger::new_chan_tab: can't find server: {}", serv));
             return;
         }
 
+        if self.servers[serv].chans.contains_key(&casemap(chan)) {
+            return;
+        }
+
+        let fd = report_io_err!(self.report_err, self.open(serv, Some(chan)));
         let server = self.servers.get_mut(serv).unwrap();
-        let mut path = self.log_dir.clone();
-        path.push(&format!("{}_{}.txt", serv, chan));
-        debug!("Trying to open log file: {:?}", path);
-        let mut fd = report_io_err!(
-            self.report_err,
-            OpenOptions::new().create(true).append(true).open(path)
-        );
-        report_io_err!(self.report_err, print_header(&mut fd));
-        server.chans.insert(chan.to_string(), fd);
+        server.chans.insert(casemap(chan), fd);
     }
 
     fn close_chan_tab(&mut self, serv: &str, chan: &str) {
+        if !self.is_logged(serv, None) {
+            return;
+        }
         if !self.servers.contains_key(serv) {
             (self.report_err)(format!(
                 "Logger::close_chan_tab: can't find server: {}",
@@ -184,12 +406,30 @@ impl LoggerInner {
         }
 
         let server = self.servers.get_mut(serv).unwrap();
-        server.chans.remove(chan);
+        server.chans.remove(&casemap(chan));
     }
 
-    // TODO: Where's new_user_tab?
+    fn new_user_tab(&mut self, serv: &str, nick: &str) {
+        if !self.is_logged(serv, Some(nick)) {
+            return;
+        }
+        if !self.servers.contains_key(serv) {
+            (self.report_err)(format!("Logger::new_user_tab: can't find server: {}", serv));
+            return;
+        }
+        if self.servers[serv].users.contains_key(&casemap(nick)) {
+            return;
+        }
+
+        let fd = report_io_err!(self.report_err, self.open(serv, Some(nick)));
+        let server = self.servers.get_mut(serv).unwrap();
+        server.users.insert(casemap(nick), fd);
+    }
 
     fn close_user_tab(&mut self, serv: &str, nick: &str) {
+        if !self.is_logged(serv, None) {
+            return;
+        }
         if !self.servers.contains_key(serv) {
             (self.report_err)(format!(
                 "Logger::close_user_tab: can't find server: {}",
@@ -199,38 +439,43 @@ impl LoggerInner {
         }
 
         let server = self.servers.get_mut(serv).unwrap();
-        server.users.remove(nick);
+        server.users.remove(&casemap(nick));
+    }
+
+    fn user_tab_exists(&self, serv: &str, nick: &str) -> bool {
+        match self.servers.get(serv) {
+            None => false,
+            Some(server) => server.users.contains_key(&casemap(nick)),
+        }
     }
 
     fn add_client_msg(&mut self, msg: &str, target: &MsgTarget) {
-        let now = now();
-        self.apply_to_target(target, move |fd: &mut File| {
-            // TODO: Report errors?
-            let _ = writeln!(fd, "[{}] [client] {}", now, msg);
-        });
+        self.log_event(target, &Event::new(EventType::Client, msg, time::now()));
     }
 
     fn add_msg(&mut self, msg: &str, ts: Tm, target: &MsgTarget) {
-        self.apply_to_target(target, |fd: &mut File| {
-            // TODO: Report errors?
-            let _ = writeln!(fd, "[{}] {}", strf(&ts), msg);
-        });
+        self.log_event(target, &Event::new(EventType::Msg, msg, ts));
     }
 
-    fn add_err_msg(&self, _msg: &str, _ts: Tm, _target: &MsgTarget) {
-        // IRC error messages are ignored
+    fn add_err_msg(&mut self, msg: &str, ts: Tm, target: &MsgTarget) {
+        self.log_event(target, &Event::new(EventType::Error, msg, ts));
     }
 
-    fn add_client_err_msg(&self, _msg: &str, _target: &MsgTarget) {
-        // Ditto with client error messages
+    fn add_client_err_msg(&mut self, msg: &str, target: &MsgTarget) {
+        let event = Event::new(EventType::ClientError, msg, time::now());
+        self.log_event(target, &event);
     }
 
     fn clear_nicks(&self, _serv: &str) {
         // Nothing to do here
     }
 
-    fn set_nick(&self, _serv: &str, _nick: &str) {
-        // Ditto
+    fn set_nick(&mut self, serv: &str, nick: &str) {
+        // Nick changes are logged in channels by `rename_nick`, this logs our nick in the server
+        // log, e.g. after registration
+        let msg = format!("Your nick is now {}", nick);
+        let event = Event::new(EventType::Client, &msg, time::now());
+        self.log_event(&MsgTarget::Server { serv }, &event);
     }
 
     fn add_privmsg(
@@ -239,115 +484,199 @@ impl LoggerInner {
         msg: &str,
         ts: Tm,
         target: &MsgTarget,
-        _highlight: bool,
+        highlight: bool,
         is_action: bool,
+        tags: MsgTags,
     ) {
-        self.apply_to_target(target, |fd: &mut File| {
-            // TODO: Report errors?
-            if is_action {
-                let _ = writeln!(fd, "[{}] {} {}", strf(&ts), sender, msg);
-            } else {
-                let _ = writeln!(fd, "[{}] {}: {}", strf(&ts), sender, msg);
+        // Messages from or to a user in the server tab (e.g. NickServ notices) follow the user's
+        // rules
+        if let (MsgTarget::Server { serv }, Some(peer)) = (target, tags.peer) {
+            if !self.is_logged(serv, Some(peer)) {
+                return;
             }
-        });
+        }
+        let ty = if tags.is_notice {
+            EventType::Notice
+        } else {
+            EventType::Privmsg
+        };
+        let event = Event {
+            is_action,
+            highlight,
+            msgid: tags.msgid,
+            ..Event::new(ty, msg, ts).sender(sender)
+        };
+        self.log_event(target, &event);
+    }
+
+    fn add_reaction(
+        &mut self,
+        sender: &str,
+        reaction: &str,
+        _msgid: &str,
+        ts: Tm,
+        target: &MsgTarget,
+    ) {
+        let event = Event::new(EventType::Reaction, reaction, ts).sender(sender);
+        self.log_event(target, &event);
+    }
+
+    fn add_mention(&mut self, sender: &str, msg: &str, ts: Tm, serv: &str, chan: &str) {
+        // Mentions are copied from the channel, don't copy them from channels that are not logged
+        if !self.is_logged(serv, Some(chan)) {
+            return;
+        }
+        let msg = mention_msg(sender, msg, serv, chan);
+        let event = Event::new(EventType::Msg, &msg, ts);
+        self.log_event(&MsgTarget::Server { serv: MENTIONS_TAB }, &event);
     }
 
     fn add_nick(&mut self, nick: &str, ts: Option<Tm>, target: &MsgTarget) {
-        if let Some(_ts) = ts {
+        if let Some(ts) = ts {
             // This method is only called when a user joins a chan
-            self.apply_to_target(target, |fd: &mut File| {
-                // TODO: Report errors?
-                let _ = writeln!(fd, "[{}] {} joined the channel.", now(), nick);
-            });
+            self.log_event(target, &Event::new(EventType::Join, "", ts).sender(nick));
         }
     }
 
     fn remove_nick(&mut self, nick: &str, ts: Option<Tm>, target: &MsgTarget) {
-        if let Some(_ts) = ts {
+        if let Some(ts) = ts {
             // TODO: Did the user leave a channel or the server? Currently we can't tell.
-            self.apply_to_target(target, |fd: &mut File| {
-                // TODO: Report errors?
-                let _ = writeln!(fd, "[{}] {} left.", now(), nick);
-            });
+            self.log_event(target, &Event::new(EventType::Part, "", ts).sender(nick));
         }
     }
 
     fn rename_nick(&mut self, old_nick: &str, new_nick: &str, ts: Tm, target: &MsgTarget) {
-        self.apply_to_target(target, |fd: &mut File| {
-            // TODO: Report errors?
-            let _ = writeln!(
-                fd,
-                "[{}] {} is now known as {}.",
-                strf(&ts),
-                old_nick,
-                new_nick
-            );
-        });
+        let event = Event::new(EventType::Nick, new_nick, ts).sender(old_nick);
+        self.log_event(target, &event);
+
+        // Private conversations continue in the same log file after a nick change, unless the new
+        // nick is not logged
+        if let MsgTarget::User { serv, nick } = *target {
+            let fd = self
+                .servers
+                .get_mut(serv)
+                .and_then(|server| server.users.remove(&casemap(nick)));
+            match fd {
+                Some(mut fd) => {
+                    if self.is_logged(serv, Some(new_nick)) {
+                        fd.target = Some(new_nick.to_owned());
+                        let server = self.servers.get_mut(serv).unwrap();
+                        server.users.insert(casemap(new_nick), fd);
+                    }
+                }
+                None => self.new_user_tab(serv, new_nick),
+            }
+        }
     }
 
     fn set_topic(&mut self, topic: &str, ts: Tm, serv: &str, chan: &str) {
         let target = MsgTarget::Chan { serv, chan };
-        self.apply_to_target(&target, |fd: &mut File| {
-            // TODO: Report errors?
-            let _ = writeln!(fd, "[{}] Channel topic: {}.", strf(&ts), topic);
-        });
+        self.log_event(&target, &Event::new(EventType::Topic, topic, ts));
+    }
+
+    fn add_chan_event(&mut self, event: ChanEvent, ts: Tm, serv: &str, chan: &str) {
+        let target = MsgTarget::Chan { serv, chan };
+        let (ty, sender, text) = match event {
+            ChanEvent::Kick {
+                kicker,
+                nick,
+                reason,
+            } => match reason {
+                None => (EventType::Kick, kicker, nick.to_owned()),
+                Some(reason) => (EventType::Kick, kicker, format!("{} ({})", nick, reason)),
+            },
+            ChanEvent::Mode { setter, modes } => (EventType::Mode, setter, modes.to_owned()),
+            ChanEvent::TopicSetBy { setter, set_at } => {
+                let set_at = set_at.map(|set_at| set_at.strftime("%F %T").unwrap().to_string());
+                (EventType::TopicSetBy, setter, set_at.unwrap_or_default())
+            }
+        };
+        self.log_event(&target, &Event::new(ty, &text, ts).sender(sender));
     }
 
     fn set_tab_style(&self, _: TabStyle, _: &MsgTarget) {
         // Nothing to do here
     }
 
-    fn apply_to_target(&mut self, target: &MsgTarget, f: impl Fn(&mut File)) {
+    fn show_chan_list(&self, _serv: &str, _chans: &[ChanListEntry]) {
+        // Channel lists are not logged
+    }
+
+    fn add_raw_msg(&self, _serv: &str, _msg: &str, _ts: Tm, _outgoing: bool) {
+        // Raw messages are not logged, they may have passwords (PASS, AUTHENTICATE etc.)
+    }
+
+    fn log_event(&mut self, target: &MsgTarget, event: &Event) {
+        if self.ignore.contains(&event.ty) {
+            return;
+        }
+        let logged = match *target {
+            MsgTarget::Server { serv } => self.is_logged(serv, None),
+            MsgTarget::Chan { serv, chan } => self.is_logged(serv, Some(chan)),
+            MsgTarget::User { serv, nick } => self.is_logged(serv, Some(nick)),
+            _ => true,
+        };
+        if !logged {
+            return;
+        }
+        let rotation = self.rotation;
+        let formatter = &*self.formatter;
+        let cipher = self.cipher.as_ref();
+        let report_err = &self.report_err;
+        let write = |fd: &mut LogFile| {
+            if let Err(err) = fd.write(rotation, formatter, cipher, event) {
+                report_err(format!("{:?}", err));
+            }
+        };
         match *target {
+            MsgTarget::Server { serv } if serv == LOG_SEARCH_TAB => {}
             MsgTarget::Server { serv } => {
                 if !self.servers.contains_key(serv) {
-                    (self.report_err)(format!("Logger: can't find server: {}", serv));
+                    report_err(format!("Logger: can't find server: {}", serv));
                     return;
                 }
                 let ServerLogs { ref mut fd, .. } = self.servers.get_mut(serv).unwrap();
-                f(fd);
+                write(fd);
             }
             MsgTarget::Chan { serv, chan } => {
                 if !self.servers.contains_key(serv) {
-                    (self.report_err)(format!("Logger: can't find server: {}", serv));
+                    report_err(format!("Logger: can't find server: {}", serv));
                     return;
                 }
                 let ServerLogs { ref mut chans, .. } = self.servers.get_mut(serv).unwrap();
-                if !chans.contains_key(chan) {
-                    (self.report_err)(format!(
-                        "Logger: can't find chan {} in server {}",
-                        chan, serv
-                    ));
-                    return;
-                }
-                let fd = chans.get_mut(chan).unwrap();
-                f(fd);
+                let fd = match chans.get_mut(&casemap(chan)) {
+                    None => {
+                        report_err(format!(
+                            "Logger: can't find chan {} in server {}",
+                            chan, serv
+                        ));
+                        return;
+                    }
+                    Some(fd) => fd,
+                };
+                write(fd);
             }
             MsgTarget::User { serv, nick } => {
                 if !self.servers.contains_key(serv) {
-                    (self.report_err)(format!("Logger: can't find server: {}", serv));
+                    report_err(format!("Logger: can't find server: {}", serv));
                     return;
                 }
                 let ServerLogs { ref mut users, .. } = self.servers.get_mut(serv).unwrap();
-                if !users.contains_key(nick) {
-                    // We don't have a `new_user_tab` trait method so user log files are created
-                    // here
-                    let mut path = self.log_dir.clone();
-                    path.push(&format!("{}_{}.txt", serv, nick));
-                    debug!("Trying to open log file: {:?}", path);
-                    let mut fd = report_io_err!(
-                        self.report_err,
-                        OpenOptions::new().create(true).append(true).open(path)
-                    );
-                    report_io_err!(self.report_err, print_header(&mut fd));
-                    users.insert(nick.to_owned(), fd);
-                }
-                let fd = users.get_mut(nick).unwrap();
-                f(fd);
+                let fd = match users.get_mut(&casemap(nick)) {
+                    None => {
+                        report_err(format!(
+                            "Logger: can't find user {} in server {}",
+                            nick, serv
+                        ));
+                        return;
+                    }
+                    Some(fd) => fd,
+                };
+                write(fd);
             }
             MsgTarget::AllServTabs { serv } => {
                 if !self.servers.contains_key(serv) {
-                    (self.report_err)(format!("Logger: can't find server: {}", serv));
+                    report_err(format!("Logger: can't find server: {}", serv));
                     return;
                 }
                 let ServerLogs {
@@ -356,12 +685,12 @@ impl LoggerInner {
                     ref mut users,
                     ..
                 } = self.servers.get_mut(serv).unwrap();
-                f(fd);
+                write(fd);
                 for (_, fd) in chans.iter_mut() {
-                    f(fd);
+                    write(fd);
                 }
                 for (_, fd) in users.iter_mut() {
-                    f(fd);
+                    write(fd);
                 }
             }
             MsgTarget::CurrentTab => {
@@ -371,10 +700,274 @@ impl LoggerInner {
     }
 }
 
-fn now() -> String {
-    time::strftime("%H:%M:%S", &time::now()).unwrap()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_log_follows_nick_change() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        let logger = Logger::new(
+            log_dir.to_path_buf(),
+            Config::default(),
+            Box::new(|err| panic!("{}", err)),
+        )
+        .unwrap();
+        let ts = time::now();
+
+        logger.new_server_tab("serv");
+        logger.new_user_tab("serv", "osa1");
+        assert!(logger.user_tab_exists("serv", "osa1"));
+        let target = MsgTarget::User {
+            serv: "serv",
+            nick: "osa1",
+        };
+        logger.add_privmsg("osa1", "hi", ts, &target, false, false, MsgTags::default());
+        logger.rename_nick("osa1", "osa1_", ts, &target);
+        let target = MsgTarget::User {
+            serv: "serv",
+            nick: "osa1_",
+        };
+        logger.add_privmsg(
+            "osa1_",
+            "hello",
+            ts,
+            &target,
+            false,
+            false,
+            MsgTags::default(),
+        );
+        logger.close_user_tab("serv", "osa1_");
+        assert!(!logger.user_tab_exists("serv", "osa1_"));
+
+        let log = fs::read_to_string(log_dir.join("serv_osa1.txt")).unwrap();
+        let lines: Vec<&str> = log.lines().filter(|line| line.starts_with('[')).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("] osa1: hi"));
+        assert!(lines[1].ends_with("] osa1 is now known as osa1_."));
+        assert!(lines[2].ends_with("] osa1_: hello"));
+        assert!(!log_dir.join("serv_osa1_.txt").exists());
+    }
+
+    #[test]
+    fn test_casemapped_tabs() {
+        let log_dir = tempfile::tempdir().unwrap();
+        let logger = Logger::new(
+            log_dir.path().to_path_buf(),
+            Config::default(),
+            Box::new(|err| panic!("{}", err)),
+        )
+        .unwrap();
+        let ts = time::now();
+
+        logger.new_server_tab("serv");
+        logger.new_chan_tab("serv", "#Tiny");
+        logger.new_chan_tab("serv", "#tiny");
+        for chan in &["#Tiny", "#tiny", "#TINY"] {
+            let target = MsgTarget::Chan { serv: "serv", chan };
+            logger.add_privmsg("osa1", chan, ts, &target, false, false, MsgTags::default());
+        }
+        logger.new_user_tab("serv", "Osa1");
+        assert!(logger.user_tab_exists("serv", "osa1"));
+        logger.close_chan_tab("serv", "#TINY");
+
+        let log = fs::read_to_string(log_dir.path().join("serv_#tiny.txt")).unwrap();
+        // One header: the file is opened once
+        assert_eq!(log.matches("*** Logging started at ").count(), 1);
+        let lines: Vec<&str> = log.lines().filter(|line| line.starts_with('[')).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].ends_with("] osa1: #TINY"));
+        let inner = logger.inner.borrow();
+        assert!(inner.servers["serv"].chans.is_empty());
+    }
+
+    #[test]
+    fn test_rotation_header() {
+        let log_dir = tempfile::tempdir().unwrap();
+        let formatter = Config::default().formatter();
+        let mut file = LogFile::open(
+            log_dir.path(),
+            "serv",
+            Some("#tiny"),
+            Rotation::Daily,
+            &*formatter,
+            None,
+        )
+        .unwrap();
+
+        // Date changes, and the file of the new date doesn't exist yet
+        let new_file_path = file.file_path.clone();
+        fs::remove_file(&new_file_path).unwrap();
+        file.date = (0, 0);
+        file.file_path = log_dir.path().join("serv/#tiny/1900-01-01.log");
+        file.check_date(Rotation::Daily, &*formatter, None).unwrap();
+
+        // New file starts with a header, so dates of the lines are known
+        let log = fs::read_to_string(&new_file_path).unwrap();
+        assert!(log.starts_with("\n*** Logging started at "));
+        assert!(!log.contains("*** Date changed to "));
+    }
+
+    #[test]
+    fn test_chan_events() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        let config = Config {
+            ignore: vec![EventType::Mode],
+            ..Config::default()
+        };
+        let logger = Logger::new(
+            log_dir.to_path_buf(),
+            config,
+            Box::new(|err| panic!("{}", err)),
+        )
+        .unwrap();
+        let ts = time::now();
+
+        logger.new_server_tab("serv");
+        logger.new_chan_tab("serv", "#tiny");
+        let target = MsgTarget::Chan {
+            serv: "serv",
+            chan: "#tiny",
+        };
+        let tags = MsgTags {
+            is_notice: true,
+            ..MsgTags::default()
+        };
+        logger.add_privmsg("ChanServ", "welcome", ts, &target, false, false, tags);
+        let kick = ChanEvent::Kick {
+            kicker: "osa1",
+            nick: "bob",
+            reason: Some("spam"),
+        };
+        logger.add_chan_event(kick, ts, "serv", "#tiny");
+        let mode = ChanEvent::Mode {
+            setter: "osa1",
+            modes: "+b bob!*@*",
+        };
+        logger.add_chan_event(mode, ts, "serv", "#tiny");
+        logger.add_err_msg("You're not channel operator", ts, &target);
+
+        let lines = read_log_file(&log_dir.join("serv_#tiny.txt"), None).unwrap();
+        let lines: Vec<String> = lines.iter().map(LogLine::to_string).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "-ChanServ- welcome",
+                "osa1 kicked bob (spam)",
+                "[error] You're not channel operator"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_log_rules() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        let logger = Logger::new(
+            log_dir.to_path_buf(),
+            Config::default(),
+            Box::new(|err| panic!("{}", err)),
+        )
+        .unwrap();
+        logger.set_rules(
+            "serv",
+            LogRules {
+                deny: vec!["#secret*".to_owned(), "NickServ".to_owned()],
+                ..LogRules::default()
+            },
+        );
+        logger.set_rules(
+            "private",
+            LogRules {
+                log: false,
+                ..LogRules::default()
+            },
+        );
+        logger.new_server_tab(MENTIONS_TAB);
+        let ts = time::now();
+
+        for serv in &["serv", "private"] {
+            logger.new_server_tab(serv);
+            logger.new_chan_tab(serv, "#tiny");
+            logger.new_chan_tab(serv, "#secret-ops");
+            logger.new_user_tab(serv, "nickserv");
+            logger.add_client_msg(
+                "hi",
+                &MsgTarget::Chan {
+                    serv,
+                    chan: "#tiny",
+                },
+            );
+            let target = MsgTarget::Chan {
+                serv,
+                chan: "#secret-ops",
+            };
+            logger.add_client_msg("hi", &target);
+            let target = MsgTarget::User {
+                serv,
+                nick: "nickserv",
+            };
+            logger.add_client_msg("hi", &target);
+
+            // Mentions are copied from the channels to the mentions tab
+            logger.add_mention("bob", "tiny: hi", ts, serv, "#tiny");
+            logger.add_mention("bob", "tiny: hi", ts, serv, "#secret-ops");
+
+            // Messages from and to users in the server tab follow the users' rules
+            let target = MsgTarget::Server { serv };
+            let notice = MsgTags {
+                is_notice: true,
+                ..MsgTags::default()
+            };
+            logger.add_privmsg("serv", "welcome", ts, &target, false, false, notice);
+            let tags = MsgTags {
+                peer: Some("NickServ"),
+                ..notice
+            };
+            logger.add_privmsg("NickServ", "identified", ts, &target, false, false, tags);
+            let tags = MsgTags {
+                peer: Some("NickServ"),
+                ..MsgTags::default()
+            };
+            logger.add_privmsg("tiny", "identify pass", ts, &target, false, false, tags);
+
+            // Raw tabs follow the server's rules
+            let raw_tab = raw_tab_name(serv);
+            logger.new_server_tab(&raw_tab);
+            logger.add_client_msg("raw", &MsgTarget::Server { serv: &raw_tab });
+
+            logger.close_chan_tab(serv, "#secret-ops");
+        }
 
-fn strf(tm: &Tm) -> String {
-    time::strftime("%H:%M:%S", tm).unwrap()
+        let mut files: Vec<String> = fs::read_dir(log_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "mentions.txt",
+                "serv (raw).txt",
+                "serv.txt",
+                "serv_#tiny.txt"
+            ]
+        );
+
+        let read_lines = |file: &str| -> Vec<String> {
+            read_log_file(&log_dir.join(file), None)
+                .unwrap()
+                .iter()
+                .map(LogLine::to_string)
+                .collect()
+        };
+        assert_eq!(
+            read_lines("mentions.txt"),
+            vec!["bob in serv:#tiny: tiny: hi"]
+        );
+        assert_eq!(read_lines("serv.txt"), vec!["-serv- welcome"]);
+        assert_eq!(read_lines("serv (raw).txt"), vec!["[client] raw"]);
+    }
 }
@@ -0,0 +1,297 @@
+//! Log file paths.
+//!
+//! Server, channel and nick names are casemapped (so `#tiny` and `#Tiny` share a log file) and
+//! escaped before being used in file names, so that names with `/`, `..`, control characters
+//! etc. can't create files outside of the log directory. Escaping is reversible: `parse_log_path`
+//! maps a log file back to the server and channel or nick.
+//!
+//! Layouts:
+//!
+//! - `Rotation::None`: `{serv}.txt` for servers, `{serv}_{chan}.txt` for channels and users.
+//! - `Rotation::Daily` and `Rotation::Monthly`: `{serv}/2019-10-17.log` for servers,
+//!   `{serv}/{chan}/2019-10-17.log` for channels and users.
+
+use std::path::{Path, PathBuf};
+use time::Tm;
+
+use crate::config::Rotation;
+
+/// Lowercase a name using the `rfc1459` casemapping, which is the default casemapping in IRC:
+/// `[]\~` are the uppercase versions of `{}|^`.
+pub fn casemap(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            'A'..='Z' => c.to_ascii_lowercase(),
+            '[' => '{',
+            ']' => '}',
+            '\\' => '|',
+            '~' => '^',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Escape a name to be used as (part of) a file name. Path separators, control characters,
+/// characters not allowed in Windows file names, a leading `.` and `%` are replaced with `%XX`
+/// (hex encoding of the byte). `_` is escaped too when `escape_underscore` is set (for server
+/// names, as `_` separates server and channel names in some layouts).
+fn escape(name: &str, escape_underscore: bool) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for (idx, c) in name.char_indices() {
+        let escape_char = c.is_control()
+            || ['/', '\\', '%', ':', '*', '?', '"', '<', '>', '|'].contains(&c)
+            || (c == '.' && idx == 0)
+            || (c == '_' && escape_underscore);
+        if escape_char {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// Escape a channel or nick name to be used in a file name. See `unescape_name` for the reverse.
+pub fn escape_name(name: &str) -> String {
+    escape(name, false)
+}
+
+/// Reverse of `escape_name`. Returns `None` if the string is not a valid escaped name.
+pub fn unescape_name(name: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut iter = name.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn escape_serv(serv: &str) -> String {
+    escape(&casemap(serv), true)
+}
+
+fn escape_target(target: &str) -> String {
+    escape(&casemap(target), false)
+}
+
+/// Returns path of a tab's log file (`Rotation::None`) or log directory (other rotations).
+/// `target` is the channel or nick, `None` for server tabs.
+pub(crate) fn log_path(
+    log_dir: &Path,
+    rotation: Rotation,
+    ext: &str,
+    serv: &str,
+    target: Option<&str>,
+) -> PathBuf {
+    let mut path = log_dir.to_path_buf();
+    let serv = escape_serv(serv);
+    match rotation {
+        Rotation::None => match target {
+            None => path.push(format!("{}.{}", serv, ext)),
+            Some(target) => path.push(format!("{}_{}.{}", serv, escape_target(target), ext)),
+        },
+        Rotation::Daily | Rotation::Monthly => {
+            path.push(serv);
+            if let Some(target) = target {
+                path.push(escape_target(target));
+            }
+        }
+    }
+    path
+}
+
+/// Returns path of the log file to use at the given time. `path` is the return value of
+/// `log_path`.
+pub(crate) fn log_file_path(path: &Path, rotation: Rotation, ext: &str, now: &Tm) -> PathBuf {
+    let date = match rotation {
+        Rotation::None => return path.to_path_buf(),
+        Rotation::Daily => time::strftime("%Y-%m-%d", now).unwrap(),
+        Rotation::Monthly => time::strftime("%Y-%m", now).unwrap(),
+    };
+    path.join(format!("{}.{}", date, ext))
+}
+
+/// What a log file is for. See `parse_log_path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileInfo {
+    /// Server name, casemapped
+    pub serv: String,
+
+    /// Channel or nick, casemapped. `None` for server logs.
+    pub target: Option<String>,
+
+    /// Date of the log file (`2019-10-17` or `2019-10`) when logs are rotated.
+    pub date: Option<String>,
+}
+
+/// Map a log file in `log_dir` back to its server and channel or nick. Returns `None` if the path
+/// is not a log file path generated by the logger. Names are casemapped after unescaping, as logs
+/// written by older versions of the logger have names in their original case.
+pub fn parse_log_path(log_dir: &Path, path: &Path) -> Option<LogFileInfo> {
+    let rel_path = path.strip_prefix(log_dir).ok()?;
+    let components = rel_path
+        .iter()
+        .map(|c| c.to_str())
+        .collect::<Option<Vec<&str>>>()?;
+
+    match components.as_slice() {
+        [file] => {
+            let (name, _ext) = split_ext(file)?;
+            let (serv, target) = match name.find('_') {
+                None => (parse_name(name)?, None),
+                Some(sep) => (
+                    parse_name(&name[..sep])?,
+                    Some(parse_name(&name[sep + 1..])?),
+                ),
+            };
+            Some(LogFileInfo {
+                serv,
+                target,
+                date: None,
+            })
+        }
+        [serv, file] => Some(LogFileInfo {
+            serv: parse_name(serv)?,
+            target: None,
+            date: Some(split_ext(file)?.0.to_owned()),
+        }),
+        [serv, target, file] => Some(LogFileInfo {
+            serv: parse_name(serv)?,
+            target: Some(parse_name(target)?),
+            date: Some(split_ext(file)?.0.to_owned()),
+        }),
+        _ => None,
+    }
+}
+
+/// Unescape and casemap a server, channel or nick name in a log file path.
+fn parse_name(name: &str) -> Option<String> {
+    unescape_name(name).map(|name| casemap(&name))
+}
+
+/// Split file name to name and extension.
+fn split_ext(file: &str) -> Option<(&str, &str)> {
+    let dot = file.rfind('.')?;
+    Some((&file[..dot], &file[dot + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        let names = [
+            "#tiny",
+            "../../etc/passwd",
+            "#a/b\\c",
+            "..",
+            ".hidden",
+            "#a\x01b\nc",
+            "100%",
+            "#çay_ocağı",
+            "#rust_gamedev",
+        ];
+        for name in names.iter() {
+            let escaped = escape_name(name);
+            assert!(!escaped.contains('/'));
+            assert!(!escaped.starts_with('.'));
+            assert!(!escaped.chars().any(char::is_control));
+            assert_eq!(unescape_name(&escaped).as_deref(), Some(*name));
+        }
+        assert_eq!(escape_name(".."), "%2E.");
+        assert_eq!(escape_name("#rust_gamedev"), "#rust_gamedev");
+        assert_eq!(escape_serv("irc_server.net"), "irc%5Fserver.net");
+        assert_eq!(unescape_name("%2"), None);
+        assert_eq!(unescape_name("%zz"), None);
+    }
+
+    #[test]
+    fn test_casemap() {
+        assert_eq!(casemap("#Tiny[m]"), "#tiny{m}");
+        assert_eq!(casemap("Nick\\~"), "nick|^");
+    }
+
+    #[test]
+    fn test_log_paths() {
+        let log_dir = Path::new("/logs");
+        let tm = time::strptime("2019-10-17 18:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let path = log_path(
+            log_dir,
+            Rotation::None,
+            "txt",
+            "irc.freenode.net",
+            Some("#Tiny"),
+        );
+        assert_eq!(path, Path::new("/logs/irc.freenode.net_#tiny.txt"));
+        assert_eq!(log_file_path(&path, Rotation::None, "txt", &tm), path);
+        assert_eq!(
+            parse_log_path(log_dir, &path),
+            Some(LogFileInfo {
+                serv: "irc.freenode.net".to_owned(),
+                target: Some("#tiny".to_owned()),
+                date: None,
+            })
+        );
+
+        let path = log_path(log_dir, Rotation::Daily, "log", "irc_net", Some("#a/.."));
+        let path = log_file_path(&path, Rotation::Daily, "log", &tm);
+        assert_eq!(path, Path::new("/logs/irc%5Fnet/#a%2F../2019-10-17.log"));
+        assert_eq!(
+            parse_log_path(log_dir, &path),
+            Some(LogFileInfo {
+                serv: "irc_net".to_owned(),
+                target: Some("#a/..".to_owned()),
+                date: Some("2019-10-17".to_owned()),
+            })
+        );
+
+        let path = log_path(
+            log_dir,
+            Rotation::Monthly,
+            "jsonl",
+            "irc.freenode.net",
+            None,
+        );
+        let path = log_file_path(&path, Rotation::Monthly, "jsonl", &tm);
+        assert_eq!(path, Path::new("/logs/irc.freenode.net/2019-10.jsonl"));
+        assert_eq!(
+            parse_log_path(log_dir, &path),
+            Some(LogFileInfo {
+                serv: "irc.freenode.net".to_owned(),
+                target: None,
+                date: Some("2019-10".to_owned()),
+            })
+        );
+
+        // Logs written before names were casemapped
+        assert_eq!(
+            parse_log_path(log_dir, Path::new("/logs/Irc.Mozilla.org_#Rust[m].txt")),
+            Some(LogFileInfo {
+                serv: "irc.mozilla.org".to_owned(),
+                target: Some("#rust{m}".to_owned()),
+                date: None,
+            })
+        );
+        assert_eq!(
+            parse_log_path(log_dir, Path::new("/logs/Freenode/NickServ/2019-10-17.log")),
+            Some(LogFileInfo {
+                serv: "freenode".to_owned(),
+                target: Some("nickserv".to_owned()),
+                date: Some("2019-10-17".to_owned()),
+            })
+        );
+
+        assert_eq!(parse_log_path(log_dir, Path::new("/tmp/foo.txt")), None);
+    }
+}
@@ -0,0 +1,842 @@
+//! Reading log files back, e.g. to restore scrollback of tabs or to search logs.
+//!
+//! Both text and JSON Lines logs are supported. Text logs with `%H:%M:%S` timestamps get the
+//! dates of lines from the "Logging started" and "Date changed" lines, or from the file name when
+//! logs are rotated daily.
+//!
+//! `read_log` reads files backwards from the end, so that restoring scrollback doesn't need to
+//! read all of a large log file. Reading can end at a `LogEnd`, so that lines logged after a tab
+//! is created are not restored as old lines while the log is being read.
+//!
+//! Encrypted log files are decrypted with the given `LogDecrypter`. Reading an encrypted file
+//! without a decrypter fails, with an error that says that the file is encrypted.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use time::{Duration, Tm};
+
+use crate::config::{Config, Rotation};
+use crate::encryption::{is_encrypted_line, LogDecrypter};
+use crate::format::{event_text, EventType};
+use crate::paths::log_path;
+
+/// How much of a log to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadLimit {
+    /// Last N lines
+    Lines(usize),
+    /// Lines from the last N hours
+    Hours(u32),
+}
+
+/// An event read from a log file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub ts: Tm,
+    pub ty: EventType,
+    pub sender: Option<String>,
+    pub text: String,
+    pub is_action: bool,
+    pub highlight: bool,
+    pub msgid: Option<String>,
+}
+
+impl fmt::Display for LogLine {
+    /// Shows the line as in text logs, without the timestamp.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sender = self.sender.as_deref().unwrap_or("");
+        f.write_str(&event_text(self.ty, sender, &self.text, self.is_action))
+    }
+}
+
+/// End of the log of a tab at some point, see `log_end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogEnd {
+    /// Newest log file of the tab and its length. `None` when the tab doesn't have logs.
+    last_file: Option<(PathBuf, u64)>,
+}
+
+/// Current end of the log of a tab. `target` is the channel or nick, `None` for server tabs. Only
+/// reads file metadata, so this is cheap enough to call when a tab is created.
+pub fn log_end(
+    log_dir: &Path,
+    config: &Config,
+    serv: &str,
+    target: Option<&str>,
+) -> io::Result<LogEnd> {
+    for file in log_files(log_dir, config, serv, target)? {
+        match fs::metadata(&file) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+            Ok(metadata) => {
+                return Ok(LogEnd {
+                    last_file: Some((file, metadata.len())),
+                })
+            }
+        }
+    }
+    Ok(LogEnd { last_file: None })
+}
+
+/// Log files of a tab, newest first. Files may not exist.
+fn log_files(
+    log_dir: &Path,
+    config: &Config,
+    serv: &str,
+    target: Option<&str>,
+) -> io::Result<Vec<PathBuf>> {
+    let ext = config.formatter().file_extension(config.rotation);
+    let path = log_path(log_dir, config.rotation, ext, serv, target);
+    match config.rotation {
+        Rotation::None => Ok(vec![path]),
+        Rotation::Daily | Rotation::Monthly => {
+            let mut files = match fs::read_dir(&path) {
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => vec![],
+                Err(err) => return Err(err),
+                Ok(entries) => entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| {
+                        path.is_file() && path.extension().and_then(|ext| ext.to_str()) == Some(ext)
+                    })
+                    .collect(),
+            };
+            // File names are dates so this sorts by date
+            files.sort_unstable_by(|f1, f2| f2.cmp(f1));
+            Ok(files)
+        }
+    }
+}
+
+/// Read the log of a tab. `target` is the channel or nick, `None` for server tabs. Lines are
+/// returned in the order they were logged. When `end` is given only lines logged before it are
+/// read.
+pub fn read_log(
+    log_dir: &Path,
+    config: &Config,
+    serv: &str,
+    target: Option<&str>,
+    limit: ReadLimit,
+    end: Option<&LogEnd>,
+    mut decrypter: Option<&mut LogDecrypter>,
+) -> io::Result<Vec<LogLine>> {
+    let mut files = log_files(log_dir, config, serv, target)?;
+    if let Some(end) = end {
+        match end.last_file {
+            None => files.clear(),
+            Some((ref last_file, _)) => files.retain(|file| file <= last_file),
+        }
+    }
+
+    let cutoff = match limit {
+        ReadLimit::Lines(_) => None,
+        ReadLimit::Hours(hours) => {
+            Some((time::now() - Duration::hours(i64::from(hours))).to_timespec())
+        }
+    };
+
+    let mut lines: Vec<LogLine> = vec![];
+    for file in files {
+        let n_lines = lines.len();
+        let file_done = |n_file_lines: usize, oldest: &LogLine| match limit {
+            ReadLimit::Lines(n) => n_lines + n_file_lines >= n,
+            ReadLimit::Hours(_) => oldest.ts.to_timespec() < cutoff.unwrap(),
+        };
+        let file_len = end
+            .and_then(|end| end.last_file.as_ref())
+            .filter(|(last_file, _)| *last_file == file)
+            .map(|(_, len)| *len);
+        let file_lines = read_log_file_tail(&file, file_len, decrypter.as_deref_mut(), file_done);
+        let mut file_lines = match file_lines {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+            Ok(file_lines) => file_lines,
+        };
+        file_lines.append(&mut lines);
+        lines = file_lines;
+
+        let done = match limit {
+            ReadLimit::Lines(n) => lines.len() >= n,
+            ReadLimit::Hours(_) => match lines.first() {
+                None => false,
+                Some(line) => line.ts.to_timespec() < cutoff.unwrap(),
+            },
+        };
+        if done {
+            break;
+        }
+    }
+
+    match limit {
+        ReadLimit::Lines(n) => {
+            let skip = lines.len().saturating_sub(n);
+            lines.drain(..skip);
+        }
+        ReadLimit::Hours(_) => {
+            let cutoff = cutoff.unwrap();
+            lines.retain(|line| line.ts.to_timespec() >= cutoff);
+        }
+    }
+
+    Ok(lines)
+}
+
+fn is_json_log(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("jsonl")
+}
+
+/// Date in the file name, when logs are rotated daily.
+fn file_name_date(path: &Path) -> Option<String> {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .filter(|stem| time::strptime(stem, "%Y-%m-%d").is_ok())
+        .map(str::to_owned)
+}
+
+/// Read all lines of a log file. `.jsonl` files are read as JSON Lines logs, others as text logs.
+/// Lines that can't be parsed are skipped.
+pub fn read_log_file(
+    path: &Path,
+    decrypter: Option<&mut LogDecrypter>,
+) -> io::Result<Vec<LogLine>> {
+    read_log_file_prefix(path, u64::MAX, decrypter)
+}
+
+/// Read lines in the first `len` bytes of a log file. See `read_log_file`.
+fn read_log_file_prefix(
+    path: &Path,
+    len: u64,
+    decrypter: Option<&mut LogDecrypter>,
+) -> io::Result<Vec<LogLine>> {
+    let mut log = String::new();
+    fs::File::open(path)?.take(len).read_to_string(&mut log)?;
+    if log.lines().any(is_encrypted_line) {
+        log = match decrypter {
+            None => return Err(encrypted_log_err(path)),
+            Some(decrypter) => decrypter.decrypt(&log)?,
+        };
+    }
+    let json = is_json_log(path);
+
+    // Date of the lines in text logs
+    let mut date: Option<String> = file_name_date(path);
+
+    let mut lines = vec![];
+    for line in log.lines() {
+        let parsed = if json {
+            parse_json_line(line)
+        } else {
+            parse_text_line(line, &mut date)
+        };
+        if let Some(parsed) = parsed {
+            lines.push(parsed);
+        }
+    }
+    Ok(lines)
+}
+
+fn encrypted_log_err(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "{} is encrypted and the passphrase is not set, it can be read with tiny-logcat",
+            path.display()
+        ),
+    )
+}
+
+/// Read lines of a log file backwards, until `done` returns `true` for the number of lines read
+/// so far and the oldest line, or the start of the file is reached. Lines are returned in the
+/// order they were logged. When `len` is given only the first `len` bytes of the file are read.
+///
+/// Dates of lines in text logs are not known until the "Logging started" or "Date changed" line
+/// before them is read, so lines are parsed in batches, up to those lines.
+///
+/// Encrypted lines can't be decrypted before the key line before them is read, so encrypted files
+/// are read from the start with `read_log_file`.
+fn read_log_file_tail<F>(
+    path: &Path,
+    len: Option<u64>,
+    decrypter: Option<&mut LogDecrypter>,
+    done: F,
+) -> io::Result<Vec<LogLine>>
+where
+    F: Fn(usize, &LogLine) -> bool,
+{
+    let json = is_json_log(path);
+    let file = fs::File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let len = len.map_or(file_len, |len| std::cmp::min(len, file_len));
+
+    // Lines read so far, newest first
+    let mut lines: Vec<LogLine> = vec![];
+    // Text lines after the last date line read, newest first
+    let mut undated: Vec<String> = vec![];
+
+    for line in RevLines::new(file, len, READ_CHUNK_SIZE) {
+        let line = line?;
+        if is_encrypted_line(&line) {
+            return read_log_file_prefix(path, len, decrypter);
+        }
+        if json {
+            if let Some(line) = parse_json_line(&line) {
+                lines.push(line);
+            }
+        } else if line.starts_with("*** Logging started at ")
+            || line.starts_with("*** Date changed to ")
+        {
+            let mut date = None;
+            parse_text_line(&line, &mut date);
+            lines.extend(
+                undated
+                    .drain(..)
+                    .filter_map(|line| parse_text_line(&line, &mut date.clone())),
+            );
+        } else {
+            undated.push(line);
+            continue;
+        }
+
+        if let Some(oldest) = lines.last() {
+            if done(lines.len(), oldest) {
+                lines.reverse();
+                return Ok(lines);
+            }
+        }
+    }
+
+    // Lines before the first date line
+    let date = file_name_date(path);
+    lines.extend(
+        undated
+            .drain(..)
+            .filter_map(|line| parse_text_line(&line, &mut date.clone())),
+    );
+    lines.reverse();
+    Ok(lines)
+}
+
+/// Size of the chunks read by `RevLines`.
+const READ_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Iterates lines of a file from the end. Lines are returned without the newline, invalid UTF-8
+/// is replaced with U+FFFD.
+struct RevLines<R> {
+    reader: R,
+
+    /// Position of `buf` in the file
+    pos: u64,
+
+    /// Read but not yet returned part of the file, at `pos`. The first line may be partial.
+    buf: Vec<u8>,
+
+    /// Complete lines in `buf`, in file order
+    lines: Vec<String>,
+
+    chunk_size: u64,
+}
+
+impl<R: Read + Seek> RevLines<R> {
+    /// `len` is the length of the file.
+    fn new(reader: R, len: u64, chunk_size: u64) -> RevLines<R> {
+        RevLines {
+            reader,
+            pos: len,
+            buf: vec![],
+            lines: vec![],
+            chunk_size,
+        }
+    }
+
+    fn read_chunk(&mut self) -> io::Result<()> {
+        let chunk_size = std::cmp::min(self.chunk_size, self.pos);
+        self.pos -= chunk_size;
+        let mut chunk = vec![0; chunk_size as usize];
+        self.reader.seek(SeekFrom::Start(self.pos))?;
+        self.reader.read_exact(&mut chunk)?;
+        chunk.append(&mut self.buf);
+        self.buf = chunk;
+
+        let mut pieces: Vec<&[u8]> = self.buf.split(|byte| *byte == b'\n').collect();
+        // Newline at the end of a line, or the file
+        if pieces.last() == Some(&&b""[..]) {
+            pieces.pop();
+        }
+        // First line is partial when we're not at the start of the file
+        let first = if self.pos == 0 || pieces.is_empty() {
+            None
+        } else {
+            Some(pieces.remove(0))
+        };
+        let lines = pieces
+            .iter()
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect();
+        let buf = match first {
+            None => vec![],
+            Some(first) => {
+                let mut buf = first.to_vec();
+                buf.push(b'\n');
+                buf
+            }
+        };
+        self.lines = lines;
+        self.buf = buf;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Iterator for RevLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<io::Result<String>> {
+        loop {
+            if let Some(line) = self.lines.pop() {
+                return Some(Ok(line));
+            }
+            if self.pos == 0 {
+                return None;
+            }
+            if let Err(err) = self.read_chunk() {
+                // Don't retry
+                self.pos = 0;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonLine {
+    #[serde(rename = "type")]
+    ty: EventType,
+    sender: Option<String>,
+    text: String,
+    timestamp: String,
+    is_action: bool,
+    highlight: bool,
+    msgid: Option<String>,
+}
+
+fn parse_json_line(line: &str) -> Option<LogLine> {
+    let line: JsonLine = serde_json::from_str(line).ok()?;
+    Some(LogLine {
+        ts: parse_iso_timestamp(&line.timestamp)?,
+        ty: line.ty,
+        sender: line.sender,
+        text: line.text,
+        is_action: line.is_action,
+        highlight: line.highlight,
+        msgid: line.msgid,
+    })
+}
+
+/// Parse a line in a text log. `date` is the date of the lines, updated when the line is a
+/// "Logging started" or "Date changed" line.
+fn parse_text_line(line: &str, date: &mut Option<String>) -> Option<LogLine> {
+    if let Some(started) = line.strip_prefix("*** Logging started at ") {
+        *date = started.get(..10).map(str::to_owned);
+        return None;
+    }
+    if let Some(changed) = line.strip_prefix("*** Date changed to ") {
+        *date = Some(changed.to_owned());
+        return None;
+    }
+
+    let line = line.strip_prefix('[')?;
+    let ts_end = line.find("] ")?;
+    let ts = &line[..ts_end];
+    let rest = &line[ts_end + 2..];
+
+    let ts = if ts.len() == 8 {
+        // %H:%M:%S
+        let date = match date {
+            Some(date) => date.clone(),
+            None => time::strftime("%Y-%m-%d", &time::now()).unwrap(),
+        };
+        parse_local_timestamp(&format!("{} {}", date, ts))?
+    } else {
+        parse_iso_timestamp(ts)?
+    };
+
+    let (ty, sender, text) = parse_text_event(rest);
+    Some(LogLine {
+        ts,
+        ty,
+        sender: sender.map(str::to_owned),
+        text: text.to_owned(),
+        is_action: false,
+        highlight: false,
+        msgid: None,
+    })
+}
+
+/// Parse an event in text logs (see `event_text`). Actions (`osa1 waves`) look the same as server
+/// messages, and are returned as `EventType::Msg`.
+fn parse_text_event(text: &str) -> (EventType, Option<&str>, &str) {
+    let is_nick = |s: &str| !s.is_empty() && !s.contains([' ', ':']);
+
+    if let Some(msg) = text.strip_prefix("[client] ") {
+        return (EventType::Client, None, msg);
+    }
+    if let Some(msg) = text.strip_prefix("[error] ") {
+        return (EventType::Error, None, msg);
+    }
+    if let Some(msg) = text.strip_prefix("[client error] ") {
+        return (EventType::ClientError, None, msg);
+    }
+    if let Some(notice) = text.strip_prefix('-') {
+        if let Some(end) = notice.find("- ") {
+            if is_nick(&notice[..end]) {
+                return (EventType::Notice, Some(&notice[..end]), &notice[end + 2..]);
+            }
+        }
+    }
+    if let Some(setter) = text.strip_prefix("Topic set by ") {
+        let mut split = setter.splitn(2, " on ");
+        if let Some(setter) = split.next().filter(|setter| is_nick(setter)) {
+            return (
+                EventType::TopicSetBy,
+                Some(setter),
+                split.next().unwrap_or(""),
+            );
+        }
+    }
+    if let Some(topic) = text
+        .strip_prefix("Channel topic: ")
+        .and_then(|topic| topic.strip_suffix('.'))
+    {
+        return (EventType::Topic, None, topic);
+    }
+    if let Some(nick) = text.strip_suffix(" joined the channel.") {
+        if is_nick(nick) {
+            return (EventType::Join, Some(nick), "");
+        }
+    }
+    if let Some(nick) = text.strip_suffix(" left.") {
+        if is_nick(nick) {
+            return (EventType::Part, Some(nick), "");
+        }
+    }
+    if let Some(nicks) = text.strip_suffix('.') {
+        let mut split = nicks.splitn(2, " is now known as ");
+        if let (Some(old), Some(new)) = (split.next(), split.next()) {
+            if is_nick(old) && is_nick(new) {
+                return (EventType::Nick, Some(old), new);
+            }
+        }
+    }
+    for (ty, sep) in [
+        (EventType::Reaction, " reacted with "),
+        (EventType::Kick, " kicked "),
+        (EventType::Mode, " sets mode "),
+    ]
+    .iter()
+    {
+        let mut split = text.splitn(2, sep);
+        if let (Some(nick), Some(rest)) = (split.next(), split.next()) {
+            if is_nick(nick) {
+                return (*ty, Some(nick), rest);
+            }
+        }
+    }
+    if let Some(colon) = text.find(": ") {
+        let nick = &text[..colon];
+        if is_nick(nick) {
+            return (EventType::Privmsg, Some(nick), &text[colon + 2..]);
+        }
+    }
+    (EventType::Msg, None, text)
+}
+
+/// Parse a timestamp in `%Y-%m-%d %H:%M:%S` format, in local time. The UTC offset is the one in
+/// effect at that time, which may be different than the current offset because of daylight
+/// saving time.
+fn parse_local_timestamp(ts: &str) -> Option<Tm> {
+    let mut tm = time::strptime(ts, "%Y-%m-%d %H:%M:%S").ok()?;
+    tm.tm_utcoff = 0;
+    // The time as if it was UTC
+    let utc = tm.to_timespec();
+    // The offset depends on the time we're looking for, start with the offset a few hours off
+    // and correct it if the time is on the other side of a DST change.
+    let offset = time::at(utc).tm_utcoff;
+    let local = utc - Duration::seconds(i64::from(offset));
+    let local_offset = time::at(local).tm_utcoff;
+    if local_offset == offset {
+        Some(time::at(local))
+    } else {
+        Some(time::at(utc - Duration::seconds(i64::from(local_offset))))
+    }
+}
+
+/// Parse an ISO 8601 timestamp as generated by `Tm::rfc3339`: `2019-10-17T18:30:00+03:00` or
+/// `2019-10-17T18:30:00Z`. Returns the time in local time.
+fn parse_iso_timestamp(ts: &str) -> Option<Tm> {
+    let mut tm = time::strptime(ts.get(..19)?, "%Y-%m-%dT%H:%M:%S").ok()?;
+    tm.tm_utcoff = 0;
+    let offset = match ts.get(19..)? {
+        "Z" => 0,
+        offset => {
+            let sign = match offset.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let hours: i64 = offset.get(1..3)?.parse().ok()?;
+            let mins: i64 = offset.get(4..6)?.parse().ok()?;
+            sign * (hours * 60 + mins) * 60
+        }
+    };
+    Some(time::at(tm.to_timespec() - Duration::seconds(offset)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_text_line() {
+        let mut date = None;
+        assert_eq!(
+            parse_text_line("*** Logging started at 2019-10-17 18:00:00", &mut date),
+            None
+        );
+        assert_eq!(date.as_deref(), Some("2019-10-17"));
+
+        let line = parse_text_line("[18:30:00] osa1: hi: there", &mut date).unwrap();
+        assert_eq!(
+            time::strftime("%F %T", &line.ts).unwrap(),
+            "2019-10-17 18:30:00"
+        );
+        assert_eq!(line.ty, EventType::Privmsg);
+        assert_eq!(line.sender.as_deref(), Some("osa1"));
+        assert_eq!(line.text, "hi: there");
+        assert_eq!(line.to_string(), "osa1: hi: there");
+
+        assert_eq!(
+            parse_text_line("*** Date changed to 2019-10-18", &mut date),
+            None
+        );
+        let line = parse_text_line("[00:01:00] osa1 is now known as osa1_.", &mut date).unwrap();
+        assert_eq!(
+            time::strftime("%F %T", &line.ts).unwrap(),
+            "2019-10-18 00:01:00"
+        );
+        assert_eq!(line.ty, EventType::Nick);
+        assert_eq!(line.text, "osa1_");
+
+        let line = parse_text_line("[2019-10-17T18:30:00Z] [client] hello", &mut date).unwrap();
+        assert_eq!(line.ts.to_timespec(), time::Timespec::new(1571337000, 0));
+        assert_eq!(line.ty, EventType::Client);
+        assert_eq!(line.to_string(), "[client] hello");
+
+        assert_eq!(
+            parse_text_event("Channel topic: tiny IRC client."),
+            (EventType::Topic, None, "tiny IRC client")
+        );
+        assert_eq!(
+            parse_text_event("osa1 joined the channel."),
+            (EventType::Join, Some("osa1"), "")
+        );
+        assert_eq!(
+            parse_text_event("-NickServ- This nickname is registered."),
+            (
+                EventType::Notice,
+                Some("NickServ"),
+                "This nickname is registered."
+            )
+        );
+        assert_eq!(
+            parse_text_event("ChanServ kicked bob (spam)"),
+            (EventType::Kick, Some("ChanServ"), "bob (spam)")
+        );
+        assert_eq!(
+            parse_text_event("osa1 sets mode +o bob"),
+            (EventType::Mode, Some("osa1"), "+o bob")
+        );
+        assert_eq!(
+            parse_text_event("Topic set by osa1 on 2019-10-17 18:30:00"),
+            (EventType::TopicSetBy, Some("osa1"), "2019-10-17 18:30:00")
+        );
+        assert_eq!(
+            parse_text_event("[error] Nickname is already in use"),
+            (EventType::Error, None, "Nickname is already in use")
+        );
+        assert_eq!(
+            parse_text_event("bob: joined the channel."),
+            (EventType::Privmsg, Some("bob"), "joined the channel.")
+        );
+        assert_eq!(
+            parse_text_event("End of /NAMES list."),
+            (EventType::Msg, None, "End of /NAMES list.")
+        );
+    }
+
+    #[test]
+    fn test_parse_iso_timestamp() {
+        assert_eq!(
+            parse_iso_timestamp("2019-10-17T21:30:00+03:00").map(|tm| tm.to_timespec()),
+            Some(time::Timespec::new(1571337000, 0))
+        );
+        assert_eq!(parse_iso_timestamp("2019-10-17"), None);
+    }
+
+    #[test]
+    fn test_parse_local_timestamp() {
+        // Dates in and out of DST
+        for ts in &[
+            "2019-01-15 12:00:00",
+            "2019-07-15 12:00:00",
+            "2019-10-27 00:30:00",
+            "2019-03-31 23:30:00",
+        ] {
+            let tm = parse_local_timestamp(ts).unwrap();
+            assert_eq!(&time::strftime("%F %T", &tm).unwrap(), ts);
+        }
+    }
+
+    #[test]
+    fn test_rev_lines() {
+        let rev_lines = |s: &str, chunk_size| -> Vec<String> {
+            RevLines::new(io::Cursor::new(s), s.len() as u64, chunk_size)
+                .map(Result::unwrap)
+                .collect()
+        };
+        for chunk_size in 1..8 {
+            assert_eq!(
+                rev_lines("a\n\nbc\ndef\n", chunk_size),
+                vec!["def", "bc", "", "a"]
+            );
+            assert_eq!(rev_lines("abc\nd", chunk_size), vec!["d", "abc"]);
+            assert_eq!(rev_lines("\n", chunk_size), vec![""]);
+            assert!(rev_lines("", chunk_size).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_log() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        let config = Config {
+            rotation: Rotation::Daily,
+            ..Config::default()
+        };
+        let dir = log_path(log_dir, Rotation::Daily, "log", "serv", Some("#chan"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("2019-10-16.log"),
+            "\n*** Logging started at 2019-10-16 10:00:00\n\n[10:00:00] a: 1\n[11:00:00] b: 2\n",
+        )
+        .unwrap();
+        fs::write(dir.join("2019-10-17.log"), "[09:00:00] c: 3\n").unwrap();
+        // Reading stops at the date line before the first line
+        let path = log_dir.join("serv.txt");
+        fs::write(
+            &path,
+            "\n*** Logging started at 2019-10-16 10:00:00\n\n[10:00:00] a: 1\n\
+             \n*** Date changed to 2019-10-17\n\n[11:00:00] b: 2\n",
+        )
+        .unwrap();
+        let lines = read_log_file_tail(&path, None, None, |n, _| n >= 1).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].to_string(), "b: 2");
+        assert_eq!(
+            time::strftime("%F %T", &lines[0].ts).unwrap(),
+            "2019-10-17 11:00:00"
+        );
+        assert_eq!(
+            read_log_file_tail(&path, None, None, |_, _| false)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let lines = read_log(
+            log_dir,
+            &config,
+            "serv",
+            Some("#chan"),
+            ReadLimit::Lines(2),
+            None,
+            None,
+        )
+        .unwrap();
+        let texts: Vec<String> = lines.iter().map(LogLine::to_string).collect();
+        assert_eq!(texts, vec!["b: 2", "c: 3"]);
+        assert_eq!(
+            time::strftime("%F %T", &lines[1].ts).unwrap(),
+            "2019-10-17 09:00:00"
+        );
+
+        let lines = read_log(
+            log_dir,
+            &config,
+            "serv",
+            Some("#nope"),
+            ReadLimit::Hours(1),
+            None,
+            None,
+        );
+        assert_eq!(lines.unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_read_log_end() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        let read = |config: &Config, target, end: &LogEnd| -> Vec<String> {
+            read_log(
+                log_dir,
+                config,
+                "serv",
+                Some(target),
+                ReadLimit::Lines(10),
+                Some(end),
+                None,
+            )
+            .unwrap()
+            .iter()
+            .map(LogLine::to_string)
+            .collect()
+        };
+
+        // Lines logged after the end are not read
+        let config = Config::default();
+        let path = log_path(log_dir, Rotation::None, "txt", "serv", Some("#chan"));
+        fs::write(
+            &path,
+            "\n*** Logging started at 2019-10-16 10:00:00\n\n[10:00:00] a: 1\n",
+        )
+        .unwrap();
+        let end = log_end(log_dir, &config, "serv", Some("#chan")).unwrap();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        write!(
+            file,
+            "\n*** Logging started at 2019-10-17 10:00:00\n\n[10:00:00] b: 2\n"
+        )
+        .unwrap();
+        assert_eq!(read(&config, "#chan", &end), vec!["a: 1"]);
+
+        // Tab without logs
+        let end = log_end(log_dir, &config, "serv", Some("#new")).unwrap();
+        let path = log_path(log_dir, Rotation::None, "txt", "serv", Some("#new"));
+        fs::write(&path, "[10:00:00] a: 1\n").unwrap();
+        assert!(read(&config, "#new", &end).is_empty());
+
+        // Files created after the end are not read
+        let config = Config {
+            rotation: Rotation::Daily,
+            ..Config::default()
+        };
+        let dir = log_path(log_dir, Rotation::Daily, "log", "serv", Some("#chan"));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("2019-10-16.log"), "[10:00:00] a: 1\n").unwrap();
+        let end = log_end(log_dir, &config, "serv", Some("#chan")).unwrap();
+        fs::write(dir.join("2019-10-17.log"), "[10:00:00] b: 2\n").unwrap();
+        assert_eq!(read(&config, "#chan", &end), vec!["a: 1"]);
+    }
+}
@@ -0,0 +1,89 @@
+//! Rules for what to log in a server. See `Logger::set_rules`.
+
+use crate::paths::casemap;
+use libtiny_wire::wildcard_match;
+
+/// What to log in a server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRules {
+    /// Log the server. When `false` nothing in the server is logged.
+    pub log: bool,
+
+    /// Glob patterns (`*` and `?` wildcards) of channels and nicks to log. Everything is logged
+    /// when empty.
+    pub allow: Vec<String>,
+
+    /// Glob patterns of channels and nicks to never log. Takes precedence over `allow`.
+    pub deny: Vec<String>,
+}
+
+impl Default for LogRules {
+    fn default() -> LogRules {
+        LogRules {
+            log: true,
+            allow: vec![],
+            deny: vec![],
+        }
+    }
+}
+
+impl LogRules {
+    /// Should the channel or user tab be logged? `target` is the channel or nick, `None` for the
+    /// server tab.
+    pub fn logs(&self, target: Option<&str>) -> bool {
+        if !self.log {
+            return false;
+        }
+        match target {
+            None => true,
+            Some(target) => {
+                // Patterns and targets are matched case-insensitively
+                let target = casemap(target);
+                let matches = |pattern: &String| wildcard_match(&casemap(pattern), &target);
+                (self.allow.is_empty() || self.allow.iter().any(matches))
+                    && !self.deny.iter().any(matches)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_rules() {
+        let strs = |strs: &[&str]| strs.iter().map(|s| (*s).to_owned()).collect();
+
+        let rules = LogRules::default();
+        assert!(rules.logs(None));
+        assert!(rules.logs(Some("#tiny")));
+
+        let rules = LogRules {
+            deny: strs(&["#secret*", "nickserv"]),
+            ..LogRules::default()
+        };
+        assert!(rules.logs(Some("#tiny")));
+        assert!(!rules.logs(Some("#Secret-ops")));
+        assert!(!rules.logs(Some("NickServ")));
+
+        let rules = LogRules {
+            allow: strs(&["#tiny*", "#rust"]),
+            deny: strs(&["#tiny-private"]),
+            ..LogRules::default()
+        };
+        assert!(rules.logs(None));
+        assert!(rules.logs(Some("#tiny")));
+        assert!(rules.logs(Some("#tiny-dev")));
+        assert!(!rules.logs(Some("#tiny-private")));
+        assert!(!rules.logs(Some("#rust-offtopic")));
+        assert!(!rules.logs(Some("osa1")));
+
+        let rules = LogRules {
+            log: false,
+            ..LogRules::default()
+        };
+        assert!(!rules.logs(None));
+        assert!(!rules.logs(Some("#tiny")));
+    }
+}
@@ -0,0 +1,306 @@
+//! Searching log files. See `search_logs`.
+
+use regex::Regex;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::encryption::LogDecrypter;
+use crate::paths::{casemap, parse_log_path};
+use crate::reader::{read_log_file, LogLine};
+
+/// What to search for in `search_logs`. Filters that are `None` match everything.
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    /// Matched against lines as shown in text logs, without timestamps (see `LogLine`'s `Display`)
+    pub pattern: Regex,
+
+    /// Only search logs of this server
+    pub serv: Option<String>,
+
+    /// Only search logs of this channel or user
+    pub target: Option<String>,
+
+    /// Only match lines sent by this nick
+    pub nick: Option<String>,
+
+    /// Only match lines logged on or after this date (`2019-10-17`)
+    pub since: Option<String>,
+
+    /// Only match lines logged on or before this date (`2019-10-17`)
+    pub until: Option<String>,
+}
+
+impl SearchQuery {
+    /// A query without filters.
+    pub fn new(pattern: &str) -> Result<SearchQuery, regex::Error> {
+        Ok(SearchQuery {
+            pattern: Regex::new(pattern)?,
+            serv: None,
+            target: None,
+            nick: None,
+            since: None,
+            until: None,
+        })
+    }
+
+    fn matches_file(&self, serv: &str, target: Option<&str>, date: Option<&str>) -> bool {
+        if let Some(ref query_serv) = self.serv {
+            if casemap(query_serv) != serv {
+                return false;
+            }
+        }
+        if let Some(ref query_target) = self.target {
+            if Some(casemap(query_target).as_str()) != target {
+                return false;
+            }
+        }
+        // `date` is `2019-10-17` or `2019-10`. Compare with the same prefix of the range dates.
+        if let Some(date) = date {
+            let before_since = self
+                .since
+                .as_ref()
+                .and_then(|since| since.get(..date.len()))
+                .is_some_and(|since| date < since);
+            let after_until = self
+                .until
+                .as_ref()
+                .and_then(|until| until.get(..date.len()))
+                .is_some_and(|until| date > until);
+            if before_since || after_until {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn matches_line(&self, line: &LogLine) -> bool {
+        if let Some(ref nick) = self.nick {
+            match line.sender {
+                Some(ref sender) if casemap(sender) == casemap(nick) => {}
+                _ => {
+                    return false;
+                }
+            }
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let date = time::strftime("%Y-%m-%d", &line.ts).unwrap();
+            if self.since.as_ref().is_some_and(|since| &date < since)
+                || self.until.as_ref().is_some_and(|until| &date > until)
+            {
+                return false;
+            }
+        }
+        self.pattern.is_match(&line.to_string())
+    }
+}
+
+/// A line that matches a `SearchQuery`.
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Server of the log file, casemapped
+    pub serv: String,
+
+    /// Channel or nick of the log file, casemapped. `None` for server logs.
+    pub target: Option<String>,
+
+    /// The log file
+    pub path: PathBuf,
+
+    /// Index of the line in `read_log_file(path, ..)`. Used by `read_context`.
+    pub idx: usize,
+
+    pub line: LogLine,
+}
+
+/// Search all logs in `log_dir`. Returns at most `max_results` results, the most recent ones,
+/// sorted by time. Encrypted logs are decrypted with `decrypter`, searching fails when there are
+/// encrypted logs and no decrypter.
+pub fn search_logs(
+    log_dir: &Path,
+    query: &SearchQuery,
+    max_results: usize,
+    mut decrypter: Option<&mut LogDecrypter>,
+) -> io::Result<Vec<SearchResult>> {
+    let mut files = vec![];
+    collect_files(log_dir, 3, &mut files)?;
+
+    // Most recent `max_results` results so far. The oldest result is at the top, and dropped when
+    // a new result is found and the heap is full.
+    let mut results: BinaryHeap<Reverse<HeapEntry>> = BinaryHeap::new();
+    let mut n_results = 0;
+    for path in files {
+        let info = match parse_log_path(log_dir, &path) {
+            None => continue,
+            Some(info) => info,
+        };
+        if !query.matches_file(&info.serv, info.target.as_deref(), info.date.as_deref()) {
+            continue;
+        }
+        let lines = read_log_file(&path, decrypter.as_deref_mut())?;
+        for (idx, line) in lines.into_iter().enumerate() {
+            if query.matches_line(&line) {
+                results.push(Reverse(HeapEntry {
+                    key: (line.ts.to_timespec(), n_results),
+                    result: SearchResult {
+                        serv: info.serv.clone(),
+                        target: info.target.clone(),
+                        path: path.clone(),
+                        idx,
+                        line,
+                    },
+                }));
+                n_results += 1;
+                if results.len() > max_results {
+                    results.pop();
+                }
+            }
+        }
+    }
+
+    let mut results: Vec<HeapEntry> = results.into_iter().map(|Reverse(entry)| entry).collect();
+    results.sort_by_key(|entry| entry.key);
+    Ok(results.into_iter().map(|entry| entry.result).collect())
+}
+
+/// A search result in the heap of `search_logs`, ordered by time of the line, then by the order
+/// the results were found.
+struct HeapEntry {
+    key: (time::Timespec, usize),
+    result: SearchResult,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &HeapEntry) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &HeapEntry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &HeapEntry) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Files in `dir` and its subdirectories, up to `depth` levels deep.
+fn collect_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if depth == 0 {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, depth - 1, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Read `n` lines before and after a search result. Returns the lines and index of the result in
+/// the lines.
+pub fn read_context(
+    result: &SearchResult,
+    n: usize,
+    decrypter: Option<&mut LogDecrypter>,
+) -> io::Result<(Vec<LogLine>, usize)> {
+    let mut lines = read_log_file(&result.path, decrypter)?;
+    let start = result.idx.saturating_sub(n);
+    lines.truncate(result.idx + n + 1);
+    lines.drain(..start);
+    Ok((lines, result.idx - start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_logs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let log_dir = tmp_dir.path();
+        fs::create_dir_all(log_dir.join("irc.freenode.net/#tiny")).unwrap();
+        fs::write(
+            log_dir.join("irc.freenode.net/#tiny/2019-10-17.log"),
+            "[18:30:00] osa1: see https://github.com/osa1/tiny\n\
+             [18:31:00] bob: thanks\n\
+             [18:32:00] bob: https://example.com is better\n",
+        )
+        .unwrap();
+        fs::write(
+            log_dir.join("irc.freenode.net/#tiny/2019-10-18.log"),
+            "[09:00:00] Bob: https://example.org\n",
+        )
+        .unwrap();
+        fs::write(
+            // Written before log file names were casemapped
+            log_dir.join("irc.OFTC.net_#Tiny.txt"),
+            "\n*** Logging started at 2019-10-16 10:00:00\n\n\
+             [10:00:00] bob: https://oftc.net\n",
+        )
+        .unwrap();
+
+        let urls = |query: &SearchQuery| -> Vec<String> {
+            search_logs(log_dir, query, 10, None)
+                .unwrap()
+                .into_iter()
+                .map(|result| result.line.text)
+                .collect()
+        };
+
+        let mut query = SearchQuery::new("https?://").unwrap();
+        query.nick = Some("bob".to_owned());
+        assert_eq!(
+            urls(&query),
+            vec![
+                "https://oftc.net",
+                "https://example.com is better",
+                "https://example.org"
+            ]
+        );
+
+        query.serv = Some("irc.freenode.net".to_owned());
+        query.target = Some("#Tiny".to_owned());
+        query.until = Some("2019-10-17".to_owned());
+        assert_eq!(urls(&query), vec!["https://example.com is better"]);
+
+        query.since = Some("2019-10-18".to_owned());
+        query.until = None;
+        assert_eq!(urls(&query), vec!["https://example.org"]);
+
+        let mut query = SearchQuery::new("https?://").unwrap();
+        query.serv = Some("irc.oftc.net".to_owned());
+        query.target = Some("#tiny".to_owned());
+        assert_eq!(urls(&query), vec!["https://oftc.net"]);
+
+        // Only the most recent results are returned
+        let query = SearchQuery::new("https?://").unwrap();
+        let results = search_logs(log_dir, &query, 2, None).unwrap();
+        let results: Vec<&str> = results.iter().map(|result| &*result.line.text).collect();
+        assert_eq!(
+            results,
+            vec!["https://example.com is better", "https://example.org"]
+        );
+
+        let query = SearchQuery::new("thanks").unwrap();
+        let results = search_logs(log_dir, &query, 10, None).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].target.as_deref(), Some("#tiny"));
+        let (lines, idx) = read_context(&results[0], 1, None).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[idx].text, "thanks");
+        let (lines, idx) = read_context(&results[0], 10, None).unwrap();
+        assert_eq!((lines.len(), idx), (3, 1));
+    }
+}
@@ -0,0 +1,304 @@
+//! A scriptable IRC server, for testing IRC clients. The server listens on a random port on
+//! localhost, and each accepted connection is driven by the test: expect a line from the client,
+//! send a line to the client, and so on. For example:
+//!
+//! ```ignore
+//! let mut server = MockServer::new().await;
+//! // ... connect the client to `server.port()` ...
+//! let mut conn = server.accept().await;
+//! conn.run(&[
+//!     Step::Expect("NICK tiny"),
+//!     Step::Expect("USER tiny 8 * :*"),
+//!     Step::Send(":server 001 tiny :Welcome"),
+//! ])
+//! .await;
+//! ```
+//!
+//! `MockServer::accept_ws` accepts IRC over WebSocket connections instead, with one message in
+//! each text frame. `MockServer::new_unix` listens on a Unix domain socket.
+//!
+//! Failed expectations and timeouts panic, so this is only useful in tests.
+
+use libtiny_wire::wildcard_match;
+use std::path::Path;
+use std::time::Duration;
+use tokio::future::FutureExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, UnixListener};
+
+/// How long to wait for a client to connect, or for a line from a client, before panicking.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A step in a script. See `Conn::run`.
+#[derive(Debug, Clone, Copy)]
+pub enum Step<'a> {
+    /// Expect a line from the client. See `Conn::expect`.
+    Expect(&'a str),
+
+    /// Send a line to the client. See `Conn::send`.
+    Send(&'a str),
+}
+
+/// Connection streams, TCP or Unix domain socket.
+trait Stream: AsyncRead + AsyncWrite + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> Stream for T {}
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+pub struct MockServer {
+    listener: Listener,
+}
+
+impl MockServer {
+    /// Create a server listening on a random port on localhost.
+    pub async fn new() -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        MockServer {
+            listener: Listener::Tcp(listener),
+        }
+    }
+
+    /// Create a server listening on a Unix domain socket at the given path.
+    pub fn new_unix(path: &Path) -> MockServer {
+        let listener = UnixListener::bind(path).unwrap();
+        MockServer {
+            listener: Listener::Unix(listener),
+        }
+    }
+
+    /// Port of the server. Panics when the server is listening on a Unix domain socket.
+    pub fn port(&self) -> u16 {
+        match &self.listener {
+            Listener::Tcp(listener) => listener.local_addr().unwrap().port(),
+            Listener::Unix(_) => panic!("port() called on a Unix domain socket server"),
+        }
+    }
+
+    /// Wait for a client to connect. Panics after `TIMEOUT`.
+    pub async fn accept(&mut self) -> Conn {
+        let stream: std::io::Result<Box<dyn Stream>> = match &mut self.listener {
+            Listener::Tcp(listener) => accept_timeout(listener.accept())
+                .await
+                .map(|(stream, _)| Box::new(stream) as Box<dyn Stream>),
+            Listener::Unix(listener) => accept_timeout(listener.accept())
+                .await
+                .map(|(stream, _)| Box::new(stream) as Box<dyn Stream>),
+        };
+        match stream {
+            Err(err) => panic!("Can't accept connection: {:?}", err),
+            Ok(stream) => Conn {
+                stream: BufReader::new(stream),
+                ws: false,
+            },
+        }
+    }
+
+    /// Wait for a WebSocket client to connect, and do the opening handshake. Panics when the
+    /// request path is not `path`, or the client doesn't ask for the `text.ircv3.net`
+    /// subprotocol.
+    pub async fn accept_ws(&mut self, path: &str) -> Conn {
+        let mut conn = self.accept().await;
+        conn.ws = true;
+
+        let request_line = conn.recv_line().await.expect("Connection closed");
+        assert_eq!(request_line, format!("GET {} HTTP/1.1", path));
+
+        let mut key = None;
+        let mut protocol = None;
+        loop {
+            let line = conn.recv_line().await.expect("Connection closed");
+            if line.is_empty() {
+                break;
+            }
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap().trim().to_lowercase();
+            let value = header.next().unwrap_or("").trim().to_owned();
+            match name.as_str() {
+                "sec-websocket-key" => key = Some(value),
+                "sec-websocket-protocol" => protocol = Some(value),
+                _ => {}
+            }
+        }
+
+        let key = key.expect("Sec-WebSocket-Key missing");
+        assert!(protocol
+            .expect("Sec-WebSocket-Protocol missing")
+            .split(',')
+            .any(|p| p.trim() == "text.ircv3.net"));
+
+        let mut sha1 = sha1::Sha1::new();
+        sha1.update(key.as_bytes());
+        sha1.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\
+             Sec-WebSocket-Protocol: text.ircv3.net\r\n\
+             \r\n",
+            base64::encode(&sha1.digest().bytes())
+        );
+        conn.write(response.as_bytes()).await;
+        conn
+    }
+}
+
+async fn accept_timeout<F: std::future::Future>(accept: F) -> F::Output {
+    match accept.timeout(TIMEOUT).await {
+        Err(_) => panic!("Timed out waiting for a client to connect"),
+        Ok(ret) => ret,
+    }
+}
+
+/// A client connection. Dropping a `Conn` closes the connection.
+pub struct Conn {
+    stream: BufReader<Box<dyn Stream>>,
+
+    /// Is this a WebSocket connection? When it is, lines are sent and received in frames.
+    ws: bool,
+}
+
+impl Conn {
+    /// Receive a line, without the trailing "\r\n". Returns `None` when the client closes the
+    /// connection. Panics after `TIMEOUT`.
+    ///
+    /// With WebSocket connections this receives a text frame, and panics when the frame has more
+    /// than one line in it.
+    pub async fn recv(&mut self) -> Option<String> {
+        let line = if self.ws {
+            self.recv_frame().timeout(TIMEOUT).await
+        } else {
+            self.recv_line().timeout(TIMEOUT).await
+        };
+        match line {
+            Err(_) => panic!("Timed out waiting for a line from the client"),
+            Ok(line) => line,
+        }
+    }
+
+    async fn recv_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.stream.read_line(&mut line).await {
+            Err(err) => panic!("Can't read from the client: {:?}", err),
+            Ok(0) => None,
+            Ok(_) => {
+                let len = line.trim_end_matches(&['\r', '\n'][..]).len();
+                line.truncate(len);
+                Some(line)
+            }
+        }
+    }
+
+    /// Read `n` bytes. Returns `None` when the connection is closed.
+    async fn read_bytes(&mut self, n: usize) -> Option<Vec<u8>> {
+        let mut bytes = vec![0; n];
+        match self.stream.read_exact(&mut bytes).await {
+            Ok(_) => Some(bytes),
+            Err(ref err) if err.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(err) => panic!("Can't read from the client: {:?}", err),
+        }
+    }
+
+    /// Receive a text frame. Control frames other than close are skipped.
+    async fn recv_frame(&mut self) -> Option<String> {
+        loop {
+            let header = self.read_bytes(2).await?;
+            let (fin, opcode) = (header[0] & 0x80 != 0, header[0] & 0x0F);
+            assert!(header[1] & 0x80 != 0, "Client frames must be masked");
+            let len = match header[1] & 0x7F {
+                126 => {
+                    let len = self.read_bytes(2).await?;
+                    usize::from(u16::from_be_bytes([len[0], len[1]]))
+                }
+                127 => panic!("Frame too large"),
+                len => usize::from(len),
+            };
+            let mask = self.read_bytes(4).await?;
+            let mut payload = self.read_bytes(len).await?;
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+
+            match opcode {
+                0x1 => {
+                    assert!(fin, "Messages should not be fragmented");
+                    let msg = String::from_utf8(payload).unwrap();
+                    assert!(
+                        !msg.contains(&['\r', '\n'][..]),
+                        "Frame should have one message without \"\\r\\n\": {:?}",
+                        msg
+                    );
+                    return Some(msg);
+                }
+                0x8 => return None,
+                0x9 | 0xA => {}
+                _ => panic!("Unexpected frame opcode: {}", opcode),
+            }
+        }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) {
+        self.stream.get_mut().write_all(bytes).await.unwrap();
+    }
+
+    /// Receive a line and check that it matches the pattern. `*` in the pattern matches any
+    /// string, `?` matches any character. Panics when the line doesn't match, or the connection
+    /// is closed.
+    pub async fn expect(&mut self, pattern: &str) {
+        match self.recv().await {
+            None => panic!(
+                "Expected {:?}, but the client closed the connection",
+                pattern
+            ),
+            Some(line) => {
+                if !wildcard_match(pattern, &line) {
+                    panic!("Expected {:?}, got {:?}", pattern, line);
+                }
+            }
+        }
+    }
+
+    /// Expect the client to close the connection. Panics when the client sends a line instead.
+    pub async fn expect_closed(&mut self) {
+        if let Some(line) = self.recv().await {
+            panic!("Expected the connection to be closed, got {:?}", line);
+        }
+    }
+
+    /// Send a line. "\r\n" is added by this method. With WebSocket connections the line is sent
+    /// in a text frame, without "\r\n".
+    pub async fn send(&mut self, line: &str) {
+        if self.ws {
+            let mut frame = vec![0x81];
+            if line.len() < 126 {
+                frame.push(line.len() as u8);
+            } else {
+                frame.push(126);
+                frame.extend_from_slice(&(line.len() as u16).to_be_bytes());
+            }
+            frame.extend_from_slice(line.as_bytes());
+            self.write(&frame).await;
+        } else {
+            self.write(format!("{}\r\n", line).as_bytes()).await;
+        }
+    }
+
+    /// Send a WebSocket ping frame. The client should answer with a pong, which is not checked.
+    pub async fn send_ws_ping(&mut self) {
+        self.write(&[0x89, 0x04, b'p', b'i', b'n', b'g']).await;
+    }
+
+    /// Run a script.
+    pub async fn run(&mut self, script: &[Step<'_>]) {
+        for step in script {
+            match step {
+                Step::Expect(pattern) => self.expect(pattern).await,
+                Step::Send(line) => self.send(line).await,
+            }
+        }
+    }
+}
@@ -19,7 +19,7 @@ fn main() {
     let lines = file_buffered.lines().map(Result::unwrap).collect();
 
     let mut executor = Runtime::new().unwrap();
-    let (tui, _) = TUI::run(Colors::default(), &mut executor);
+    let (tui, _) = TUI::run(Colors::default(), false, &mut executor);
 
     tui.new_server_tab("test");
     tui.draw();
@@ -34,7 +34,15 @@ async fn bench_task(tui: TUI, lines: Vec<String>) {
     let time = time::now();
 
     for line in &lines {
-        tui.add_privmsg("server", line, time, &msg_target, false, false);
+        tui.add_privmsg(
+            "server",
+            line,
+            time,
+            &msg_target,
+            false,
+            false,
+            MsgTags::default(),
+        );
         tui.draw();
     }
 }
@@ -0,0 +1,336 @@
+//! A full-screen view for channel lists (replies to LIST command). Channels can be sorted, filtered
+//! by a substring of the name or topic, and joined with enter.
+
+use term_input::{Arrow, Key};
+use termbox_simple::Termbox;
+
+use crate::{config::Colors, termbox, widget::WidgetRet, ChanListEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    /// Channels with more users first
+    Users,
+    /// Alphabetically, by channel name
+    Name,
+}
+
+pub(crate) struct ChanList {
+    /// Server of the channels
+    serv: String,
+
+    chans: Vec<ChanListEntry>,
+
+    /// Indices of channels in `chans` that match `filter`, in sort order
+    visible: Vec<usize>,
+
+    /// Channels with the name or topic containing this (case-insensitive) are shown
+    filter: String,
+
+    sort: Sort,
+
+    /// Index of the selected channel in `visible`
+    selected: usize,
+
+    /// Index of the channel in `visible` shown at the top
+    scroll: usize,
+
+    width: i32,
+    height: i32,
+}
+
+impl ChanList {
+    pub(crate) fn new(serv: &str, chans: Vec<ChanListEntry>, width: i32, height: i32) -> ChanList {
+        let mut chan_list = ChanList {
+            serv: serv.to_owned(),
+            chans,
+            visible: vec![],
+            filter: String::new(),
+            sort: Sort::Users,
+            selected: 0,
+            scroll: 0,
+            width,
+            height,
+        };
+        chan_list.update_visible();
+        chan_list
+    }
+
+    pub(crate) fn serv(&self) -> &str {
+        &self.serv
+    }
+
+    pub(crate) fn resize(&mut self, width: i32, height: i32) {
+        self.width = width;
+        self.height = height;
+        self.fix_scroll();
+    }
+
+    /// Number of rows available for channels. First row is used for the header, last row is used
+    /// for the filter.
+    fn list_height(&self) -> usize {
+        ::std::cmp::max(self.height - 2, 1) as usize
+    }
+
+    fn update_visible(&mut self) {
+        let filter = self.filter.to_lowercase();
+        let chans = &self.chans;
+        self.visible = (0..chans.len())
+            .filter(|idx| {
+                let chan = &chans[*idx];
+                filter.is_empty()
+                    || chan.chan.to_lowercase().contains(&filter)
+                    || chan.topic.to_lowercase().contains(&filter)
+            })
+            .collect();
+        match self.sort {
+            Sort::Users => self.visible.sort_by(|idx1, idx2| {
+                let (chan1, chan2) = (&chans[*idx1], &chans[*idx2]);
+                chan2
+                    .n_users
+                    .cmp(&chan1.n_users)
+                    .then_with(|| chan1.chan.cmp(&chan2.chan))
+            }),
+            Sort::Name => self.visible.sort_by(|idx1, idx2| {
+                chans[*idx1]
+                    .chan
+                    .to_lowercase()
+                    .cmp(&chans[*idx2].chan.to_lowercase())
+            }),
+        }
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn selected_chan(&self) -> Option<&ChanListEntry> {
+        self.visible.get(self.selected).map(|idx| &self.chans[*idx])
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = ::std::cmp::min(selected, self.visible.len().saturating_sub(1));
+        self.fix_scroll();
+    }
+
+    /// Update `scroll` to make the selected channel visible.
+    fn fix_scroll(&mut self) {
+        let list_height = self.list_height();
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+    }
+
+    pub(crate) fn keypressed(&mut self, key: Key) -> WidgetRet {
+        match key {
+            Key::Esc | Key::Ctrl('c') => WidgetRet::Remove,
+
+            Key::Char('\r') => match self.selected_chan() {
+                None => WidgetRet::KeyHandled,
+                Some(chan) => WidgetRet::Input(format!("/join {}", chan.chan).chars().collect()),
+            },
+
+            Key::Arrow(Arrow::Up) => {
+                self.select(self.selected.saturating_sub(1));
+                WidgetRet::KeyHandled
+            }
+
+            Key::Arrow(Arrow::Down) => {
+                self.select(self.selected + 1);
+                WidgetRet::KeyHandled
+            }
+
+            Key::PageUp => {
+                self.select(self.selected.saturating_sub(self.list_height()));
+                WidgetRet::KeyHandled
+            }
+
+            Key::PageDown => {
+                self.select(self.selected + self.list_height());
+                WidgetRet::KeyHandled
+            }
+
+            Key::Home => {
+                self.select(0);
+                WidgetRet::KeyHandled
+            }
+
+            Key::End => {
+                self.select(self.visible.len());
+                WidgetRet::KeyHandled
+            }
+
+            Key::Tab => {
+                self.sort = match self.sort {
+                    Sort::Users => Sort::Name,
+                    Sort::Name => Sort::Users,
+                };
+                self.update_visible();
+                WidgetRet::KeyHandled
+            }
+
+            Key::Backspace => {
+                if self.filter.pop().is_some() {
+                    self.update_visible();
+                }
+                WidgetRet::KeyHandled
+            }
+
+            Key::Char(c) => {
+                self.filter.push(c);
+                self.update_visible();
+                WidgetRet::KeyHandled
+            }
+
+            _ => WidgetRet::KeyIgnored,
+        }
+    }
+
+    pub(crate) fn draw(&self, tb: &mut Termbox, colors: &Colors, pos_x: i32, pos_y: i32) {
+        let width = self.width as usize;
+
+        let header = format!(
+            "{}: {}/{} channels, sorted by {} (enter: join, tab: change sorting, esc: close)",
+            self.serv,
+            self.visible.len(),
+            self.chans.len(),
+            match self.sort {
+                Sort::Users => "users",
+                Sort::Name => "name",
+            }
+        );
+        termbox::print_chars(tb, pos_x, pos_y, colors.topic, header.chars().take(width));
+
+        // Channel names are aligned, but use at most a third of the screen
+        let chan_width = self
+            .visible
+            .iter()
+            .map(|idx| self.chans[*idx].chan.chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(width / 3);
+
+        let rows = self.visible[self.scroll..]
+            .iter()
+            .take(self.list_height())
+            .enumerate();
+        for (row, idx) in rows {
+            let chan = &self.chans[*idx];
+            let row_y = pos_y + 1 + row as i32;
+            let selected = self.scroll + row == self.selected;
+
+            let (chan_style, users_style, topic_style) = if selected {
+                (colors.completion, colors.completion, colors.completion)
+            } else {
+                (colors.user_msg, colors.faded, colors.user_msg)
+            };
+
+            if selected {
+                for x in 0..self.width {
+                    tb.change_cell(
+                        pos_x + x,
+                        row_y,
+                        ' ',
+                        colors.completion.fg,
+                        colors.completion.bg,
+                    );
+                }
+            }
+
+            let chan_name = format!("{:<w$}", chan.chan, w = chan_width);
+            let users = format!(" {:>6} ", chan.n_users);
+            let mut x = pos_x;
+            termbox::print_chars(tb, x, row_y, chan_style, chan_name.chars().take(chan_width));
+            x += chan_width as i32;
+            termbox::print_chars(tb, x, row_y, users_style, users.chars());
+            x += users.len() as i32;
+            let topic_width = (pos_x + self.width - x).max(0) as usize;
+            termbox::print_chars(
+                tb,
+                x,
+                row_y,
+                topic_style,
+                chan.topic.chars().take(topic_width),
+            );
+        }
+
+        let filter_y = pos_y + self.height - 1;
+        let filter = format!("Filter: {}", self.filter);
+        let filter_len = filter.chars().count();
+        termbox::print_chars(
+            tb,
+            pos_x,
+            filter_y,
+            colors.user_msg,
+            filter.chars().take(width),
+        );
+        if filter_len < width {
+            tb.set_cursor(Some((
+                (pos_x as usize + filter_len) as u16,
+                filter_y as u16,
+            )));
+        } else {
+            tb.hide_cursor();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chan(chan: &str, n_users: usize, topic: &str) -> ChanListEntry {
+        ChanListEntry {
+            chan: chan.to_owned(),
+            n_users,
+            topic: topic.to_owned(),
+        }
+    }
+
+    fn visible_chans(chan_list: &ChanList) -> Vec<&str> {
+        chan_list
+            .visible
+            .iter()
+            .map(|idx| chan_list.chans[*idx].chan.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_filter() {
+        let chans = vec![
+            chan("#tiny", 10, "tiny IRC client"),
+            chan("#rust", 1000, "Rust programming language"),
+            chan("#haskell", 800, "Haskell"),
+        ];
+        let mut chan_list = ChanList::new("server", chans, 80, 10);
+        assert_eq!(
+            visible_chans(&chan_list),
+            vec!["#rust", "#haskell", "#tiny"]
+        );
+
+        chan_list.keypressed(Key::Tab);
+        assert_eq!(
+            visible_chans(&chan_list),
+            vec!["#haskell", "#rust", "#tiny"]
+        );
+
+        // Filter matches topics too
+        for c in "IRC".chars() {
+            chan_list.keypressed(Key::Char(c));
+        }
+        assert_eq!(visible_chans(&chan_list), vec!["#tiny"]);
+        match chan_list.keypressed(Key::Char('\r')) {
+            WidgetRet::Input(input) => {
+                assert_eq!(input.into_iter().collect::<String>(), "/join #tiny")
+            }
+            _ => panic!(),
+        }
+
+        chan_list.keypressed(Key::Backspace);
+        chan_list.keypressed(Key::Backspace);
+        assert_eq!(visible_chans(&chan_list), vec!["#rust", "#tiny"]);
+
+        chan_list.keypressed(Key::Arrow(Arrow::Down));
+        chan_list.keypressed(Key::Arrow(Arrow::Down));
+        assert_eq!(chan_list.selected_chan().unwrap().chan, "#tiny");
+    }
+}
@@ -0,0 +1,205 @@
+//! Rendering messages as HTML with the colors of the TUI, e.g. to export logs.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::config::Colors;
+use crate::msg_area::line::irc_color_to_termbox;
+use crate::utils::{nick_color_hash, parse_color_code};
+
+// Default colors of xterm, used for the first 16 colors of the 256-color palette
+const SYSTEM_COLORS: [&str; 16] = [
+    "#000000", "#cd0000", "#00cd00", "#cdcd00", "#0000ee", "#cd00cd", "#00cdcd", "#e5e5e5",
+    "#7f7f7f", "#ff0000", "#00ff00", "#ffff00", "#5c5cff", "#ff00ff", "#00ffff", "#ffffff",
+];
+
+/// CSS color of a termbox color (e.g. a `Style` fg). `None` for the terminal's default color.
+pub fn termbox_color(color: u16) -> Option<String> {
+    // Higher bits are attributes (bold, underline)
+    let color = color as u8;
+    match color {
+        0 => None,
+        1..=15 => Some(SYSTEM_COLORS[usize::from(color)].to_owned()),
+        16..=231 => {
+            // 6x6x6 color cube
+            let level = |c: u8| if c == 0 { 0 } else { 55 + c * 40 };
+            let c = color - 16;
+            Some(format!(
+                "#{:02x}{:02x}{:02x}",
+                level(c / 36),
+                level((c / 6) % 6),
+                level(c % 6)
+            ))
+        }
+        232..=255 => {
+            let level = 8 + (color - 232) * 10;
+            Some(format!("#{:02x}{:02x}{:02x}", level, level, level))
+        }
+    }
+}
+
+/// CSS color of a nick, as shown in the TUI.
+pub fn nick_color(colors: &Colors, nick: &str) -> Option<String> {
+    if colors.nick.is_empty() {
+        return None;
+    }
+    termbox_color(u16::from(
+        colors.nick[nick_color_hash(nick) % colors.nick.len()],
+    ))
+}
+
+/// Formatting state of a message, changed by IRC control chars.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct IrcStyle {
+    fg: Option<u8>,
+    bg: Option<u8>,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    strikethrough: bool,
+    reverse: bool,
+}
+
+impl IrcStyle {
+    fn css(&self) -> String {
+        let (fg, bg) = if self.reverse {
+            (self.bg, self.fg)
+        } else {
+            (self.fg, self.bg)
+        };
+        let irc_color = |color: u8| termbox_color(u16::from(irc_color_to_termbox(color)));
+
+        let mut css = vec![];
+        if let Some(fg) = fg.and_then(irc_color) {
+            css.push(format!("color: {}", fg));
+        }
+        if let Some(bg) = bg.and_then(irc_color) {
+            css.push(format!("background-color: {}", bg));
+        }
+        if self.bold {
+            css.push("font-weight: bold".to_owned());
+        }
+        if self.italic {
+            css.push("font-style: italic".to_owned());
+        }
+        match (self.underline, self.strikethrough) {
+            (false, false) => {}
+            (true, false) => css.push("text-decoration: underline".to_owned()),
+            (false, true) => css.push("text-decoration: line-through".to_owned()),
+            (true, true) => css.push("text-decoration: underline line-through".to_owned()),
+        }
+        css.join("; ")
+    }
+}
+
+/// Parses the colors after a `\x03`: `fg` or `fg,bg`. Like in `translate_irc_control_chars`, a
+/// comma that is not followed by a color is not consumed.
+fn parse_colors(chars: &mut Peekable<Chars>) -> Option<(u8, Option<u8>)> {
+    let fg = parse_color_code(chars)?;
+    if chars.peek() == Some(&',') {
+        let mut bg_chars = chars.clone();
+        bg_chars.next(); // consume ','
+        if let Some(bg) = parse_color_code(&mut bg_chars) {
+            *chars = bg_chars;
+            return Some((fg, Some(bg)));
+        }
+    }
+    Some((fg, None))
+}
+
+/// Escape a message for HTML, translating IRC colors, bold, italic, underline, strikethrough,
+/// reverse and reset codes to styled `<span>`s. Other control chars are removed.
+pub fn irc_to_html(msg: &str) -> String {
+    let mut ret = String::with_capacity(msg.len());
+    let mut style = IrcStyle::default();
+    // Style of the open span
+    let mut span_style = IrcStyle::default();
+    let mut iter = msg.chars().peekable();
+
+    while let Some(char) = iter.next() {
+        match char {
+            '\x02' => style.bold = !style.bold,
+            '\x1D' => style.italic = !style.italic,
+            '\x1F' => style.underline = !style.underline,
+            '\x1E' => style.strikethrough = !style.strikethrough,
+            '\x16' => style.reverse = !style.reverse,
+            '\x0F' => style = IrcStyle::default(),
+            '\x03' => match parse_colors(&mut iter) {
+                None => {
+                    style.fg = None;
+                    style.bg = None;
+                }
+                Some((fg, bg)) => {
+                    style.fg = Some(fg);
+                    // Background stays the same when not specified
+                    if bg.is_some() {
+                        style.bg = bg;
+                    }
+                }
+            },
+            _ if char.is_ascii_control() => {}
+            _ => {
+                if style != span_style {
+                    if span_style != IrcStyle::default() {
+                        ret.push_str("</span>");
+                    }
+                    if style != IrcStyle::default() {
+                        ret.push_str(&format!("<span style=\"{}\">", style.css()));
+                    }
+                    span_style = style;
+                }
+                match char {
+                    '&' => ret.push_str("&amp;"),
+                    '<' => ret.push_str("&lt;"),
+                    '>' => ret.push_str("&gt;"),
+                    '"' => ret.push_str("&quot;"),
+                    '\'' => ret.push_str("&#39;"),
+                    _ => ret.push(char),
+                }
+            }
+        }
+    }
+
+    if span_style != IrcStyle::default() {
+        ret.push_str("</span>");
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_irc_to_html() {
+        assert_eq!(irc_to_html("<b> & \"x\""), "&lt;b&gt; &amp; &quot;x&quot;");
+        assert_eq!(
+            irc_to_html("a \x02bold\x02 b"),
+            "a <span style=\"font-weight: bold\">bold</span> b"
+        );
+        assert_eq!(
+            irc_to_html("\x0304,02red\x03 \x1D\x1Fit\x0F."),
+            "<span style=\"color: #ff0000; background-color: #00005f\">red</span> \
+             <span style=\"font-style: italic; text-decoration: underline\">it</span>."
+        );
+        // Background is kept when only foreground is changed
+        assert_eq!(
+            irc_to_html("\x0304,02a\x0308b"),
+            "<span style=\"color: #ff0000; background-color: #00005f\">a</span>\
+             <span style=\"color: #ffff00; background-color: #00005f\">b</span>"
+        );
+        assert_eq!(
+            irc_to_html("\x0304,foo"),
+            "<span style=\"color: #ff0000\">,foo</span>"
+        );
+        assert_eq!(irc_to_html("\x02\x02\x07x"), "x");
+    }
+
+    #[test]
+    fn test_termbox_color() {
+        assert_eq!(termbox_color(0), None);
+        assert_eq!(termbox_color(9 | 0x0100), Some("#ff0000".to_owned()));
+        assert_eq!(termbox_color(17), Some("#00005f".to_owned()));
+        assert_eq!(termbox_color(242), Some("#6c6c6c".to_owned()));
+    }
+}
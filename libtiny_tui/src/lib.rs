@@ -2,8 +2,10 @@
 #![allow(clippy::too_many_arguments)]
 #![allow(clippy::cognitive_complexity)]
 
+mod chan_list;
 mod config;
 mod exit_dialogue;
+pub mod html;
 mod messaging;
 // FIXME: This is "pub" to be able to use in an example
 #[doc(hidden)]
@@ -20,6 +22,7 @@ mod widget;
 
 pub use crate::config::Colors;
 pub use crate::tab::TabStyle;
+pub use crate::tui::ScrollbackLoader;
 pub use libtiny_ui::*;
 
 use futures::select;
@@ -171,6 +174,7 @@ impl UI for TUI {
     delegate!(close_server_tab(serv_name: &str,));
     delegate!(new_chan_tab(serv_name: &str, chan: &str,));
     delegate!(close_chan_tab(serv_name: &str, chan: &str,));
+    delegate!(new_user_tab(serv_name: &str, nick: &str,));
     delegate!(close_user_tab(serv_name: &str, nick: &str,));
     delegate!(add_client_msg(msg: &str, target: &MsgTarget,));
     delegate!(add_msg(msg: &str, ts: Tm, target: &MsgTarget,));
@@ -185,6 +189,21 @@ impl UI for TUI {
         target: &MsgTarget,
         highlight: bool,
         is_action: bool,
+        tags: MsgTags,
+    ));
+    delegate!(add_reaction(
+        sender: &str,
+        reaction: &str,
+        msgid: &str,
+        ts: Tm,
+        target: &MsgTarget,
+    ));
+    delegate!(add_mention(
+        sender: &str,
+        msg: &str,
+        ts: Tm,
+        serv_name: &str,
+        chan_name: &str,
     ));
     delegate!(add_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
     delegate!(remove_nick(nick: &str, ts: Option<Tm>, target: &MsgTarget,));
@@ -200,7 +219,20 @@ impl UI for TUI {
         serv_name: &str,
         chan_name: &str,
     ));
+    delegate!(add_chan_event(
+        event: ChanEvent,
+        ts: Tm,
+        serv_name: &str,
+        chan_name: &str,
+    ));
     delegate!(set_tab_style(style: TabStyle, target: &MsgTarget,));
+    delegate!(show_chan_list(serv_name: &str, chans: &[ChanListEntry],));
+    delegate!(add_raw_msg(
+        serv_name: &str,
+        msg: &str,
+        ts: Tm,
+        outgoing: bool,
+    ));
 
     fn user_tab_exists(&self, serv_name: &str, nick: &str) -> bool {
         match self.inner.upgrade() {
@@ -212,4 +244,11 @@ impl UI for TUI {
 
 impl TUI {
     delegate_pub!(set_colors(colors: Colors,));
+    delegate_pub!(set_scrollback_loader(loader: ScrollbackLoader,));
+    delegate_pub!(add_restored_lines(
+        serv: &str,
+        target: &str,
+        lines: &[(Tm, String)],
+    ));
+    delegate_pub!(add_restore_err(serv: &str, target: &str, err: &str,));
 }
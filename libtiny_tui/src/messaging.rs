@@ -15,6 +15,7 @@ use crate::{
     termbox,
     text_field::TextField,
     trie::Trie,
+    utils::nick_color_hash,
     widget::WidgetRet,
 };
 
@@ -122,7 +123,7 @@ impl MessagingUI {
 
         if let Some(ref nick) = self.current_nick {
             if self.show_current_nick {
-                let nick_color = colors.nick[self.get_nick_color(nick) % colors.nick.len()];
+                let nick_color = colors.nick[nick_color_hash(nick) % colors.nick.len()];
                 let style = Style {
                     fg: u16::from(nick_color),
                     bg: colors.user_msg.bg,
@@ -324,8 +325,20 @@ impl MessagingUI {
         ts: Timestamp,
         highlight: bool,
         is_action: bool,
+        msgid: Option<&str>,
+        reply_to: Option<&str>,
     ) {
         self.reset_activity_line();
+
+        // Quote the parent message of a reply, if we have it
+        if let Some(summary) = reply_to.and_then(|msgid| self.msg_area.get_msgid_summary(msgid)) {
+            let quote = format!("> {}", summary);
+            self.msg_area
+                .set_style(SegStyle::SchemeStyle(SchemeStyle::Faded));
+            self.msg_area.add_text(&quote);
+            self.msg_area.flush_line();
+        }
+
         self.add_timestamp(ts);
 
         if is_action {
@@ -335,7 +348,7 @@ impl MessagingUI {
         }
 
         {
-            let nick_color = self.get_nick_color(sender);
+            let nick_color = nick_color_hash(sender);
             let style = SegStyle::Index(nick_color);
             self.msg_area.set_style(style);
             self.msg_area.add_text(sender);
@@ -355,7 +368,32 @@ impl MessagingUI {
         }
 
         self.msg_area.add_text(msg);
-        self.msg_area.flush_line();
+        let line_idx = self.msg_area.flush_line();
+
+        if let Some(msgid) = msgid {
+            self.msg_area
+                .set_msgid(line_idx, msgid, msg_summary(sender, msg));
+        }
+    }
+
+    /// Show a reaction to the message with the given `msgid`. Reactions to messages that are not
+    /// in this tab are shown as separate lines.
+    pub(crate) fn add_reaction(
+        &mut self,
+        sender: &str,
+        reaction: &str,
+        msgid: &str,
+        ts: Timestamp,
+    ) {
+        if !self.msg_area.add_reaction(msgid, reaction) {
+            self.reset_activity_line();
+            self.add_timestamp(ts);
+            self.msg_area
+                .set_style(SegStyle::SchemeStyle(SchemeStyle::Faded));
+            self.msg_area
+                .add_text(&format!("{} reacted with {}", sender, reaction));
+            self.msg_area.flush_line();
+        }
     }
 
     pub(crate) fn add_msg(&mut self, msg: &str, ts: Timestamp) {
@@ -382,13 +420,39 @@ impl MessagingUI {
         self.msg_area.clear();
     }
 
-    fn get_nick_color(&self, sender: &str) -> usize {
-        // Anything works as long as it's fast
-        let mut hash: usize = 5381;
-        for c in sender.chars() {
-            hash = hash.wrapping_mul(33).wrapping_add(c as usize);
+    /// Show lines restored from logs, followed by a separator. Lines are shown in faded style,
+    /// before the existing lines as they're loaded after the tab is created.
+    pub(crate) fn add_restored_lines(&mut self, lines: &[(Tm, String)]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let first_restored_idx = self.msg_area.num_lines();
+        self.reset_activity_line();
+        for (ts, line) in lines {
+            self.add_timestamp(Timestamp::from(*ts));
+            self.msg_area
+                .set_style(SegStyle::SchemeStyle(SchemeStyle::Faded));
+            self.msg_area.add_text(line);
+            self.msg_area.flush_line();
         }
-        hash
+
+        self.msg_area
+            .set_style(SegStyle::SchemeStyle(SchemeStyle::Faded));
+        self.msg_area.add_text("--- restored from log ---");
+        self.msg_area.flush_line();
+        self.msg_area.move_lines_to_top(first_restored_idx);
+        // Show timestamp of the next message
+        self.last_activity_ts = None;
+    }
+}
+
+/// Summary of a message to be shown when quoting it in a reply.
+fn msg_summary(sender: &str, msg: &str) -> String {
+    const MAX_CHARS: usize = 50;
+    match msg.char_indices().nth(MAX_CHARS) {
+        None => format!("{}: {}", sender, msg),
+        Some((idx, _)) => format!("{}: {}...", sender, &msg[..idx]),
     }
 }
 
@@ -5,7 +5,7 @@ use crate::{config, config::Colors, utils::translate_irc_control_chars};
 
 /// A single line added to the widget. May be rendered as multiple lines on the
 /// screen.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Line {
     /// Line segments.
     segs: Vec<Seg>,
@@ -24,9 +24,17 @@ pub(crate) struct Line {
     /// as we use difference between two indices in this vector as length of
     /// substrings.
     splits: Vec<i32>,
+
+    /// Reactions (IRCv3 `+draft/react`) to this line, with number of times each reaction is
+    /// received.
+    reactions: Vec<(String, usize)>,
+
+    /// The line without the reactions. Set when the first reaction is added, used to render the
+    /// reactions again when a new reaction is added.
+    without_reactions: Option<Box<Line>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Seg {
     text: String,
     style: SegStyle,
@@ -98,6 +106,8 @@ impl Line {
             },
             len_chars: 0,
             splits: Vec::new(),
+            reactions: Vec::new(),
+            without_reactions: None,
         }
     }
 
@@ -160,6 +170,34 @@ impl Line {
         self.len_chars += 1;
     }
 
+    /// Add a reaction to the line. Reactions are shown at the end of the line, grouped by the
+    /// reaction, e.g. `[+1 2] [:) 1]`.
+    pub(crate) fn add_reaction(&mut self, reaction: &str) {
+        let mut reactions = mem::take(&mut self.reactions);
+        match reactions
+            .iter_mut()
+            .find(|(reaction_, _)| reaction_ == reaction)
+        {
+            Some((_, n)) => *n += 1,
+            None => reactions.push((reaction.to_owned(), 1)),
+        }
+
+        let without_reactions = match self.without_reactions.take() {
+            Some(line) => line,
+            None => Box::new(self.clone()),
+        };
+        *self = (*without_reactions).clone();
+
+        self.set_style(SegStyle::SchemeStyle(SchemeStyle::Faded));
+        for (reaction, n) in &reactions {
+            self.add_char(' ');
+            self.add_text(&format!("[{} {}]", reaction, n));
+        }
+
+        self.reactions = reactions;
+        self.without_reactions = Some(without_reactions);
+    }
+
     /// How many lines does this take when rendered? O(n) where n = number of
     /// split positions in the line (i.e. whitespaces).
     pub(crate) fn rendered_height(&self, width: i32) -> i32 {
@@ -270,7 +308,7 @@ impl Line {
 // IRC colors: http://en.wikichip.org/wiki/irc/colors
 // Termbox colors: http://www.calmar.ws/vim/256-xterm-24bit-rgb-color-chart.html
 //                 (alternatively just run `cargo run --example colors`)
-fn irc_color_to_termbox(irc_color: u8) -> u8 {
+pub(crate) fn irc_color_to_termbox(irc_color: u8) -> u8 {
     match irc_color {
         0 => 15,  // white
         1 => 0,   // black
@@ -391,6 +429,25 @@ mod tests {
         assert_eq!(line.rendered_height(1), 1160);
     }
 
+    #[test]
+    fn reactions_test() {
+        let mut line = Line::new();
+        line.add_text("ab cd");
+        assert_eq!(line.rendered_height(5), 1);
+
+        line.add_reaction("+1");
+        assert_eq!(line.len_chars, 12); // "ab cd [+1 1]"
+        line.add_reaction("+1");
+        assert_eq!(line.len_chars, 12); // "ab cd [+1 2]"
+        line.add_reaction(":)");
+        assert_eq!(line.len_chars, 19); // "ab cd [+1 2] [:) 1]"
+        assert_eq!(
+            line.reactions,
+            vec![("+1".to_owned(), 2), (":)".to_owned(), 1)]
+        );
+        assert_eq!(line.rendered_height(5), 5);
+    }
+
     #[bench]
     fn bench_rendered_height(b: &mut Bencher) {
         // 1160 words, 2,237 ns/iter (+/- 150)
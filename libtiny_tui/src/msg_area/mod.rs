@@ -1,6 +1,6 @@
 pub(crate) mod line;
 
-use std::{cmp::max, mem, str};
+use std::{cmp::max, collections::HashMap, mem, str};
 
 use termbox_simple::Termbox;
 
@@ -24,6 +24,11 @@ pub(crate) struct MsgArea {
     /// Cached total rendered height of all lines. Invalidate on resize, update
     /// when adding new lines.
     lines_height: Option<i32>,
+
+    /// Maps IRCv3 `msgid`s of messages in this area to their line indices and
+    /// summaries (`sender: msg`) to be shown when quoting the message in
+    /// replies.
+    msgids: HashMap<String, (usize, String)>,
 }
 
 impl MsgArea {
@@ -35,6 +40,7 @@ impl MsgArea {
             scroll: 0,
             line_buf: Line::new(),
             lines_height: Some(0),
+            msgids: HashMap::new(),
         }
     }
 
@@ -172,10 +178,63 @@ impl MsgArea {
         f(&mut self.lines[idx]);
     }
 
+    pub(crate) fn num_lines(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Move lines starting from `idx` to the beginning of the area, e.g. to show lines that are
+    /// loaded later before the existing lines. Scroll position doesn't change.
+    pub(crate) fn move_lines_to_top(&mut self, idx: usize) {
+        let n_moved = self.lines.len() - idx;
+        if self.scroll != 0 {
+            // Undo scroll updates in `flush_line`
+            let moved_height: i32 = self.lines[idx..]
+                .iter()
+                .map(|line| line.rendered_height(self.width))
+                .sum();
+            self.scroll -= moved_height;
+        }
+        self.lines.rotate_right(n_moved);
+        for (line_idx, _) in self.msgids.values_mut() {
+            *line_idx += n_moved;
+        }
+    }
+
     pub(crate) fn clear(&mut self) {
         self.lines.clear();
         self.scroll = 0;
         self.lines_height = None;
+        self.msgids.clear();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Message ids, replies and reactions
+
+impl MsgArea {
+    /// Associate a line with an IRCv3 `msgid`. `summary` is shown when the
+    /// message is quoted in a reply.
+    pub(crate) fn set_msgid(&mut self, idx: usize, msgid: &str, summary: String) {
+        self.msgids.insert(msgid.to_owned(), (idx, summary));
+    }
+
+    /// Summary of the message with the given `msgid`, if it's in this area.
+    pub(crate) fn get_msgid_summary(&self, msgid: &str) -> Option<&str> {
+        self.msgids.get(msgid).map(|(_, summary)| summary.as_str())
+    }
+
+    /// Add a reaction to the line with the given `msgid`. Returns `false` if
+    /// we don't have the line.
+    pub(crate) fn add_reaction(&mut self, msgid: &str, reaction: &str) -> bool {
+        match self.msgids.get(msgid) {
+            None => false,
+            Some((idx, _)) => {
+                self.lines[*idx].add_reaction(reaction);
+                // Line height may change
+                self.lines_height = None;
+                true
+            }
+        }
     }
 }
 
@@ -203,4 +262,40 @@ mod tests {
         msg_area.flush_line();
         assert_eq!(msg_area.scroll, 2);
     }
+
+    #[test]
+    fn move_lines_to_top() {
+        let mut msg_area = MsgArea::new(5, 1);
+        msg_area.add_text("line1");
+        let idx = msg_area.flush_line();
+        msg_area.set_msgid(idx, "id1", "nick: line1".to_owned());
+        msg_area.add_text("line2");
+        msg_area.flush_line();
+        msg_area.scroll_up();
+        assert_eq!(msg_area.scroll, 1);
+
+        // Rendered in two lines
+        msg_area.add_text("restored line");
+        msg_area.flush_line();
+        msg_area.move_lines_to_top(2);
+        assert_eq!(msg_area.scroll, 1);
+        assert_eq!(msg_area.lines[0].rendered_height(5), 2);
+        assert_eq!(msg_area.lines[1].rendered_height(5), 1);
+        assert_eq!(msg_area.msgids["id1"].0, 1);
+    }
+
+    #[test]
+    fn msgid_lookup() {
+        let mut msg_area = MsgArea::new(100, 1);
+        msg_area.add_text("line1");
+        let idx = msg_area.flush_line();
+        msg_area.set_msgid(idx, "id1", "nick: line1".to_owned());
+        assert_eq!(msg_area.get_msgid_summary("id1"), Some("nick: line1"));
+        assert_eq!(msg_area.get_msgid_summary("id2"), None);
+        assert!(msg_area.add_reaction("id1", "+1"));
+        assert!(!msg_area.add_reaction("id2", "+1"));
+
+        msg_area.clear();
+        assert_eq!(msg_area.get_msgid_summary("id1"), None);
+    }
 }
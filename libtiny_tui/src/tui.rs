@@ -6,6 +6,7 @@ use std::str;
 use std::str::SplitWhitespace;
 use time::Tm;
 
+use crate::chan_list::ChanList;
 use crate::config::Colors;
 use crate::notifier::Notifier;
 use crate::tab::TabStyle;
@@ -15,7 +16,10 @@ use crate::messaging::{MessagingUI, Timestamp};
 use crate::statusline::{draw_statusline, statusline_visible};
 use crate::tab::Tab;
 use crate::widget::WidgetRet;
-use crate::{MsgSource, MsgTarget};
+use crate::{
+    mention_msg, raw_tab_name, ChanEvent, ChanListEntry, MsgSource, MsgTags, MsgTarget,
+    MENTIONS_TAB,
+};
 use term_input::{Arrow, Event, Key};
 use termbox_simple::Termbox;
 
@@ -64,8 +68,19 @@ pub(crate) struct TUI {
 
 	// Do you want to show timestamp in every msg ?
     every_msg_ts: bool,
+
+    /// Channel list view. Shown instead of the active tab, and handles input, when available.
+    chan_list: Option<ChanList>,
+
+    /// Used to restore scrollback of new channel and user tabs. See `ScrollbackLoader`.
+    scrollback_loader: Option<ScrollbackLoader>,
 }
 
+/// Called when a channel or user tab is created, with the server name and the channel or nick,
+/// before anything is added to the tab. Should load the lines to show in the tab without blocking
+/// (e.g. in another thread), and add them with `TUI::add_restored_lines`.
+pub type ScrollbackLoader = Box<dyn Fn(&str, &str)>;
+
 impl TUI {
     pub(crate) fn new(colors: Colors, tsmsg: bool) -> TUI {
         let mut tb = Termbox::init().unwrap(); // TODO: check errors
@@ -85,6 +100,8 @@ impl TUI {
             show_statusline: false,
             statusline_visible: statusline_visible(width, height),
             every_msg_ts: tsmsg,
+            chan_list: None,
+            scrollback_loader: None,
         }
     }
 
@@ -189,6 +206,36 @@ impl TUI {
         }
     }
 
+    pub(crate) fn set_scrollback_loader(&mut self, loader: ScrollbackLoader) {
+        self.scrollback_loader = Some(loader);
+    }
+
+    fn restore_scrollback(&mut self, serv: &str, target: &str) {
+        if let Some(ref loader) = self.scrollback_loader {
+            loader(serv, target);
+        }
+    }
+
+    /// Add lines restored from logs to a channel or user tab. Lines are shown before the messages
+    /// added to the tab since it was created.
+    pub(crate) fn add_restored_lines(&mut self, serv: &str, target: &str, lines: &[(Tm, String)]) {
+        if let Some(tab_idx) = self.find_restored_tab_idx(serv, target) {
+            self.tabs[tab_idx].widget.add_restored_lines(lines);
+        }
+    }
+
+    /// Show an error in a channel or user tab when its scrollback can't be restored.
+    pub(crate) fn add_restore_err(&mut self, serv: &str, target: &str, err: &str) {
+        if let Some(tab_idx) = self.find_restored_tab_idx(serv, target) {
+            self.tabs[tab_idx].widget.add_client_err_msg(err);
+        }
+    }
+
+    fn find_restored_tab_idx(&self, serv: &str, target: &str) -> Option<usize> {
+        self.find_chan_tab_idx(serv, target)
+            .or_else(|| self.find_user_tab_idx(serv, target))
+    }
+
     pub(crate) fn set_colors(&mut self, colors: Colors) {
         self.tb
             .set_clear_attributes(colors.clear.fg as u8, colors.clear.bg as u8);
@@ -267,6 +314,9 @@ impl TUI {
 
     /// Closes a server tab and all associated channel tabs.
     pub(crate) fn close_server_tab(&mut self, serv: &str) {
+        if self.chan_list.as_ref().map(|chan_list| chan_list.serv() == serv) == Some(true) {
+            self.chan_list = None;
+        }
         if let Some(tab_idx) = self.find_serv_tab_idx(serv) {
             self.tabs.retain(|tab: &Tab| tab.src.serv_name() != serv);
             if self.active_idx == tab_idx {
@@ -313,6 +363,7 @@ impl TUI {
                     {
                         self.tabs[tab_idx].widget.set_nick(nick);
                     }
+                    self.restore_scrollback(serv, chan);
                     Some(tab_idx)
                 }
             },
@@ -351,6 +402,7 @@ impl TUI {
                     if let Some(nick) = self.tabs[tab_idx].widget.get_nick().map(str::to_owned) {
                         self.tabs[tab_idx + 1].widget.set_nick(nick);
                     }
+                    self.restore_scrollback(serv, nick);
                     self.tabs[tab_idx + 1].widget.join(nick, None);
                     Some(tab_idx + 1)
                 }
@@ -442,6 +494,27 @@ impl TUI {
     }
 
     fn keypressed(&mut self, key: Key) -> TUIRet {
+        if let Some(chan_list) = &mut self.chan_list {
+            return match chan_list.keypressed(key) {
+                WidgetRet::KeyHandled => TUIRet::KeyHandled,
+                WidgetRet::KeyIgnored => TUIRet::KeyIgnored(key),
+                WidgetRet::Input(input) => {
+                    // Input is a /join command for the selected channel
+                    let serv = chan_list.serv().to_owned();
+                    self.chan_list = None;
+                    TUIRet::Input {
+                        msg: input,
+                        from: MsgSource::Serv { serv },
+                    }
+                }
+                WidgetRet::Remove => {
+                    self.chan_list = None;
+                    TUIRet::KeyHandled
+                }
+                WidgetRet::Abort => TUIRet::Abort,
+            };
+        }
+
         match self.tabs[self.active_idx].widget.keypressed(key) {
             WidgetRet::KeyHandled => TUIRet::KeyHandled,
             WidgetRet::KeyIgnored => self.handle_keypress(key),
@@ -538,6 +611,9 @@ impl TUI {
             tab.widget
                 .resize(self.width, self.height - 1 - statusline_height);
         }
+        if let Some(chan_list) = &mut self.chan_list {
+            chan_list.resize(self.width, self.height - 1 - statusline_height);
+        }
         // scroll the tab bar so that currently active tab is still visible
         let (mut tab_left, mut tab_right) = self.rendered_tabs();
         if tab_left == tab_right {
@@ -694,9 +770,15 @@ impl TUI {
             );
         }
 
-        self.tabs[self.active_idx]
-            .widget
-            .draw(&mut self.tb, &self.colors, 0, statusline_height);
+        match &self.chan_list {
+            Some(chan_list) => chan_list.draw(&mut self.tb, &self.colors, 0, statusline_height),
+            None => self.tabs[self.active_idx].widget.draw(
+                &mut self.tb,
+                &self.colors,
+                0,
+                statusline_height,
+            ),
+        }
 
         // decide whether we need to draw left/right arrows in tab bar
         let left_arr = self.draw_left_arrow();
@@ -1020,10 +1102,18 @@ impl TUI {
         target: &MsgTarget,
         highlight: bool,
         is_action: bool,
+        tags: MsgTags,
     ) {
         self.apply_to_target(target, &|tab: &mut Tab, _| {
-            tab.widget
-                .add_privmsg(sender, msg, Timestamp::from(ts), highlight, is_action);
+            tab.widget.add_privmsg(
+                sender,
+                msg,
+                Timestamp::from(ts),
+                highlight,
+                is_action,
+                tags.msgid,
+                tags.reply_to,
+            );
             let nick = tab.widget.get_nick();
             if let Some(nick_) = nick {
                 tab.notifier
@@ -1032,6 +1122,29 @@ impl TUI {
         });
     }
 
+    /// A reaction to a message. Shown next to the message when we have it, as a
+    /// separate line otherwise.
+    pub(crate) fn add_reaction(
+        &mut self,
+        sender: &str,
+        reaction: &str,
+        msgid: &str,
+        ts: Tm,
+        target: &MsgTarget,
+    ) {
+        self.apply_to_target(target, &|tab: &mut Tab, _| {
+            tab.widget
+                .add_reaction(sender, reaction, msgid, Timestamp::from(ts));
+        });
+    }
+
+    /// A channel message that mentions the user, shown in the mentions tab.
+    pub(crate) fn add_mention(&mut self, sender: &str, msg: &str, ts: Tm, serv: &str, chan: &str) {
+        let target = MsgTarget::Server { serv: MENTIONS_TAB };
+        self.add_msg(&mention_msg(sender, msg, serv, chan), ts, &target);
+        self.set_tab_style(TabStyle::Highlight, &target);
+    }
+
     /// A message without any explicit sender info. Useful for e.g. in server
     /// and debug log tabs. Timestamped and logged.
     pub(crate) fn add_msg(&mut self, msg: &str, ts: Tm, target: &MsgTarget) {
@@ -1040,6 +1153,14 @@ impl TUI {
         });
     }
 
+    /// Messages in raw protocol tabs. Outgoing messages are marked with ">>", incoming messages
+    /// are marked with "<<".
+    pub(crate) fn add_raw_msg(&mut self, serv: &str, msg: &str, ts: Tm, outgoing: bool) {
+        let msg = format!("{} {}", if outgoing { ">>" } else { "<<" }, msg);
+        let serv = raw_tab_name(serv);
+        self.add_msg(&msg, ts, &MsgTarget::Server { serv: &serv });
+    }
+
     /// Error messages related with the protocol - e.g. can't join a channel,
     /// nickname is in use etc. Timestamped and logged.
     pub(crate) fn add_err_msg(&mut self, msg: &str, ts: Tm, target: &MsgTarget) {
@@ -1055,6 +1176,39 @@ impl TUI {
         });
     }
 
+    /// Kicks, mode changes etc. Kicked nicks are removed from the channel.
+    pub(crate) fn add_chan_event(&mut self, event: ChanEvent, ts: Tm, serv: &str, chan: &str) {
+        let target = MsgTarget::Chan { serv, chan };
+        match event {
+            ChanEvent::Kick {
+                kicker,
+                nick,
+                reason,
+            } => {
+                let msg = match reason {
+                    None => format!("{} kicked {}", kicker, nick),
+                    Some(reason) => format!("{} kicked {} ({})", kicker, nick, reason),
+                };
+                self.add_msg(&msg, ts, &target);
+                self.remove_nick(nick, None, &target);
+            }
+            ChanEvent::Mode { setter, modes } => {
+                self.add_msg(&format!("{} sets mode {}", setter, modes), ts, &target);
+            }
+            ChanEvent::TopicSetBy { setter, set_at } => {
+                let msg = match set_at {
+                    None => format!("Topic set by {}", setter),
+                    Some(set_at) => format!(
+                        "Topic set by {} on {}",
+                        setter,
+                        set_at.strftime("%F %T").unwrap()
+                    ),
+                };
+                self.add_client_msg(&msg, &target);
+            }
+        }
+    }
+
     pub(crate) fn clear_nicks(&mut self, serv: &str) {
         let target = MsgTarget::AllServTabs { serv };
         self.apply_to_target(&target, &|tab: &mut Tab, _| {
@@ -1104,6 +1258,21 @@ impl TUI {
         self.apply_to_target(target, &|tab: &mut Tab, _| tab.widget.clear());
     }
 
+    /// Show the channel list view.
+    pub(crate) fn show_chan_list(&mut self, serv: &str, chans: &[ChanListEntry]) {
+        let statusline_height = if self.statusline_visible && self.show_statusline {
+            1
+        } else {
+            0
+        };
+        self.chan_list = Some(ChanList::new(
+            serv,
+            chans.to_vec(),
+            self.width,
+            self.height - 1 - statusline_height,
+        ));
+    }
+
     pub(crate) fn toggle_statusline(&mut self) {
         self.show_statusline = !self.show_statusline;
         self.resize();
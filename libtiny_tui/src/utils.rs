@@ -53,13 +53,23 @@ pub(crate) fn is_nick_char(c: char) -> bool {
                     // this char in the wild
 }
 
+/// Hash of a nick, used to pick the nick's color from `Colors::nick`.
+pub(crate) fn nick_color_hash(nick: &str) -> usize {
+    // Anything works as long as it's fast
+    let mut hash: usize = 5381;
+    for c in nick.chars() {
+        hash = hash.wrapping_mul(33).wrapping_add(c as usize);
+    }
+    hash
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 use std::{iter::Peekable, str::Chars};
 
 /// Parse at least one, at most two digits. Does not consume the iterator when
 /// result is `None`.
-fn parse_color_code(chars: &mut Peekable<Chars>) -> Option<u8> {
+pub(crate) fn parse_color_code(chars: &mut Peekable<Chars>) -> Option<u8> {
     fn to_dec(ch: char) -> Option<u8> {
         ch.to_digit(10).map(|c| c as u8)
     }
@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments)]
+
 pub use objekt::clone_box;
 use time::Tm;
 
@@ -58,6 +60,73 @@ impl MsgSource {
     }
 }
 
+/// IRCv3 tags of a message that are relevant to the UIs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MsgTags<'a> {
+    /// Unique id of the message, given by the server (`msgid` tag).
+    pub msgid: Option<&'a str>,
+
+    /// `msgid` of the message this message is a reply to (`+draft/reply` tag).
+    pub reply_to: Option<&'a str>,
+
+    /// Whether the message is a NOTICE rather than a PRIVMSG. Not a tag, but passed with the tags
+    /// as UIs show notices the same as other messages.
+    pub is_notice: bool,
+
+    /// For messages shown in a server tab: nick of the user the message is from or sent to (e.g.
+    /// NickServ). Not a tag, loggers check their rules for the nick as the message is not in the
+    /// nick's tab.
+    pub peer: Option<&'a str>,
+}
+
+/// Channel events shown with `UI::add_chan_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChanEvent<'a> {
+    /// `nick` was kicked from the channel by `kicker`.
+    Kick {
+        kicker: &'a str,
+        nick: &'a str,
+        reason: Option<&'a str>,
+    },
+
+    /// `setter` changed modes of the channel or its users, e.g. `+o osa1`.
+    Mode { setter: &'a str, modes: &'a str },
+
+    /// `setter` set the current topic of the channel, at `set_at` when known (RPL_TOPICWHOTIME).
+    TopicSetBy { setter: &'a str, set_at: Option<Tm> },
+}
+
+/// Name of the raw protocol tab of a server. Raw tabs are server tabs with this name.
+pub fn raw_tab_name(serv: &str) -> String {
+    format!("{} (raw)", serv)
+}
+
+/// If the server tab name is a raw protocol tab, returns the server of the tab.
+pub fn raw_tab_serv(tab_name: &str) -> Option<&str> {
+    tab_name.strip_suffix(" (raw)")
+}
+
+/// Name of the server tab that shows log search results (`/grep`). Loggers don't log this tab,
+/// so search results don't show up in later searches.
+pub const LOG_SEARCH_TAB: &str = "log search";
+
+/// Name of the server tab that lists messages that mention the client's nick
+/// (`UI::add_mention`).
+pub const MENTIONS_TAB: &str = "mentions";
+
+/// How a message that mentions the client's nick is shown in the mentions tab.
+pub fn mention_msg(sender: &str, msg: &str, serv: &str, chan: &str) -> String {
+    format!("{} in {}:{}: {}", sender, serv, chan, msg)
+}
+
+/// A channel in a channel list (LIST command, 322 RPL_LIST replies).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChanListEntry {
+    pub chan: String,
+    pub n_users: usize,
+    pub topic: String,
+}
+
 // NOTE: Keep the variants sorted in increasing significance, to avoid updating
 // style with higher significance for a less significant style (e.g. updating
 // from `Highlight` to `NewMsg` in `set_tab_style`).
@@ -102,6 +171,10 @@ pub trait UI: objekt::Clone {
     /// Close a channel tab.
     fn close_chan_tab(&self, serv: &str, chan: &str);
 
+    /// Create a tab for private messages with a user in the given server. Does nothing if the tab
+    /// already exists.
+    fn new_user_tab(&self, serv: &str, nick: &str);
+
     /// Close a user tab.
     fn close_user_tab(&self, serv: &str, nick: &str);
 
@@ -132,6 +205,9 @@ pub trait UI: objekt::Clone {
     ///
     /// - is_action: `true` when this is a CTCP ACTION message.
     ///
+    /// - tags: IRCv3 tags of the message, used to identify the message and to link replies to
+    ///   their parent messages.
+    ///
     fn add_privmsg(
         &self,
         sender: &str,
@@ -140,8 +216,16 @@ pub trait UI: objekt::Clone {
         target: &MsgTarget,
         highlight: bool,
         is_action: bool,
+        tags: MsgTags,
     );
 
+    /// Show a reaction (IRCv3 `+draft/react`) to the message with the given `msgid`.
+    fn add_reaction(&self, sender: &str, reaction: &str, msgid: &str, ts: Tm, target: &MsgTarget);
+
+    /// Show a channel message that mentions the client's nick in the mentions tab
+    /// (`MENTIONS_TAB`). `serv` and `chan` are where the message was sent.
+    fn add_mention(&self, sender: &str, msg: &str, ts: Tm, serv: &str, chan: &str);
+
     /// Add a nick to the given tabs. When `ts` is not provided this does not show anything; just
     /// updated the channel nick list etc. Otherwise this shows a line like "foo joined channel".
     fn add_nick(&self, nick: &str, ts: Option<Tm>, target: &MsgTarget);
@@ -156,9 +240,19 @@ pub trait UI: objekt::Clone {
     /// Set topic of given tabs.
     fn set_topic(&self, topic: &str, ts: Tm, serv: &str, chan: &str);
 
+    /// Show a kick, mode change etc. in a channel tab.
+    fn add_chan_event(&self, event: ChanEvent, ts: Tm, serv: &str, chan: &str);
+
     /// Set style of the given tabs.
     fn set_tab_style(&self, style: TabStyle, target: &MsgTarget);
 
+    /// Show a channel list of the given server (reply to a LIST command).
+    fn show_chan_list(&self, serv: &str, chans: &[ChanListEntry]);
+
+    /// Show a message sent to or received from the server, as sent over the wire, in the raw
+    /// protocol tab of the server (see `raw_tab_name`). The tab is created if it doesn't exist.
+    fn add_raw_msg(&self, serv: &str, msg: &str, ts: Tm, outgoing: bool);
+
     /// Do we have a tab for the given user? This is useful for deciding where to show a PRIVMSG
     /// coming from server; e.g. messages from services sometimes shown in their own tabs,
     /// sometimes in the server tab.
@@ -197,6 +291,11 @@ impl<UI1: UI + Clone, UI2: UI + Clone> UI for CombinedUIs<UI1, UI2> {
         self.ui2.close_chan_tab(serv, chan);
     }
 
+    fn new_user_tab(&self, serv: &str, nick: &str) {
+        self.ui1.new_user_tab(serv, nick);
+        self.ui2.new_user_tab(serv, nick);
+    }
+
     fn close_user_tab(&self, serv: &str, nick: &str) {
         self.ui1.close_user_tab(serv, nick);
         self.ui2.close_user_tab(serv, nick);
@@ -240,11 +339,22 @@ impl<UI1: UI + Clone, UI2: UI + Clone> UI for CombinedUIs<UI1, UI2> {
         target: &MsgTarget,
         highlight: bool,
         is_action: bool,
+        tags: MsgTags,
     ) {
         self.ui1
-            .add_privmsg(sender, msg, ts, target, highlight, is_action);
+            .add_privmsg(sender, msg, ts, target, highlight, is_action, tags);
         self.ui2
-            .add_privmsg(sender, msg, ts, target, highlight, is_action);
+            .add_privmsg(sender, msg, ts, target, highlight, is_action, tags);
+    }
+
+    fn add_reaction(&self, sender: &str, reaction: &str, msgid: &str, ts: Tm, target: &MsgTarget) {
+        self.ui1.add_reaction(sender, reaction, msgid, ts, target);
+        self.ui2.add_reaction(sender, reaction, msgid, ts, target);
+    }
+
+    fn add_mention(&self, sender: &str, msg: &str, ts: Tm, serv: &str, chan: &str) {
+        self.ui1.add_mention(sender, msg, ts, serv, chan);
+        self.ui2.add_mention(sender, msg, ts, serv, chan);
     }
 
     fn add_nick(&self, nick: &str, ts: Option<Tm>, target: &MsgTarget) {
@@ -267,11 +377,26 @@ impl<UI1: UI + Clone, UI2: UI + Clone> UI for CombinedUIs<UI1, UI2> {
         self.ui2.set_topic(topic, ts, serv, chan);
     }
 
+    fn add_chan_event(&self, event: ChanEvent, ts: Tm, serv: &str, chan: &str) {
+        self.ui1.add_chan_event(event, ts, serv, chan);
+        self.ui2.add_chan_event(event, ts, serv, chan);
+    }
+
     fn set_tab_style(&self, style: TabStyle, target: &MsgTarget) {
         self.ui1.set_tab_style(style, target);
         self.ui2.set_tab_style(style, target);
     }
 
+    fn show_chan_list(&self, serv: &str, chans: &[ChanListEntry]) {
+        self.ui1.show_chan_list(serv, chans);
+        self.ui2.show_chan_list(serv, chans);
+    }
+
+    fn add_raw_msg(&self, serv: &str, msg: &str, ts: Tm, outgoing: bool) {
+        self.ui1.add_raw_msg(serv, msg, ts, outgoing);
+        self.ui2.add_raw_msg(serv, msg, ts, outgoing);
+    }
+
     fn user_tab_exists(&self, serv: &str, nick: &str) -> bool {
         // TODO weird
         self.ui1.user_tab_exists(serv, nick)
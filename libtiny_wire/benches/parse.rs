@@ -0,0 +1,58 @@
+//! Parsing benchmarks. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libtiny_wire::{parse_irc_msg, parse_irc_msg_ref};
+
+static MSGS: [&str; 6] = [
+    ":nick!~nick@unaffiliated/nick PRIVMSG #haskell :a message in a busy channel, \
+     long enough to be realistic\r\n",
+    "@time=2019-10-17T10:00:00.000Z;msgid=abcdef :nick!~nick@host PRIVMSG #rust :hi\r\n",
+    ":tiny!~tiny@192.168.0.1 JOIN #haskell\r\n",
+    ":tiny!~tiny@123.123.123.123 PART #haskell :bye\r\n",
+    ":barjavel.freenode.net 353 tiny = #haskell :tiny @op +voice nick1 nick2 nick3\r\n",
+    "PING :barjavel.freenode.net\r\n",
+];
+
+fn msgs_buf() -> Vec<u8> {
+    let mut buf = vec![];
+    for _ in 0..100 {
+        for msg in MSGS.iter() {
+            buf.extend_from_slice(msg.as_bytes());
+        }
+    }
+    buf
+}
+
+fn bench_parse_irc_msg(c: &mut Criterion) {
+    let msgs = msgs_buf();
+    c.bench_function("parse_irc_msg", |b| {
+        b.iter(|| {
+            let mut buf = msgs.clone();
+            let mut n_msgs = 0;
+            while let Some(msg) = parse_irc_msg(&mut buf) {
+                black_box(msg);
+                n_msgs += 1;
+            }
+            n_msgs
+        })
+    });
+}
+
+fn bench_parse_irc_msg_ref(c: &mut Criterion) {
+    let msgs = msgs_buf();
+    c.bench_function("parse_irc_msg_ref", |b| {
+        b.iter(|| {
+            let mut buf: &[u8] = &msgs;
+            let mut n_msgs = 0;
+            while let Some((len, msg)) = parse_irc_msg_ref(buf) {
+                black_box(msg);
+                buf = &buf[len..];
+                n_msgs += 1;
+            }
+            n_msgs
+        })
+    });
+}
+
+criterion_group!(benches, bench_parse_irc_msg, bench_parse_irc_msg_ref);
+criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libtiny_wire::parse_ctcp;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(msg) = std::str::from_utf8(data) {
+        match parse_ctcp(msg) {
+            None => assert!(!msg.starts_with('\x01')),
+            Some((_, args)) => assert!(args.len() < msg.len()),
+        }
+    }
+});
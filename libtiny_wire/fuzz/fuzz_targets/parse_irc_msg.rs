@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libtiny_wire::{parse_irc_msg, parse_irc_msg_ref};
+
+fuzz_target!(|data: &[u8]| {
+    // Allocation-free parser should agree with `parse_irc_msg` on valid UTF-8
+    let mut buf: &[u8] = data;
+    let mut msgs = vec![];
+    while let Some((len, msg)) = parse_irc_msg_ref(buf) {
+        if let Some(msg) = msg {
+            msgs.push(msg.to_msg());
+        }
+        buf = &buf[len..];
+    }
+
+    let mut buf = data.to_vec();
+    let mut msg_idx = 0;
+    while let Some(msg) = parse_irc_msg(&mut buf) {
+        if std::str::from_utf8(data).is_ok() {
+            assert_eq!(Some(&msg), msgs.get(msg_idx));
+            msg_idx += 1;
+        }
+
+        // Messages that can be encoded should be parsed back to the same message
+        if let Ok(encoded) = msg.encode() {
+            let mut encoded = encoded.into_bytes();
+            assert_eq!(parse_irc_msg(&mut encoded), Some(msg));
+            assert!(encoded.is_empty());
+        }
+    }
+});
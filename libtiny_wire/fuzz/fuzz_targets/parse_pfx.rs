@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use libtiny_wire::{drop_nick_prefix, parse_pfx, PfxRef};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pfx) = std::str::from_utf8(data) {
+        match parse_pfx(pfx) {
+            PfxRef::Server(serv) => assert_eq!(serv, pfx),
+            PfxRef::User { nick, user } => {
+                assert_eq!(format!("{}!{}", nick, user), pfx);
+                drop_nick_prefix(nick);
+            }
+        }
+    }
+});
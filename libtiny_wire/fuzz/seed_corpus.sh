@@ -0,0 +1,50 @@
+#!/bin/sh
+# Seed fuzzing corpora with the message examples in RFC 1459 and RFC 2812. The generated
+# corpora are committed in `corpus`, so this only needs to be run again when the examples below
+# change.
+#
+# Usage (in libtiny_wire/fuzz):
+#
+#     ./seed_corpus.sh
+#     cargo fuzz run parse_irc_msg
+#
+# Example lines look like this in the RFCs:
+#
+#     :WiZ!jto@tolsun.oulu.fi PART #playzone :I lost   ; comment
+
+set -e
+
+cd "$(dirname "$0")"
+RFCS=../../rfcs
+
+mkdir -p corpus/parse_irc_msg corpus/parse_pfx corpus/parse_ctcp
+
+awk '
+/^   Examples?:/ { in_ex = 1; next }
+/^[0-9]+\.[0-9.]* / { in_ex = 0 }
+in_ex && /^   [^ ]/ {
+    line = substr($0, 4)
+    sub(/ +;.*$/, "", line)
+    if (line ~ /^(:[^ ]+ |[A-Z]+( |$)|[0-9][0-9][0-9] )/) print line
+}' $RFCS/rfc1459.txt $RFCS/rfc2812.txt | sort -u > corpus/rfc_examples.txt
+
+n=0
+while IFS= read -r line; do
+    n=$((n + 1))
+    printf '%s\r\n' "$line" > corpus/parse_irc_msg/rfc_$n
+    case "$line" in
+        :*) printf '%s' "${line%% *}" | cut -c 2- | tr -d '\n' > corpus/parse_pfx/rfc_$n ;;
+    esac
+done < corpus/rfc_examples.txt
+rm corpus/rfc_examples.txt
+
+# Messages with tags and CTCPs are not in the RFCs
+printf '@msgid=abc;+draft/reply=x\\sy :nick!~user@host PRIVMSG #chan :hi\r\n' \
+    > corpus/parse_irc_msg/tags
+printf ':nick!~user@host PRIVMSG #chan :\001ACTION waves\001\r\n' \
+    > corpus/parse_irc_msg/action
+printf '\001ACTION waves\001' > corpus/parse_ctcp/action
+printf '\001VERSION\001' > corpus/parse_ctcp/version
+printf '\001PING 123' > corpus/parse_ctcp/unterminated
+
+echo "Seeded $n messages from the RFCs"
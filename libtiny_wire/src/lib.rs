@@ -2,78 +2,183 @@
 
 //! IRC wire protocol message parsers and generators. Incomplete; new messages are added as needed.
 
+mod numeric;
+mod wildcard;
+
+pub use numeric::{numeric, numeric_by_name, Numeric, NumericCategory};
+pub use wildcard::wildcard_match;
+
+use std::borrow::Cow;
+use std::fmt;
 use std::str;
 
-pub fn pass(pass: &str) -> String {
-    format!("PASS {}\r\n", pass)
+// Message generators validate the messages like `Msg::encode`, and fail when a message can't be
+// sent as a single IRC message, e.g. because an argument has a newline.
+
+pub fn pass(pass: &str) -> Result<String, EncodeError> {
+    encode_cmd("PASS", &[pass], false)
 }
 
-// FIXME: Option<String> because going from Option<String> to Option<&str> is too painful...
-pub fn quit(reason: Option<String>) -> String {
+pub fn quit(reason: Option<&str>) -> Result<String, EncodeError> {
     match reason {
-        None => "QUIT\r\n".to_string(),
-        Some(reason) => format!("QUIT :{}\r\n", reason),
+        None => encode_cmd("QUIT", &[], false),
+        Some(reason) => encode_cmd("QUIT", &[reason], true),
     }
 }
 
-pub fn user(hostname: &str, realname: &str) -> String {
-    format!("USER {} 8 * :{}\r\n", hostname, realname)
+pub fn user(hostname: &str, realname: &str) -> Result<String, EncodeError> {
+    encode_cmd("USER", &[hostname, "8", "*", realname], true)
+}
+
+pub fn nick(arg: &str) -> Result<String, EncodeError> {
+    encode_cmd("NICK", &[arg], false)
 }
 
-pub fn nick(arg: &str) -> String {
-    format!("NICK {}\r\n", arg)
+pub fn ping(arg: &str) -> Result<String, EncodeError> {
+    encode_cmd("PING", &[arg], false)
 }
 
-pub fn ping(arg: &str) -> String {
-    format!("PING {}\r\n", arg)
+pub fn pong(arg: &str) -> Result<String, EncodeError> {
+    encode_cmd("PONG", &[arg], false)
 }
 
-pub fn pong(arg: &str) -> String {
-    format!("PONG {}\r\n", arg)
+pub fn join(chans: &[&str]) -> Result<String, EncodeError> {
+    encode_cmd("JOIN", &[&chans.join(",")], false)
 }
 
-pub fn join(chans: &[&str]) -> String {
-    format!("JOIN {}\r\n", chans.join(","))
+/// LIST command. `args` are channel names or masks and, with ELIST, conditions like `>10`.
+pub fn list(args: &[&str]) -> Result<String, EncodeError> {
+    if args.is_empty() {
+        encode_cmd("LIST", &[], false)
+    } else {
+        encode_cmd("LIST", &[&args.join(",")], false)
+    }
 }
 
-pub fn part(channel: &str) -> String {
-    format!("PART {}\r\n", channel)
+pub fn part(channel: &str) -> Result<String, EncodeError> {
+    encode_cmd("PART", &[channel], false)
 }
 
-pub fn privmsg(msgtarget: &str, msg: &str) -> String {
-    // IRC messages need to be shorter than 512 bytes (see RFC 1459 or 2812). This should be dealt
-    // with at call sites as we can't show how we split messages into multiple messages in the UI
-    // at this point.
-    assert!(msgtarget.len() + msg.len() + 12 <= 512);
-    format!("PRIVMSG {} :{}\r\n", msgtarget, msg)
+/// MODE command, e.g. `mode("#tiny", "+ov", &["nick1", "nick2"])`. Requests current modes of
+/// the target when `modes` is empty.
+pub fn mode(target: &str, modes: &str, params: &[&str]) -> Result<String, EncodeError> {
+    let mut args = vec![target];
+    if !modes.is_empty() {
+        args.push(modes);
+        args.extend_from_slice(params);
+    }
+    encode_cmd("MODE", &args, false)
+}
+
+pub fn kick(chan: &str, nick: &str, reason: Option<&str>) -> Result<String, EncodeError> {
+    match reason {
+        None => encode_cmd("KICK", &[chan, nick], false),
+        Some(reason) => encode_cmd("KICK", &[chan, nick, reason], true),
+    }
+}
+
+/// TOPIC command. Sets the topic when `topic` is given, requests it otherwise.
+pub fn topic(chan: &str, topic: Option<&str>) -> Result<String, EncodeError> {
+    match topic {
+        None => encode_cmd("TOPIC", &[chan], false),
+        Some(topic) => encode_cmd("TOPIC", &[chan, topic], true),
+    }
 }
 
-pub fn action(msgtarget: &str, msg: &str) -> String {
-    assert!(msgtarget.len() + msg.len() + 21 <= 512); // See comments in `privmsg`
-    format!("PRIVMSG {} :\x01ACTION {}\x01\r\n", msgtarget, msg)
+pub fn invite(nick: &str, chan: &str) -> Result<String, EncodeError> {
+    encode_cmd("INVITE", &[nick, chan], false)
 }
 
-pub fn away(msg: Option<&str>) -> String {
+/// PRIVMSG command. Fails when the message is longer than `MAX_MSG_LEN`; long messages should be
+/// split at call sites (see `Client::split_privmsg`), so that the UI can show how they're split.
+pub fn privmsg(msgtarget: &str, msg: &str) -> Result<String, EncodeError> {
+    Msg {
+        tags: vec![],
+        pfx: None,
+        cmd: Cmd::PRIVMSG {
+            targets: vec![MsgTarget::parse(msgtarget)],
+            msg: msg.to_owned(),
+            is_notice: false,
+            ctcp: None,
+        },
+    }
+    .encode()
+}
+
+pub fn action(msgtarget: &str, msg: &str) -> Result<String, EncodeError> {
+    Msg {
+        tags: vec![],
+        pfx: None,
+        cmd: Cmd::PRIVMSG {
+            targets: vec![MsgTarget::parse(msgtarget)],
+            msg: msg.to_owned(),
+            is_notice: false,
+            ctcp: Some(CTCP::Action),
+        },
+    }
+    .encode()
+}
+
+/// A reply (IRCv3 `+draft/reply`) to the message with the given `msgid`.
+pub fn reply(msgtarget: &str, msgid: &str, msg: &str) -> Result<String, EncodeError> {
+    Msg {
+        tags: vec![Tag {
+            key: "+draft/reply".to_owned(),
+            value: msgid.to_owned(),
+        }],
+        pfx: None,
+        cmd: Cmd::PRIVMSG {
+            targets: vec![MsgTarget::parse(msgtarget)],
+            msg: msg.to_owned(),
+            is_notice: false,
+            ctcp: None,
+        },
+    }
+    .encode()
+}
+
+/// A reaction (IRCv3 `+draft/react`) to the message with the given `msgid`.
+pub fn react(msgtarget: &str, msgid: &str, reaction: &str) -> Result<String, EncodeError> {
+    Msg {
+        tags: vec![
+            Tag {
+                key: "+draft/react".to_owned(),
+                value: reaction.to_owned(),
+            },
+            Tag {
+                key: "+draft/reply".to_owned(),
+                value: msgid.to_owned(),
+            },
+        ],
+        pfx: None,
+        cmd: Cmd::TAGMSG {
+            target: MsgTarget::parse(msgtarget),
+        },
+    }
+    .encode()
+}
+
+pub fn away(msg: Option<&str>) -> Result<String, EncodeError> {
     match msg {
-        None => "AWAY\r\n".to_string(),
-        Some(msg) => format!("AWAY :{}\r\n", msg),
+        None => encode_cmd("AWAY", &[], false),
+        Some(msg) => encode_cmd("AWAY", &[msg], true),
     }
 }
 
 pub fn cap_ls() -> String {
-    "CAP LS\r\n".to_string()
+    "CAP LS\r\n".to_owned()
 }
 
-pub fn cap_req(cap_identifiers: &[&str]) -> String {
-    format!("CAP REQ :{}\r\n", cap_identifiers.join(" "))
+pub fn cap_req(cap_identifiers: &[&str]) -> Result<String, EncodeError> {
+    encode_cmd("CAP", &["REQ", &cap_identifiers.join(" ")], true)
 }
 
 pub fn cap_end() -> String {
-    "CAP END\r\n".to_string()
+    "CAP END\r\n".to_owned()
 }
 
-pub fn authenticate(msg: &str) -> String {
-    format!("AUTHENTICATE {}\r\n", msg)
+pub fn authenticate(msg: &str) -> Result<String, EncodeError> {
+    encode_cmd("AUTHENTICATE", &[msg], false)
 }
 
 /// Sender of a message
@@ -97,21 +202,52 @@ pub enum Pfx {
 }
 
 /// Target of a message
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum MsgTarget {
     Chan(String),
     User(String),
 }
 
+impl MsgTarget {
+    fn parse(target: &str) -> MsgTarget {
+        if target.chars().nth(0) == Some('#') {
+            MsgTarget::Chan(target.to_owned())
+        } else {
+            MsgTarget::User(target.to_owned())
+        }
+    }
+}
+
+/// An IRCv3 message tag. See https://ircv3.net/specs/extensions/message-tags
+///
+/// Tags without a value (`@key`) and tags with an empty value (`@key=`) are considered equivalent
+/// by the spec, so both are parsed to an empty `value`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tag {
+    /// Key of the tag, including the client-only prefix (`+`) and vendor prefix if exists, e.g.
+    /// `+draft/reply`.
+    pub key: String,
+    /// Unescaped value of the tag.
+    pub value: String,
+}
+
 /// An IRC message
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Msg {
+    pub tags: Vec<Tag>,
     pub pfx: Option<Pfx>,
     pub cmd: Cmd,
 }
 
+/// Find value of a tag.
+pub fn get_tag<'a>(tags: &'a [Tag], key: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|tag| tag.key == key)
+        .map(|tag| tag.value.as_str())
+}
+
 /// A client-to-client protocol message. See https://defs.ircdocs.horse/defs/ctcp.html
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum CTCP {
     Version,
     Action,
@@ -129,27 +265,36 @@ impl CTCP {
 }
 
 /// An IRC command or reply
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Cmd {
     /// A PRIVMSG or NOTICE. Check `is_notice` field.
     PRIVMSG {
-        // TODO: In theory this should be a list of targets, but in practice I've never
-        // encountered that case.
-        target: MsgTarget,
+        /// Targets of the message. Usually just one, but the protocol allows a comma-separated
+        /// list (`PRIVMSG #a,#b :msg`).
+        targets: Vec<MsgTarget>,
         msg: String,
         is_notice: bool,
         ctcp: Option<CTCP>,
     },
 
+    /// A TAGMSG: a message with only tags and no text, e.g. a reaction (`+draft/react`). See
+    /// https://ircv3.net/specs/extensions/message-tags#the-tagmsg-tag-only-message
+    TAGMSG {
+        target: MsgTarget,
+    },
+
     JOIN {
-        // TODO: Same as above, this should be a list ...
-        chan: String, // TODO: key field might be useful when joining restricted channels. In practice I've never
-                      // needed it.
+        /// Joined channels. Servers usually send one JOIN per channel, but bouncers may send a
+        /// comma-separated list.
+        chans: Vec<String>,
+        /// Keys of the channels, in the same order as `chans`. Usually empty; may be shorter than
+        /// `chans` when only some of the channels have keys.
+        keys: Vec<String>,
     },
 
     PART {
-        // TODO: List of channels
-        chan: String,
+        /// Channels left.
+        chans: Vec<String>,
         msg: Option<String>,
     },
 
@@ -206,6 +351,326 @@ pub enum Cmd {
     },
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// Encoding
+
+/// Max. length of a message, without tags, including the trailing "\r\n". From RFC 2812.
+pub const MAX_MSG_LEN: usize = 512;
+
+/// Max. length of the tags part of a message, including the leading '@' and the trailing space.
+/// See https://ircv3.net/specs/extensions/message-tags#size-limit
+pub const MAX_TAGS_LEN: usize = 8191;
+
+/// Errors when encoding a message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EncodeError {
+    /// Message (or the tags part of it) is longer than the protocol allows.
+    TooLong { len: usize, max: usize },
+
+    /// Message has a NUL, CR or LF character.
+    InvalidChar(char),
+
+    /// A command name, prefix, tag key or a parameter other than the last one is empty, has a
+    /// space or starts with a ':'.
+    InvalidToken(String),
+
+    /// Message has more than `MAX_PARAMS` parameters.
+    TooManyParams(usize),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::TooLong { len, max } => write!(
+                f,
+                "Message too long ({} bytes, max. allowed is {} bytes)",
+                len, max
+            ),
+            EncodeError::InvalidChar(c) => write!(f, "Message has an invalid character: {:?}", c),
+            EncodeError::InvalidToken(token) => write!(f, "Invalid token in message: {:?}", token),
+            EncodeError::TooManyParams(n) => write!(
+                f,
+                "Message has too many parameters ({}, max. allowed is {})",
+                n, MAX_PARAMS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+impl Cmd {
+    /// Command name and parameters as sent on the wire. The `bool` is `true` when the last
+    /// parameter is text (e.g. the message in a PRIVMSG) that should always be sent with a ':'
+    /// prefix.
+    fn to_wire(&self) -> (Cow<'_, str>, Vec<Cow<'_, str>>, bool) {
+        fn targets_str(targets: &[MsgTarget]) -> String {
+            let targets: Vec<&str> = targets
+                .iter()
+                .map(|target| match target {
+                    MsgTarget::Chan(chan) => chan.as_str(),
+                    MsgTarget::User(nick) => nick.as_str(),
+                })
+                .collect();
+            targets.join(",")
+        }
+
+        match self {
+            Cmd::PRIVMSG {
+                targets,
+                msg,
+                is_notice,
+                ctcp,
+            } => {
+                let cmd = if *is_notice { "NOTICE" } else { "PRIVMSG" };
+                let msg: Cow<str> = match ctcp {
+                    None => msg.into(),
+                    Some(ctcp) => {
+                        let ctcp = match ctcp {
+                            CTCP::Version => "VERSION",
+                            CTCP::Action => "ACTION",
+                            CTCP::Other(ctcp) => ctcp,
+                        };
+                        if msg.is_empty() {
+                            format!("\x01{}\x01", ctcp).into()
+                        } else {
+                            format!("\x01{} {}\x01", ctcp, msg).into()
+                        }
+                    }
+                };
+                (cmd.into(), vec![targets_str(targets).into(), msg], true)
+            }
+            Cmd::TAGMSG { target } => (
+                "TAGMSG".into(),
+                vec![targets_str(std::slice::from_ref(target)).into()],
+                false,
+            ),
+            Cmd::JOIN { chans, keys } => {
+                let mut params: Vec<Cow<str>> = vec![chans.join(",").into()];
+                if !keys.is_empty() {
+                    params.push(keys.join(",").into());
+                }
+                ("JOIN".into(), params, false)
+            }
+            Cmd::PART { chans, msg } => {
+                let mut params: Vec<Cow<str>> = vec![chans.join(",").into()];
+                if let Some(msg) = msg {
+                    params.push(msg.into());
+                }
+                let trailing = msg.is_some();
+                ("PART".into(), params, trailing)
+            }
+            Cmd::QUIT { msg, .. } => match msg {
+                None => ("QUIT".into(), vec![], false),
+                Some(msg) => ("QUIT".into(), vec![msg.into()], true),
+            },
+            Cmd::NICK { nick, .. } => ("NICK".into(), vec![nick.into()], false),
+            Cmd::PING { server } => ("PING".into(), vec![server.into()], false),
+            Cmd::PONG { server } => ("PONG".into(), vec![server.into()], false),
+            Cmd::ERROR { msg } => ("ERROR".into(), vec![msg.into()], true),
+            Cmd::TOPIC { chan, topic } => ("TOPIC".into(), vec![chan.into(), topic.into()], true),
+            Cmd::CAP {
+                client,
+                subcommand,
+                params,
+            } => (
+                "CAP".into(),
+                vec![client.into(), subcommand.into(), params.join(" ").into()],
+                true,
+            ),
+            Cmd::AUTHENTICATE { param } => ("AUTHENTICATE".into(), vec![param.into()], false),
+            Cmd::Other { cmd, params } => (
+                cmd.into(),
+                params.iter().map(|param| param.into()).collect(),
+                false,
+            ),
+            Cmd::Reply { num, params } => (
+                format!("{:03}", num).into(),
+                params.iter().map(|param| param.into()).collect(),
+                false,
+            ),
+        }
+    }
+}
+
+/// Write a message, without the trailing "\r\n". Does not validate the message.
+fn write_msg(
+    f: &mut dyn fmt::Write,
+    tags: &[Tag],
+    pfx: &Option<Pfx>,
+    cmd: &str,
+    params: &[Cow<str>],
+    trailing: bool,
+) -> fmt::Result {
+    if !tags.is_empty() {
+        f.write_char('@')?;
+        for (tag_idx, tag) in tags.iter().enumerate() {
+            if tag_idx != 0 {
+                f.write_char(';')?;
+            }
+            f.write_str(&tag.key)?;
+            if !tag.value.is_empty() {
+                write!(f, "={}", escape_tag_value(&tag.value))?;
+            }
+        }
+        f.write_char(' ')?;
+    }
+
+    match pfx {
+        None => {}
+        Some(Pfx::Server(serv)) => write!(f, ":{} ", serv)?,
+        Some(Pfx::User { nick, user }) => write!(f, ":{}!{} ", nick, user)?,
+    }
+
+    f.write_str(cmd)?;
+
+    for (param_idx, param) in params.iter().enumerate() {
+        f.write_char(' ')?;
+        if param_idx == params.len() - 1
+            && (trailing || param.is_empty() || param.contains(' ') || param.starts_with(':'))
+        {
+            f.write_char(':')?;
+        }
+        f.write_str(param)?;
+    }
+
+    Ok(())
+}
+
+/// Shows the message in wire format, without the trailing "\r\n". Use `Msg::encode` to validate
+/// the message.
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (cmd, params, trailing) = self.cmd.to_wire();
+        write_msg(f, &self.tags, &self.pfx, &cmd, &params, trailing)
+    }
+}
+
+impl Msg {
+    /// Encode the message in wire format, with the trailing "\r\n". Fails when the message is
+    /// too long, or can't be parsed back by the server as the same message.
+    pub fn encode(&self) -> Result<String, EncodeError> {
+        let (cmd, params, trailing) = self.cmd.to_wire();
+        if let Cmd::Reply { num, .. } = self.cmd {
+            if num > 999 {
+                return Err(EncodeError::InvalidToken(cmd.into_owned()));
+            }
+        }
+        encode_msg(&self.tags, &self.pfx, &cmd, &params, trailing)
+    }
+}
+
+/// Encode a command without tags and prefix, validating it like `Msg::encode`. When `trailing` is
+/// `true` the last parameter is sent with a ':' prefix, otherwise it has to be a single word like
+/// the other parameters.
+fn encode_cmd(cmd: &str, params: &[&str], trailing: bool) -> Result<String, EncodeError> {
+    let params: Vec<Cow<str>> = params.iter().map(|param| Cow::Borrowed(*param)).collect();
+    let msg = encode_msg(&[], &None, cmd, &params, trailing)?;
+    if let Some(last) = params.last() {
+        if !trailing && (last.is_empty() || last.contains(' ') || last.starts_with(':')) {
+            return Err(EncodeError::InvalidToken(last.to_string()));
+        }
+    }
+    Ok(msg)
+}
+
+/// Validate and write a message, with the trailing "\r\n". See `Msg::encode`.
+fn encode_msg(
+    tags: &[Tag],
+    pfx: &Option<Pfx>,
+    cmd: &str,
+    params: &[Cow<str>],
+    trailing: bool,
+) -> Result<String, EncodeError> {
+    fn check_chars(s: &str) -> Result<(), EncodeError> {
+        match s.chars().find(|c| *c == '\0' || *c == '\r' || *c == '\n') {
+            None => Ok(()),
+            Some(c) => Err(EncodeError::InvalidChar(c)),
+        }
+    }
+
+    fn check_token(s: &str) -> Result<(), EncodeError> {
+        check_chars(s)?;
+        if s.is_empty() || s.contains(' ') || s.starts_with(':') {
+            Err(EncodeError::InvalidToken(s.to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+
+    for tag in tags {
+        check_token(&tag.key)?;
+        if tag.key.contains('=') || tag.key.contains(';') {
+            return Err(EncodeError::InvalidToken(tag.key.clone()));
+        }
+        // CR and LF are escaped in tag values
+        if tag.value.contains('\0') {
+            return Err(EncodeError::InvalidChar('\0'));
+        }
+    }
+
+    match pfx {
+        None => {}
+        Some(Pfx::Server(serv)) => {
+            check_token(serv)?;
+            // Would be parsed as a user prefix
+            if serv.contains('!') {
+                return Err(EncodeError::InvalidToken(serv.clone()));
+            }
+        }
+        Some(Pfx::User { nick, user }) => {
+            check_token(nick)?;
+            check_token(user)?;
+            if nick.contains('!') {
+                return Err(EncodeError::InvalidToken(nick.clone()));
+            }
+        }
+    }
+
+    check_token(cmd)?;
+    if cmd.starts_with('@') {
+        return Err(EncodeError::InvalidToken(cmd.to_owned()));
+    }
+
+    if params.len() > MAX_PARAMS {
+        return Err(EncodeError::TooManyParams(params.len()));
+    }
+    for (param_idx, param) in params.iter().enumerate() {
+        if param_idx == params.len() - 1 {
+            check_chars(param)?;
+        } else {
+            check_token(param)?;
+        }
+    }
+
+    let mut ret = String::new();
+    write_msg(&mut ret, tags, pfx, cmd, params, trailing).unwrap();
+
+    let tags_len = match ret.find(' ') {
+        Some(ws_idx) if !tags.is_empty() => ws_idx + 1,
+        _ => 0,
+    };
+    if tags_len > MAX_TAGS_LEN {
+        return Err(EncodeError::TooLong {
+            len: tags_len,
+            max: MAX_TAGS_LEN,
+        });
+    }
+    let msg_len = ret.len() - tags_len + 2;
+    if msg_len > MAX_MSG_LEN {
+        return Err(EncodeError::TooLong {
+            len: msg_len,
+            max: MAX_MSG_LEN,
+        });
+    }
+
+    ret.push_str("\r\n");
+    Ok(ret)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Parsing
+
 /// An intermediate type used during parsing.
 enum MsgType<'a> {
     Cmd(&'a str),
@@ -214,101 +679,183 @@ enum MsgType<'a> {
 
 static CRLF: [u8; 2] = [b'\r', b'\n'];
 
-/// Try to read an IRC message off a buffer. Drops the message when parsing is successful.
-/// Otherwise the buffer is left unchanged.
-pub fn parse_irc_msg(buf: &mut Vec<u8>) -> Option<Msg> {
-    // find "\r\n" separator. `IntoSearcher` implementation for slice needs `str` (why??) so
-    // using this hacky method instead.
-    let crlf_idx = {
-        match buf.windows(2).position(|sub| sub == CRLF) {
-            None => return None,
-            Some(i) => i,
+/// Max. number of parameters in a message. From RFC 2812: "Parameters: ... up to 15". After the
+/// 14th parameter the rest of the message is the last parameter, even without a ':'.
+pub const MAX_PARAMS: usize = 15;
+
+/// Sender of a message, borrowed version of `Pfx`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PfxRef<'a> {
+    Server(&'a str),
+
+    /// <nick>!<user>@<host>
+    User {
+        nick: &'a str,
+        /// user@host
+        user: &'a str,
+    },
+}
+
+impl<'a> PfxRef<'a> {
+    pub fn to_pfx(self) -> Pfx {
+        match self {
+            PfxRef::Server(serv) => Pfx::Server(serv.to_owned()),
+            PfxRef::User { nick, user } => Pfx::User {
+                nick: nick.to_owned(),
+                user: user.to_owned(),
+            },
         }
-    };
+    }
+}
 
-    let ret = {
-        let mut slice: &[u8] = &buf[0..crlf_idx];
-
-        let pfx: Option<Pfx> = {
-            if slice[0] == b':' {
-                // parse prefix
-                let ws_idx = find_byte(slice, b' ').unwrap();
-                let (mut pfx, slice_) = slice.split_at(ws_idx);
-                // drop the : from pfx
-                pfx = &pfx[1..];
-                slice = &slice_[1..]; // drop the space
-                Some(parse_pfx(pfx))
-            } else {
-                None
-            }
+/// An IRCv3 message tag, borrowed version of `Tag`. `value` is not unescaped; use
+/// `unescaped_value` for that.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TagRef<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+}
+
+impl<'a> TagRef<'a> {
+    pub fn unescaped_value(self) -> String {
+        unescape_tag_value(self.value)
+    }
+
+    pub fn to_tag(self) -> Tag {
+        Tag {
+            key: self.key.to_owned(),
+            value: self.unescaped_value(),
+        }
+    }
+}
+
+/// An IRC message borrowing from the buffer it was parsed from. Parsing a `MsgRef` does not
+/// allocate. See `parse_irc_msg_ref` and `MsgRef::parse`.
+#[derive(Debug, Clone, Copy)]
+pub struct MsgRef<'a> {
+    /// Tags part of the message, without the leading '@'. Empty when the message doesn't have
+    /// tags. Use `tags` method to iterate the tags.
+    pub tags: &'a str,
+    pub pfx: Option<PfxRef<'a>>,
+    /// Command name (e.g. `PRIVMSG`) or numeric reply (e.g. `001`).
+    pub cmd: &'a str,
+    params: [&'a str; MAX_PARAMS],
+    n_params: usize,
+}
+
+impl<'a> MsgRef<'a> {
+    /// Parse a message, without the trailing "\r\n". Returns `None` if the message is malformed
+    /// (e.g. empty, or without a command).
+    pub fn parse(mut line: &'a str) -> Option<MsgRef<'a>> {
+        let tags = if line.starts_with('@') {
+            let ws_idx = line.find(' ')?;
+            let tags = &line[1..ws_idx];
+            line = line[ws_idx + 1..].trim_start_matches(' ');
+            tags
+        } else {
+            ""
         };
 
-        let msg_ty: MsgType = {
-            let ws_idx = find_byte(slice, b' ').unwrap();
-            let (cmd, slice_) = slice.split_at(ws_idx);
-            slice = &slice_[1..]; // drop the space
-            match parse_reply_num(cmd) {
-                None => MsgType::Cmd(unsafe {
-                    // Cmd strings are added by the server and they're always ASCII strings, so
-                    // this is safe and O(1).
-                    str::from_utf8_unchecked(cmd)
-                }),
-                Some(num) => MsgType::Num(num),
-            }
+        let pfx = if line.starts_with(':') {
+            let ws_idx = line.find(' ')?;
+            let pfx = parse_pfx(&line[1..ws_idx]);
+            line = line[ws_idx + 1..].trim_start_matches(' ');
+            Some(pfx)
+        } else {
+            None
+        };
+
+        let (cmd, rest) = match line.find(' ') {
+            None => (line, ""),
+            Some(ws_idx) => (&line[..ws_idx], &line[ws_idx + 1..]),
         };
+        if cmd.is_empty() {
+            return None;
+        }
+
+        let mut params = [""; MAX_PARAMS];
+        let n_params = parse_params(rest, &mut params);
+
+        Some(MsgRef {
+            tags,
+            pfx,
+            cmd,
+            params,
+            n_params,
+        })
+    }
+
+    pub fn params(&self) -> &[&'a str] {
+        &self.params[0..self.n_params]
+    }
+
+    /// Iterate tags of the message.
+    pub fn tags(&self) -> impl Iterator<Item = TagRef<'a>> {
+        self.tags
+            .split(';')
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| match tag.find('=') {
+                None => TagRef {
+                    key: tag,
+                    value: "",
+                },
+                Some(eq_idx) => TagRef {
+                    key: &tag[0..eq_idx],
+                    value: &tag[eq_idx + 1..],
+                },
+            })
+    }
+
+    /// Find (escaped) value of a tag.
+    pub fn get_tag(&self, key: &str) -> Option<&'a str> {
+        self.tags().find(|tag| tag.key == key).map(|tag| tag.value)
+    }
+
+    /// Build an owned `Msg`.
+    pub fn to_msg(self) -> Msg {
+        Msg {
+            tags: self.tags().map(|tag| tag.to_tag()).collect(),
+            pfx: self.pfx.map(|pfx| pfx.to_pfx()),
+            cmd: self.to_cmd(),
+        }
+    }
 
-        let params: Vec<&str> = parse_params(unsafe { str::from_utf8_unchecked(slice) });
-        let cmd = match msg_ty {
+    fn to_cmd(self) -> Cmd {
+        let params = &self.params[0..self.n_params];
+        let msg_ty = match parse_reply_num(self.cmd.as_bytes()) {
+            None => MsgType::Cmd(self.cmd),
+            Some(num) => MsgType::Num(num),
+        };
+        match msg_ty {
             MsgType::Cmd("PRIVMSG") | MsgType::Cmd("NOTICE") if params.len() == 2 => {
                 let is_notice = if let MsgType::Cmd("NOTICE") = msg_ty {
                     true
                 } else {
                     false
                 };
-                let target = params[0];
-                let mut msg = params[1];
-                let target = if target.chars().nth(0) == Some('#') {
-                    MsgTarget::Chan(target.to_owned())
-                } else {
-                    MsgTarget::User(target.to_owned())
+                let targets = split_list(params[0]).map(MsgTarget::parse).collect();
+                let (ctcp, msg) = match parse_ctcp(params[1]) {
+                    None => (None, params[1]),
+                    Some((ctcp, msg)) => (Some(ctcp), msg),
                 };
 
-                let mut ctcp: Option<CTCP> = None;
-                if !msg.is_empty() && msg.as_bytes()[0] == 0x01 {
-                    // Drop 0x01
-                    msg = &msg[1..];
-                    // Parse message type
-                    for (byte_idx, byte) in msg.as_bytes().iter().enumerate() {
-                        if *byte == 0x01 {
-                            let ctcp_type = &msg[0..byte_idx];
-                            ctcp = Some(CTCP::parse(ctcp_type));
-                            msg = &msg[byte_idx + 1..];
-                            break;
-                        } else if *byte == b' ' {
-                            let ctcp_type = &msg[0..byte_idx];
-                            ctcp = Some(CTCP::parse(ctcp_type));
-                            msg = &msg[byte_idx + 1..];
-                            if !msg.is_empty() && msg.as_bytes()[msg.len() - 1] == 0x01 {
-                                msg = &msg[..msg.len() - 1];
-                            }
-                            break;
-                        }
-                    }
-                }
-
                 Cmd::PRIVMSG {
-                    target,
+                    targets,
                     msg: msg.to_owned(),
                     is_notice,
                     ctcp,
                 }
             }
-            MsgType::Cmd("JOIN") if params.len() == 1 => {
-                let chan = params[0];
-                Cmd::JOIN {
-                    chan: chan.to_owned(),
-                }
-            }
+            MsgType::Cmd("TAGMSG") if params.len() == 1 => Cmd::TAGMSG {
+                target: MsgTarget::parse(params[0]),
+            },
+            MsgType::Cmd("JOIN") if params.len() == 1 || params.len() == 2 => Cmd::JOIN {
+                chans: split_list(params[0]).map(str::to_owned).collect(),
+                keys: params
+                    .get(1)
+                    .map(|keys| keys.split(',').map(str::to_owned).collect())
+                    .unwrap_or_default(),
+            },
             MsgType::Cmd("PART") if params.len() == 1 || params.len() == 2 => {
                 let mb_msg = if params.len() == 2 {
                     Some(params[1].to_owned())
@@ -316,12 +863,12 @@ pub fn parse_irc_msg(buf: &mut Vec<u8>) -> Option<Msg> {
                     None
                 };
                 Cmd::PART {
-                    chan: params[0].to_owned(),
+                    chans: split_list(params[0]).map(str::to_owned).collect(),
                     msg: mb_msg,
                 }
             }
             MsgType::Cmd("QUIT") if params.is_empty() || params.len() == 1 => {
-                let mb_msg = params.get(1).map(|s| (*s).to_owned());
+                let mb_msg = params.first().map(|s| (*s).to_owned());
 
                 Cmd::QUIT {
                     msg: mb_msg,
@@ -358,31 +905,136 @@ pub fn parse_irc_msg(buf: &mut Vec<u8>) -> Option<Msg> {
             },
             MsgType::Num(n) => Cmd::Reply {
                 num: n,
-                params: params.into_iter().map(|s| s.to_owned()).collect(),
+                params: params.iter().map(|s| (*s).to_owned()).collect(),
             },
             MsgType::Cmd(cmd) => Cmd::Other {
                 cmd: cmd.to_owned(),
-                params: params.into_iter().map(|s| s.to_owned()).collect(),
+                params: params.iter().map(|s| (*s).to_owned()).collect(),
             },
+        }
+    }
+}
+
+/// Try to parse an IRC message from the beginning of a buffer, without allocating. Returns `None`
+/// when the buffer doesn't have a complete message yet. Otherwise returns the length of the
+/// message in bytes, including the trailing "\r\n", which should be dropped from the buffer
+/// before parsing the next message. The message is `None` when it's not valid UTF-8 or
+/// malformed.
+pub fn parse_irc_msg_ref(buf: &[u8]) -> Option<(usize, Option<MsgRef<'_>>)> {
+    let crlf_idx = find_crlf(buf)?;
+    let msg = str::from_utf8(&buf[0..crlf_idx])
+        .ok()
+        .and_then(MsgRef::parse);
+    Some((crlf_idx + 2, msg))
+}
+
+/// Try to read an IRC message off a buffer. Drops the message when parsing is successful.
+/// Otherwise the buffer is left unchanged. Malformed messages are dropped.
+pub fn parse_irc_msg(buf: &mut Vec<u8>) -> Option<Msg> {
+    // Invalid UTF-8 sequences are replaced with U+FFFD. This only allocates when the message is
+    // not valid UTF-8.
+    parse_irc_msg_with(buf, String::from_utf8_lossy)
+}
+
+/// Like `parse_irc_msg`, but uses the given function to decode messages (without the trailing
+/// "\r\n") to UTF-8. Useful for servers and channels that use legacy encodings.
+pub fn parse_irc_msg_with<F>(buf: &mut Vec<u8>, decode: F) -> Option<Msg>
+where
+    F: Fn(&[u8]) -> Cow<str>,
+{
+    loop {
+        let crlf_idx = find_crlf(buf)?;
+        let msg = {
+            let line = decode(&buf[0..crlf_idx]);
+            MsgRef::parse(&line).map(|msg| msg.to_msg())
         };
+        buf.drain(0..crlf_idx + 2);
+        if msg.is_some() {
+            return msg;
+        }
+    }
+}
 
-        Msg { pfx, cmd }
-    };
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    // `IntoSearcher` implementation for slice needs `str` (why??) so using this hacky method
+    // instead.
+    buf.windows(2).position(|sub| sub == CRLF)
+}
+
+/// Split a comma-separated list of targets (channels or nicks). Empty items are dropped.
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').filter(|item| !item.is_empty())
+}
+
+/// Unescape a tag value. See https://ircv3.net/specs/extensions/message-tags#escaping-values
+fn unescape_tag_value(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(char) = chars.next() {
+        if char == '\\' {
+            match chars.next() {
+                Some(':') => ret.push(';'),
+                Some('s') => ret.push(' '),
+                Some('r') => ret.push('\r'),
+                Some('n') => ret.push('\n'),
+                Some(char) => ret.push(char),
+                // A trailing backslash is dropped
+                None => {}
+            }
+        } else {
+            ret.push(char);
+        }
+    }
+    ret
+}
 
-    buf.drain(0..crlf_idx + 2);
-    Some(ret)
+/// Escape a tag value. Inverse of `unescape_tag_value`.
+fn escape_tag_value(value: &str) -> String {
+    let mut ret = String::with_capacity(value.len());
+    for char in value.chars() {
+        match char {
+            ';' => ret.push_str("\\:"),
+            ' ' => ret.push_str("\\s"),
+            '\\' => ret.push_str("\\\\"),
+            '\r' => ret.push_str("\\r"),
+            '\n' => ret.push_str("\\n"),
+            _ => ret.push(char),
+        }
+    }
+    ret
 }
 
-fn parse_pfx(pfx: &[u8]) -> Pfx {
-    match find_byte(pfx, b'!') {
-        None => Pfx::Server(unsafe { str::from_utf8_unchecked(pfx).to_owned() }),
-        Some(idx) => Pfx::User {
-            nick: unsafe { str::from_utf8_unchecked(&pfx[0..idx]) }.to_owned(),
-            user: unsafe { str::from_utf8_unchecked(&pfx[idx + 1..]) }.to_owned(),
+/// Parse a message prefix, without the leading ':'.
+pub fn parse_pfx(pfx: &str) -> PfxRef<'_> {
+    match pfx.find('!') {
+        None => PfxRef::Server(pfx),
+        Some(idx) => PfxRef::User {
+            nick: &pfx[0..idx],
+            user: &pfx[idx + 1..],
         },
     }
 }
 
+/// Parse a CTCP message (`\x01TYPE args\x01`). Returns `None` if the message is not a CTCP
+/// message. Otherwise returns the CTCP type and the arguments. The closing `\x01` is optional, as
+/// some clients omit it.
+pub fn parse_ctcp(msg: &str) -> Option<(CTCP, &str)> {
+    let msg = msg.strip_prefix('\x01')?;
+    match msg.find(&['\x01', ' '][..]) {
+        None => Some((CTCP::parse(msg), "")),
+        Some(idx) if msg.as_bytes()[idx] == 0x01 => {
+            Some((CTCP::parse(&msg[..idx]), &msg[idx + 1..]))
+        }
+        Some(idx) => {
+            let args = &msg[idx + 1..];
+            Some((
+                CTCP::parse(&msg[..idx]),
+                args.strip_suffix('\x01').unwrap_or(args),
+            ))
+        }
+    }
+}
+
 fn parse_reply_num(bs: &[u8]) -> Option<u16> {
     fn is_num_ascii(b: u8) -> bool {
         b >= b'0' && b <= b'9'
@@ -401,27 +1053,29 @@ fn parse_reply_num(bs: &[u8]) -> Option<u16> {
     None
 }
 
-fn parse_params(chrs: &str) -> Vec<&str> {
-    debug_assert_ne!(chrs.chars().nth(0), Some(' '));
-
-    let mut ret: Vec<&str> = Vec::new();
-
-    let mut slice_begins = 0;
-    for (char_idx, char) in chrs.char_indices() {
-        if char == ':' {
-            ret.push(unsafe { chrs.get_unchecked(char_idx + 1..chrs.len()) });
-            return ret;
-        } else if char == ' ' {
-            ret.push(unsafe { chrs.get_unchecked(slice_begins..char_idx) });
-            slice_begins = char_idx + 1;
+/// Parse parameters into the given array. Returns number of parameters. Parameters may be
+/// separated by multiple spaces; middle parameters are never empty.
+fn parse_params<'a>(chrs: &'a str, params: &mut [&'a str; MAX_PARAMS]) -> usize {
+    let mut n_params = 0;
+    let mut rest = chrs.trim_start_matches(' ');
+    while !rest.is_empty() {
+        if rest.starts_with(':') || n_params == MAX_PARAMS - 1 {
+            params[n_params] = rest.strip_prefix(':').unwrap_or(rest);
+            return n_params + 1;
+        }
+        match rest.find(' ') {
+            None => {
+                params[n_params] = rest;
+                return n_params + 1;
+            }
+            Some(ws_idx) => {
+                params[n_params] = &rest[..ws_idx];
+                n_params += 1;
+                rest = rest[ws_idx + 1..].trim_start_matches(' ');
+            }
         }
     }
-
-    if slice_begins != chrs.len() {
-        ret.push(unsafe { chrs.get_unchecked(slice_begins..chrs.len()) });
-    }
-
-    ret
+    n_params
 }
 
 pub fn find_byte(buf: &[u8], byte0: u8) -> Option<usize> {
@@ -441,10 +1095,9 @@ pub fn find_byte(buf: &[u8], byte0: u8) -> Option<usize> {
 pub fn drop_nick_prefix(nick: &str) -> &str {
     static PREFIXES: [char; 5] = ['~', '&', '@', '%', '+'];
 
-    if PREFIXES.contains(&nick.chars().nth(0).unwrap()) {
-        &nick[1..]
-    } else {
-        nick
+    match nick.chars().next() {
+        Some(c) if PREFIXES.contains(&c) => &nick[1..],
+        _ => nick,
     }
 }
 
@@ -453,13 +1106,94 @@ mod tests {
     use super::*;
     use std::io::Write;
 
+    fn params(chrs: &str) -> Vec<&str> {
+        let mut params = [""; MAX_PARAMS];
+        let n_params = parse_params(chrs, &mut params);
+        params[0..n_params].to_vec()
+    }
+
     #[test]
     fn test_parse_params() {
-        assert_eq!(parse_params("p1 p2 p3"), vec!["p1", "p2", "p3"]);
+        assert_eq!(params("p1 p2 p3"), vec!["p1", "p2", "p3"]);
         let v: Vec<&str> = vec![];
-        assert_eq!(parse_params(""), v);
-        assert_eq!(parse_params(":foo bar baz "), vec!["foo bar baz "]);
-        assert_eq!(parse_params(":"), vec![""]);
+        assert_eq!(params(""), v);
+        assert_eq!(params(":foo bar baz "), vec!["foo bar baz "]);
+        assert_eq!(params(":"), vec![""]);
+        assert_eq!(params("a:b :c d"), vec!["a:b", "c d"]);
+        // Rest of the message after the 14th parameter is the last parameter
+        assert_eq!(
+            params("1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16"),
+            vec![
+                "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15 16"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_msg_ref_parsing() {
+        let buf = b"@msgid=a\\\\b;+draft/reply=x :nick!~user@host PRIVMSG #chan :hi there\r\nPING";
+        let (len, msg) = parse_irc_msg_ref(buf).unwrap();
+        assert_eq!(&buf[len..], b"PING");
+        let msg = msg.unwrap();
+        assert_eq!(
+            msg.pfx,
+            Some(PfxRef::User {
+                nick: "nick",
+                user: "~user@host"
+            })
+        );
+        assert_eq!(msg.cmd, "PRIVMSG");
+        assert_eq!(msg.params(), &["#chan", "hi there"]);
+        assert_eq!(msg.get_tag("msgid"), Some("a\\\\b"));
+        assert_eq!(
+            msg.tags()
+                .map(|tag| tag.unescaped_value())
+                .collect::<Vec<_>>(),
+            vec!["a\\b", "x"]
+        );
+
+        // Incomplete message
+        assert!(parse_irc_msg_ref(&buf[len..]).is_none());
+
+        // Invalid UTF-8 and malformed messages
+        assert_eq!(
+            parse_irc_msg_ref(b"\xffPING x\r\n").map(|(l, m)| (l, m.is_none())),
+            Some((9, true))
+        );
+        assert!(MsgRef::parse("").is_none());
+        assert!(MsgRef::parse(":pfx").is_none());
+        assert!(MsgRef::parse("@tags").is_none());
+    }
+
+    #[test]
+    fn test_malformed_msg_dropped() {
+        let mut buf = vec![];
+        write!(&mut buf, ":pfx\r\n\r\nPING :x\r\n").unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf),
+            Some(Msg {
+                tags: vec![],
+                pfx: None,
+                cmd: Cmd::PING {
+                    server: "x".to_owned()
+                },
+            })
+        );
+        assert_eq!(buf.len(), 0);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut buf = b":a!b@c PRIVMSG #chan :caf\xe9\r\n".to_vec();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().cmd,
+            Cmd::PRIVMSG {
+                targets: vec![MsgTarget::Chan("#chan".to_owned())],
+                msg: "caf\u{FFFD}".to_owned(),
+                is_notice: false,
+                ctcp: None,
+            }
+        );
     }
 
     #[test]
@@ -473,12 +1207,13 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: Some(Pfx::User {
                     nick: "nick".to_owned(),
                     user: "~nick@unaffiliated/nick".to_owned(),
                 }),
                 cmd: Cmd::PRIVMSG {
-                    target: MsgTarget::User("tiny".to_owned()),
+                    targets: vec![MsgTarget::User("tiny".to_owned())],
                     msg: "a b c".to_owned(),
                     is_notice: false,
                     ctcp: None,
@@ -499,9 +1234,10 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: Some(Pfx::Server("barjavel.freenode.net".to_owned())),
                 cmd: Cmd::PRIVMSG {
-                    target: MsgTarget::User("*".to_owned()),
+                    targets: vec![MsgTarget::User("*".to_owned())],
                     msg: "*** Looking up your hostname...".to_owned(),
                     is_notice: true,
                     ctcp: None,
@@ -553,12 +1289,13 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: Some(Pfx::User {
                     nick: "tiny".to_owned(),
                     user: "~tiny@123.123.123.123".to_owned(),
                 }),
                 cmd: Cmd::PART {
-                    chan: "#haskell".to_owned(),
+                    chans: vec!["#haskell".to_owned()],
                     msg: None,
                 },
             })
@@ -572,18 +1309,55 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: Some(Pfx::User {
                     nick: "tiny".to_owned(),
                     user: "~tiny@192.168.0.1".to_owned(),
                 }),
                 cmd: Cmd::JOIN {
-                    chan: "#haskell".to_owned(),
+                    chans: vec!["#haskell".to_owned()],
+                    keys: vec![],
                 },
             })
         );
         assert_eq!(buf.len(), 0);
     }
 
+    #[test]
+    fn test_target_list_parsing() {
+        let mut buf = vec![];
+        write!(&mut buf, ":a!b@c JOIN #foo,#bar,,#baz k1,k2\r\n").unwrap();
+        write!(&mut buf, ":a!b@c PART #foo,#bar :bye\r\n").unwrap();
+        write!(&mut buf, ":a!b@c PRIVMSG #foo,tiny :hi\r\n").unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().cmd,
+            Cmd::JOIN {
+                chans: vec!["#foo".to_owned(), "#bar".to_owned(), "#baz".to_owned()],
+                keys: vec!["k1".to_owned(), "k2".to_owned()],
+            }
+        );
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().cmd,
+            Cmd::PART {
+                chans: vec!["#foo".to_owned(), "#bar".to_owned()],
+                msg: Some("bye".to_owned()),
+            }
+        );
+        assert_eq!(
+            parse_irc_msg(&mut buf).unwrap().cmd,
+            Cmd::PRIVMSG {
+                targets: vec![
+                    MsgTarget::Chan("#foo".to_owned()),
+                    MsgTarget::User("tiny".to_owned())
+                ],
+                msg: "hi".to_owned(),
+                is_notice: false,
+                ctcp: None,
+            }
+        );
+        assert_eq!(buf.len(), 0);
+    }
+
     // Example from https://tools.ietf.org/id/draft-oakley-irc-ctcp-01.html
     #[test]
     fn test_ctcp_action_parsing_1() {
@@ -596,12 +1370,13 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: Some(Pfx::User {
                     nick: "dan".to_owned(),
                     user: "u@localhost".to_owned(),
                 }),
                 cmd: Cmd::PRIVMSG {
-                    target: MsgTarget::Chan("#ircv3".to_owned()),
+                    targets: vec![MsgTarget::Chan("#ircv3".to_owned())],
                     msg: "writes some specs!".to_owned(),
                     is_notice: false,
                     ctcp: Some(CTCP::Action),
@@ -627,7 +1402,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "msg contents".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Action),
@@ -640,7 +1415,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Action),
@@ -655,7 +1430,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "’’’’’’’".to_owned(),
                 is_notice: false,
                 ctcp: None,
@@ -671,7 +1446,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Version),
@@ -683,7 +1458,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Version),
@@ -698,7 +1473,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "blah ".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Other("blah".to_owned())),
@@ -710,7 +1485,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf).unwrap().cmd,
             Cmd::PRIVMSG {
-                target: MsgTarget::User("target".to_owned()),
+                targets: vec![MsgTarget::User("target".to_owned())],
                 msg: "blah ".to_owned(),
                 is_notice: false,
                 ctcp: Some(CTCP::Other("blah".to_owned())),
@@ -729,6 +1504,7 @@ mod tests {
         assert_eq!(
             parse_irc_msg(&mut buf),
             Some(Msg {
+                tags: vec![],
                 pfx: None,
                 cmd: Cmd::ERROR {
                     msg: "Closing Link: 212.252.143.51 (Excess Flood)".to_owned(),
@@ -736,4 +1512,421 @@ mod tests {
             }),
         );
     }
+
+    #[test]
+    fn test_tag_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            "@msgid=abc;+draft/reply=x\\sy\\:z;flag :a!b@c PRIVMSG #chan :hi\r\n"
+        )
+        .unwrap();
+        let msg = parse_irc_msg(&mut buf).unwrap();
+        assert_eq!(get_tag(&msg.tags, "msgid"), Some("abc"));
+        assert_eq!(get_tag(&msg.tags, "+draft/reply"), Some("x y;z"));
+        assert_eq!(get_tag(&msg.tags, "flag"), Some(""));
+        assert_eq!(get_tag(&msg.tags, "foo"), None);
+        assert_eq!(
+            msg.pfx,
+            Some(Pfx::User {
+                nick: "a".to_owned(),
+                user: "b@c".to_owned(),
+            })
+        );
+        assert_eq!(buf.len(), 0);
+    }
+
+    #[test]
+    fn test_tagmsg_parsing() {
+        let mut buf = vec![];
+        write!(
+            &mut buf,
+            "@+draft/react=\u{1F44D};+draft/reply=abc :a!b@c TAGMSG #chan\r\n"
+        )
+        .unwrap();
+        let msg = parse_irc_msg(&mut buf).unwrap();
+        assert_eq!(
+            msg.cmd,
+            Cmd::TAGMSG {
+                target: MsgTarget::Chan("#chan".to_owned()),
+            }
+        );
+        assert_eq!(get_tag(&msg.tags, "+draft/react"), Some("\u{1F44D}"));
+        assert_eq!(get_tag(&msg.tags, "+draft/reply"), Some("abc"));
+    }
+
+    #[test]
+    fn test_tag_value_escaping() {
+        let value = "a;b c\\d\r\n";
+        assert_eq!(escape_tag_value(value), "a\\:b\\sc\\\\d\\r\\n");
+        assert_eq!(unescape_tag_value(&escape_tag_value(value)), value);
+        // Unknown escapes are replaced with the escaped char, trailing backslash is dropped
+        assert_eq!(unescape_tag_value("\\b\\"), "b");
+    }
+
+    #[test]
+    fn test_encode() {
+        let msg = Msg {
+            tags: vec![Tag {
+                key: "+draft/reply".to_owned(),
+                value: "a;b c".to_owned(),
+            }],
+            pfx: Some(Pfx::User {
+                nick: "nick".to_owned(),
+                user: "~nick@host".to_owned(),
+            }),
+            cmd: Cmd::PRIVMSG {
+                targets: vec![
+                    MsgTarget::Chan("#a".to_owned()),
+                    MsgTarget::User("b".to_owned()),
+                ],
+                msg: "hi".to_owned(),
+                is_notice: false,
+                ctcp: Some(CTCP::Action),
+            },
+        };
+        assert_eq!(
+            msg.to_string(),
+            "@+draft/reply=a\\:b\\sc :nick!~nick@host PRIVMSG #a,b :\x01ACTION hi\x01"
+        );
+        assert_eq!(msg.encode(), Ok(format!("{}\r\n", msg)));
+
+        let msg = Msg {
+            tags: vec![],
+            pfx: None,
+            cmd: Cmd::Other {
+                cmd: "MODE".to_owned(),
+                params: vec!["#chan".to_owned(), "+o".to_owned(), ":nick".to_owned()],
+            },
+        };
+        assert_eq!(msg.encode(), Ok("MODE #chan +o ::nick\r\n".to_owned()));
+
+        let msg = Msg {
+            tags: vec![],
+            pfx: None,
+            cmd: Cmd::Reply {
+                num: 1,
+                params: vec!["tiny".to_owned(), "".to_owned()],
+            },
+        };
+        assert_eq!(msg.encode(), Ok("001 tiny :\r\n".to_owned()));
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let other = |cmd: &str, params: &[&str]| Msg {
+            tags: vec![],
+            pfx: None,
+            cmd: Cmd::Other {
+                cmd: cmd.to_owned(),
+                params: params.iter().map(|s| (*s).to_owned()).collect(),
+            },
+        };
+
+        assert_eq!(
+            other("MODE", &["#chan", "a\r\nQUIT"]).encode(),
+            Err(EncodeError::InvalidChar('\r'))
+        );
+        assert_eq!(
+            other("MODE", &["a b", "c"]).encode(),
+            Err(EncodeError::InvalidToken("a b".to_owned()))
+        );
+        assert_eq!(
+            other("MODE", &["", "c"]).encode(),
+            Err(EncodeError::InvalidToken("".to_owned()))
+        );
+        assert_eq!(
+            other(":MODE", &[]).encode(),
+            Err(EncodeError::InvalidToken(":MODE".to_owned()))
+        );
+        assert_eq!(
+            other("MODE", &["a"; MAX_PARAMS + 1]).encode(),
+            Err(EncodeError::TooManyParams(MAX_PARAMS + 1))
+        );
+
+        let long_msg = "x".repeat(500);
+        assert!(privmsg("#chan", &long_msg[..495]).is_ok());
+        assert_eq!(
+            privmsg("#chan", &long_msg[..496]),
+            Err(EncodeError::TooLong { len: 513, max: 512 })
+        );
+        // Tags are not included in the 512 bytes limit
+        assert!(reply("#chan", &long_msg, &long_msg[..495]).is_ok());
+    }
+
+    #[test]
+    fn test_generators() {
+        assert_eq!(nick("osa1"), Ok("NICK osa1\r\n".to_owned()));
+        assert_eq!(join(&["#a", "#b"]), Ok("JOIN #a,#b\r\n".to_owned()));
+        assert_eq!(
+            mode("#tiny", "+ov", &["a", "b"]),
+            Ok("MODE #tiny +ov a b\r\n".to_owned())
+        );
+        assert_eq!(
+            kick("#tiny", "osa1", Some("bye")),
+            Ok("KICK #tiny osa1 :bye\r\n".to_owned())
+        );
+        assert_eq!(topic("#tiny", Some("")), Ok("TOPIC #tiny :\r\n".to_owned()));
+        assert_eq!(quit(None), Ok("QUIT\r\n".to_owned()));
+
+        // Arguments can't inject commands
+        assert_eq!(nick("osa1\r\nQUIT"), Err(EncodeError::InvalidChar('\r')));
+        assert_eq!(join(&["#a\nPART #b"]), Err(EncodeError::InvalidChar('\n')));
+        assert_eq!(
+            kick("#tiny", "osa1", Some("bye\0")),
+            Err(EncodeError::InvalidChar('\0'))
+        );
+        assert_eq!(
+            topic("#tiny", Some("a\r\nQUIT")),
+            Err(EncodeError::InvalidChar('\r'))
+        );
+        assert_eq!(away(Some("\nQUIT")), Err(EncodeError::InvalidChar('\n')));
+        // Only the last parameter can have spaces
+        assert_eq!(
+            invite("a b", "#tiny"),
+            Err(EncodeError::InvalidToken("a b".to_owned()))
+        );
+        assert_eq!(part(""), Err(EncodeError::InvalidToken("".to_owned())));
+    }
+
+    #[test]
+    fn test_quit_parsing() {
+        let mut buf = vec![];
+        write!(&mut buf, ":nick!~nick@host QUIT :bye\r\n").unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).map(|msg| msg.cmd),
+            Some(Cmd::QUIT {
+                msg: Some("bye".to_owned()),
+                chans: vec![],
+            })
+        );
+    }
+
+    // Regression tests for bugs found by fuzzing (see `fuzz` directory)
+
+    #[test]
+    fn test_drop_nick_prefix() {
+        assert_eq!(drop_nick_prefix("@nick"), "nick");
+        assert_eq!(drop_nick_prefix("nick"), "nick");
+        assert_eq!(drop_nick_prefix(""), "");
+        assert_eq!(drop_nick_prefix("ş"), "ş");
+    }
+
+    #[test]
+    fn test_multiple_spaces() {
+        assert_eq!(params("  a   b  :c  d"), vec!["a", "b", "c  d"]);
+        assert_eq!(params("a "), vec!["a"]);
+        let v: Vec<&str> = vec![];
+        assert_eq!(params("   "), v);
+
+        let mut buf = vec![];
+        write!(&mut buf, ":nick!~nick@host  PRIVMSG   #chan  :hi\r\n").unwrap();
+        assert_eq!(
+            parse_irc_msg(&mut buf).map(|msg| msg.cmd),
+            Some(Cmd::PRIVMSG {
+                targets: vec![MsgTarget::Chan("#chan".to_owned())],
+                msg: "hi".to_owned(),
+                is_notice: false,
+                ctcp: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ctcp_parsing() {
+        assert_eq!(parse_ctcp("hi"), None);
+        assert_eq!(parse_ctcp("\x01"), Some((CTCP::Other("".to_owned()), "")));
+        assert_eq!(parse_ctcp("\x01VERSION\x01"), Some((CTCP::Version, "")));
+        // Closing \x01 is optional
+        assert_eq!(parse_ctcp("\x01VERSION"), Some((CTCP::Version, "")));
+        assert_eq!(parse_ctcp("\x01ACTION hi"), Some((CTCP::Action, "hi")));
+        assert_eq!(
+            parse_ctcp("\x01PING 123 456\x01"),
+            Some((CTCP::Other("PING".to_owned()), "123 456"))
+        );
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // Property tests
+
+    use quickcheck::{Arbitrary, Gen};
+
+    /// A `Msg` that is parsed back to the same `Msg` when encoded. E.g. targets starting with '#'
+    /// are always channels, `NICK` and `QUIT` don't have channels.
+    #[derive(Debug, Clone)]
+    struct RoundTripMsg(Msg);
+
+    // Chars that need special care in some parts of a message
+    static TOKEN_CHARS: [char; 15] = [
+        'a', 'Z', '0', '#', '!', '@', ':', ';', '=', ',', '\\', '/', '+', '\x01', 'ş',
+    ];
+
+    fn choose<G: Gen, T: Clone>(g: &mut G, xs: &[T]) -> T {
+        xs[g.next_u32() as usize % xs.len()].clone()
+    }
+
+    fn gen_range<G: Gen>(g: &mut G, lo: usize, hi: usize) -> usize {
+        lo + g.next_u32() as usize % (hi - lo)
+    }
+
+    fn gen_str<G: Gen>(g: &mut G, chars: &[char], min_len: usize) -> String {
+        let len = gen_range(g, min_len, 8);
+        (0..len).map(|_| choose(g, chars)).collect()
+    }
+
+    /// A non-empty string without spaces, not starting with ':', without the given chars.
+    fn gen_token<G: Gen>(g: &mut G, excluded: &[char]) -> String {
+        let chars: Vec<char> = TOKEN_CHARS
+            .iter()
+            .cloned()
+            .filter(|c| !excluded.contains(c))
+            .collect();
+        let token = gen_str(g, &chars, 1);
+        match token.strip_prefix(':') {
+            Some(token) => format!("a{}", token),
+            None => token,
+        }
+    }
+
+    /// A string for a last parameter: may be empty, have spaces, or start with ':'.
+    fn gen_text<G: Gen>(g: &mut G, excluded: &[char]) -> String {
+        let mut chars: Vec<char> = TOKEN_CHARS
+            .iter()
+            .cloned()
+            .filter(|c| !excluded.contains(c))
+            .collect();
+        chars.push(' ');
+        gen_str(g, &chars, 0)
+    }
+
+    fn gen_params<G: Gen>(g: &mut G) -> Vec<String> {
+        let n_params = gen_range(g, 0, MAX_PARAMS + 1);
+        let mut params: Vec<String> = (0..n_params).map(|_| gen_token(g, &[])).collect();
+        if let Some(last) = params.last_mut() {
+            *last = gen_text(g, &[]);
+        }
+        params
+    }
+
+    fn gen_cmd<G: Gen>(g: &mut G) -> Cmd {
+        let list = |g: &mut G| -> Vec<String> {
+            (0..gen_range(g, 1, 4))
+                .map(|_| gen_token(g, &[',']))
+                .collect()
+        };
+        let opt_text = |g: &mut G| -> Option<String> {
+            if bool::arbitrary(g) {
+                None
+            } else {
+                Some(gen_text(g, &[]))
+            }
+        };
+        match gen_range(g, 0, 15) {
+            0 | 1 => {
+                let ctcp = match gen_range(g, 0, 4) {
+                    0 => None,
+                    1 => Some(CTCP::Version),
+                    2 => Some(CTCP::Action),
+                    _ => Some(CTCP::Other(gen_token(g, &['\x01']))),
+                };
+                Cmd::PRIVMSG {
+                    targets: list(g).iter().map(|s| MsgTarget::parse(s)).collect(),
+                    msg: gen_text(g, &['\x01']),
+                    is_notice: bool::arbitrary(g),
+                    ctcp,
+                }
+            }
+            2 => Cmd::TAGMSG {
+                target: MsgTarget::parse(&gen_token(g, &[','])),
+            },
+            3 => {
+                let chans = list(g);
+                let keys = (0..gen_range(g, 0, chans.len() + 1))
+                    .map(|_| gen_token(g, &[',']))
+                    .collect();
+                Cmd::JOIN { chans, keys }
+            }
+            4 => Cmd::PART {
+                chans: list(g),
+                msg: opt_text(g),
+            },
+            5 => Cmd::QUIT {
+                msg: opt_text(g),
+                chans: vec![],
+            },
+            6 => Cmd::NICK {
+                nick: gen_token(g, &[]),
+                chans: vec![],
+            },
+            7 => Cmd::PING {
+                server: gen_text(g, &[]),
+            },
+            8 => Cmd::PONG {
+                server: gen_text(g, &[]),
+            },
+            9 => Cmd::ERROR {
+                msg: gen_text(g, &[]),
+            },
+            10 => Cmd::TOPIC {
+                chan: gen_token(g, &[]),
+                topic: gen_text(g, &[]),
+            },
+            11 => Cmd::CAP {
+                client: gen_token(g, &[]),
+                subcommand: gen_token(g, &[]),
+                params: (0..gen_range(g, 1, 4)).map(|_| gen_token(g, &[])).collect(),
+            },
+            12 => Cmd::AUTHENTICATE {
+                param: gen_text(g, &[]),
+            },
+            13 => Cmd::Other {
+                cmd: choose(g, &["MODE", "KICK", "WHO", "INVITE"]).to_owned(),
+                params: gen_params(g),
+            },
+            _ => Cmd::Reply {
+                num: gen_range(g, 0, 1000) as u16,
+                params: gen_params(g),
+            },
+        }
+    }
+
+    impl Arbitrary for RoundTripMsg {
+        fn arbitrary<G: Gen>(g: &mut G) -> RoundTripMsg {
+            let tags = (0..gen_range(g, 0, 3))
+                .map(|_| {
+                    let mut value_chars = TOKEN_CHARS.to_vec();
+                    value_chars.extend_from_slice(&[' ', '\r', '\n']);
+                    Tag {
+                        key: gen_token(g, &['=', ';']),
+                        value: gen_str(g, &value_chars, 0),
+                    }
+                })
+                .collect();
+            let pfx = match gen_range(g, 0, 3) {
+                0 => None,
+                1 => Some(Pfx::Server(gen_token(g, &['!']))),
+                _ => Some(Pfx::User {
+                    nick: gen_token(g, &['!']),
+                    user: gen_token(g, &[]),
+                }),
+            };
+            RoundTripMsg(Msg {
+                tags,
+                pfx,
+                cmd: gen_cmd(g),
+            })
+        }
+    }
+
+    #[test]
+    fn test_encode_parse_roundtrip() {
+        fn prop(msg: RoundTripMsg) -> bool {
+            let msg = msg.0;
+            let mut buf = msg.encode().unwrap().into_bytes();
+            parse_irc_msg(&mut buf) == Some(msg) && buf.is_empty()
+        }
+        quickcheck::QuickCheck::new()
+            .tests(1000)
+            .quickcheck(prop as fn(RoundTripMsg) -> bool);
+    }
 }
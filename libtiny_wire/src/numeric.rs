@@ -0,0 +1,310 @@
+//! Numeric replies: names and categories of RFC 1459/2812 numerics and the numerics commonly used
+//! by modern servers (see https://defs.ircdocs.horse/defs/numerics.html). Where servers disagree
+//! on a number the most common meaning is used.
+
+use self::NumericCategory::*;
+
+/// What a numeric reply is about. Clients can use this to decide where to show a reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumericCategory {
+    /// Connection registration: RPL_WELCOME, RPL_ISUPPORT etc.
+    Welcome,
+    /// Message of the day.
+    Motd,
+    /// Server and network statistics: LUSERS, STATS, TRACE.
+    Stats,
+    /// Replies to informational commands (VERSION, TIME, ADMIN, HELP etc.) and other server
+    /// notices.
+    Info,
+    /// WHOIS and WHOWAS replies. The second parameter is the nick.
+    Whois,
+    /// WHO replies.
+    Who,
+    /// LIST replies.
+    List,
+    /// NAMES replies.
+    Names,
+    /// Channel topic. The channel is the second parameter.
+    Topic,
+    /// Channel modes, creation time and URL. The channel is the second parameter.
+    Chan,
+    /// Ban, quiet, exception and invite exception lists. The channel is the second parameter.
+    ModeList,
+    /// Away status.
+    Away,
+    /// MONITOR replies.
+    Monitor,
+    /// SASL authentication.
+    Sasl,
+    /// Command errors. The second parameter is usually the nick, channel or command the error is
+    /// about.
+    Error,
+}
+
+/// A numeric reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Numeric {
+    pub num: u16,
+    /// Symbolic name, e.g. `RPL_WELCOME`, `ERR_NICKNAMEINUSE`.
+    pub name: &'static str,
+    pub category: NumericCategory,
+}
+
+impl Numeric {
+    /// Is this an error reply (`ERR_*`)? Note that error replies in some categories (e.g.
+    /// `ERR_NOMOTD`, `ERR_SASLFAIL`) are not in the `Error` category.
+    pub fn is_error(&self) -> bool {
+        self.name.starts_with("ERR_")
+    }
+}
+
+/// Find a numeric reply by number.
+pub fn numeric(num: u16) -> Option<Numeric> {
+    NUMERICS
+        .binary_search_by_key(&num, |(num, _, _)| *num)
+        .ok()
+        .map(|idx| to_numeric(&NUMERICS[idx]))
+}
+
+/// Find a numeric reply by its symbolic name.
+pub fn numeric_by_name(name: &str) -> Option<Numeric> {
+    NUMERICS
+        .iter()
+        .find(|(_, name_, _)| *name_ == name)
+        .map(to_numeric)
+}
+
+fn to_numeric(&(num, name, category): &(u16, &'static str, NumericCategory)) -> Numeric {
+    Numeric {
+        num,
+        name,
+        category,
+    }
+}
+
+// Sorted by number, for binary search.
+#[rustfmt::skip]
+static NUMERICS: &[(u16, &str, NumericCategory)] = &[
+    (1, "RPL_WELCOME", Welcome),
+    (2, "RPL_YOURHOST", Welcome),
+    (3, "RPL_CREATED", Welcome),
+    (4, "RPL_MYINFO", Welcome),
+    (5, "RPL_ISUPPORT", Welcome),
+    (10, "RPL_BOUNCE", Welcome),
+    (42, "RPL_YOURID", Welcome),
+    (200, "RPL_TRACELINK", Stats),
+    (201, "RPL_TRACECONNECTING", Stats),
+    (202, "RPL_TRACEHANDSHAKE", Stats),
+    (203, "RPL_TRACEUNKNOWN", Stats),
+    (204, "RPL_TRACEOPERATOR", Stats),
+    (205, "RPL_TRACEUSER", Stats),
+    (206, "RPL_TRACESERVER", Stats),
+    (207, "RPL_TRACESERVICE", Stats),
+    (208, "RPL_TRACENEWTYPE", Stats),
+    (209, "RPL_TRACECLASS", Stats),
+    (210, "RPL_TRACERECONNECT", Stats),
+    (211, "RPL_STATSLINKINFO", Stats),
+    (212, "RPL_STATSCOMMANDS", Stats),
+    (213, "RPL_STATSCLINE", Stats),
+    (215, "RPL_STATSILINE", Stats),
+    (216, "RPL_STATSKLINE", Stats),
+    (218, "RPL_STATSYLINE", Stats),
+    (219, "RPL_ENDOFSTATS", Stats),
+    (221, "RPL_UMODEIS", Info),
+    (234, "RPL_SERVLIST", Info),
+    (235, "RPL_SERVLISTEND", Info),
+    (241, "RPL_STATSLLINE", Stats),
+    (242, "RPL_STATSUPTIME", Stats),
+    (243, "RPL_STATSOLINE", Stats),
+    (244, "RPL_STATSHLINE", Stats),
+    (250, "RPL_STATSCONN", Stats),
+    (251, "RPL_LUSERCLIENT", Stats),
+    (252, "RPL_LUSEROP", Stats),
+    (253, "RPL_LUSERUNKNOWN", Stats),
+    (254, "RPL_LUSERCHANNELS", Stats),
+    (255, "RPL_LUSERME", Stats),
+    (256, "RPL_ADMINME", Info),
+    (257, "RPL_ADMINLOC1", Info),
+    (258, "RPL_ADMINLOC2", Info),
+    (259, "RPL_ADMINEMAIL", Info),
+    (261, "RPL_TRACELOG", Stats),
+    (262, "RPL_TRACEEND", Stats),
+    (263, "RPL_TRYAGAIN", Info),
+    (265, "RPL_LOCALUSERS", Stats),
+    (266, "RPL_GLOBALUSERS", Stats),
+    (276, "RPL_WHOISCERTFP", Whois),
+    (300, "RPL_NONE", Info),
+    (301, "RPL_AWAY", Away),
+    (302, "RPL_USERHOST", Info),
+    (303, "RPL_ISON", Info),
+    (305, "RPL_UNAWAY", Away),
+    (306, "RPL_NOWAWAY", Away),
+    (307, "RPL_WHOISREGNICK", Whois),
+    (311, "RPL_WHOISUSER", Whois),
+    (312, "RPL_WHOISSERVER", Whois),
+    (313, "RPL_WHOISOPERATOR", Whois),
+    (314, "RPL_WHOWASUSER", Whois),
+    (315, "RPL_ENDOFWHO", Who),
+    (317, "RPL_WHOISIDLE", Whois),
+    (318, "RPL_ENDOFWHOIS", Whois),
+    (319, "RPL_WHOISCHANNELS", Whois),
+    (320, "RPL_WHOISSPECIAL", Whois),
+    (321, "RPL_LISTSTART", List),
+    (322, "RPL_LIST", List),
+    (323, "RPL_LISTEND", List),
+    (324, "RPL_CHANNELMODEIS", Chan),
+    (325, "RPL_UNIQOPIS", Chan),
+    (328, "RPL_CHANNEL_URL", Chan),
+    (329, "RPL_CREATIONTIME", Chan),
+    (330, "RPL_WHOISACCOUNT", Whois),
+    (331, "RPL_NOTOPIC", Topic),
+    (332, "RPL_TOPIC", Topic),
+    (333, "RPL_TOPICWHOTIME", Topic),
+    (336, "RPL_INVITELIST", Info),
+    (337, "RPL_ENDOFINVITELIST", Info),
+    (338, "RPL_WHOISACTUALLY", Whois),
+    (341, "RPL_INVITING", Info),
+    (342, "RPL_SUMMONING", Info),
+    (346, "RPL_INVEXLIST", ModeList),
+    (347, "RPL_ENDOFINVEXLIST", ModeList),
+    (348, "RPL_EXCEPTLIST", ModeList),
+    (349, "RPL_ENDOFEXCEPTLIST", ModeList),
+    (351, "RPL_VERSION", Info),
+    (352, "RPL_WHOREPLY", Who),
+    (353, "RPL_NAMREPLY", Names),
+    (354, "RPL_WHOSPCRPL", Who),
+    (364, "RPL_LINKS", Info),
+    (365, "RPL_ENDOFLINKS", Info),
+    (366, "RPL_ENDOFNAMES", Names),
+    (367, "RPL_BANLIST", ModeList),
+    (368, "RPL_ENDOFBANLIST", ModeList),
+    (369, "RPL_ENDOFWHOWAS", Whois),
+    (371, "RPL_INFO", Info),
+    (372, "RPL_MOTD", Motd),
+    (374, "RPL_ENDOFINFO", Info),
+    (375, "RPL_MOTDSTART", Motd),
+    (376, "RPL_ENDOFMOTD", Motd),
+    (378, "RPL_WHOISHOST", Whois),
+    (379, "RPL_WHOISMODES", Whois),
+    (381, "RPL_YOUREOPER", Info),
+    (382, "RPL_REHASHING", Info),
+    (383, "RPL_YOURESERVICE", Info),
+    (391, "RPL_TIME", Info),
+    (392, "RPL_USERSSTART", Info),
+    (393, "RPL_USERS", Info),
+    (394, "RPL_ENDOFUSERS", Info),
+    (395, "RPL_NOUSERS", Info),
+    (396, "RPL_VISIBLEHOST", Info),
+    (400, "ERR_UNKNOWNERROR", Error),
+    (401, "ERR_NOSUCHNICK", Error),
+    (402, "ERR_NOSUCHSERVER", Error),
+    (403, "ERR_NOSUCHCHANNEL", Error),
+    (404, "ERR_CANNOTSENDTOCHAN", Error),
+    (405, "ERR_TOOMANYCHANNELS", Error),
+    (406, "ERR_WASNOSUCHNICK", Error),
+    (407, "ERR_TOOMANYTARGETS", Error),
+    (408, "ERR_NOSUCHSERVICE", Error),
+    (409, "ERR_NOORIGIN", Error),
+    (411, "ERR_NORECIPIENT", Error),
+    (412, "ERR_NOTEXTTOSEND", Error),
+    (413, "ERR_NOTOPLEVEL", Error),
+    (414, "ERR_WILDTOPLEVEL", Error),
+    (415, "ERR_BADMASK", Error),
+    (417, "ERR_INPUTTOOLONG", Error),
+    (421, "ERR_UNKNOWNCOMMAND", Error),
+    (422, "ERR_NOMOTD", Motd),
+    (423, "ERR_NOADMININFO", Error),
+    (424, "ERR_FILEERROR", Error),
+    (431, "ERR_NONICKNAMEGIVEN", Error),
+    (432, "ERR_ERRONEUSNICKNAME", Error),
+    (433, "ERR_NICKNAMEINUSE", Error),
+    (436, "ERR_NICKCOLLISION", Error),
+    (437, "ERR_UNAVAILRESOURCE", Error),
+    (441, "ERR_USERNOTINCHANNEL", Error),
+    (442, "ERR_NOTONCHANNEL", Error),
+    (443, "ERR_USERONCHANNEL", Error),
+    (444, "ERR_NOLOGIN", Error),
+    (445, "ERR_SUMMONDISABLED", Error),
+    (446, "ERR_USERSDISABLED", Error),
+    (451, "ERR_NOTREGISTERED", Error),
+    (461, "ERR_NEEDMOREPARAMS", Error),
+    (462, "ERR_ALREADYREGISTERED", Error),
+    (463, "ERR_NOPERMFORHOST", Error),
+    (464, "ERR_PASSWDMISMATCH", Error),
+    (465, "ERR_YOUREBANNEDCREEP", Error),
+    (466, "ERR_YOUWILLBEBANNED", Error),
+    (467, "ERR_KEYSET", Error),
+    (471, "ERR_CHANNELISFULL", Error),
+    (472, "ERR_UNKNOWNMODE", Error),
+    (473, "ERR_INVITEONLYCHAN", Error),
+    (474, "ERR_BANNEDFROMCHAN", Error),
+    (475, "ERR_BADCHANNELKEY", Error),
+    (476, "ERR_BADCHANMASK", Error),
+    (477, "ERR_NOCHANMODES", Error),
+    (478, "ERR_BANLISTFULL", Error),
+    (481, "ERR_NOPRIVILEGES", Error),
+    (482, "ERR_CHANOPRIVSNEEDED", Error),
+    (483, "ERR_CANTKILLSERVER", Error),
+    (484, "ERR_RESTRICTED", Error),
+    (485, "ERR_UNIQOPPRIVSNEEDED", Error),
+    (491, "ERR_NOOPERHOST", Error),
+    (501, "ERR_UMODEUNKNOWNFLAG", Error),
+    (502, "ERR_USERSDONTMATCH", Error),
+    (524, "ERR_HELPNOTFOUND", Error),
+    (525, "ERR_INVALIDKEY", Error),
+    (670, "RPL_STARTTLS", Info),
+    (671, "RPL_WHOISSECURE", Whois),
+    (691, "ERR_STARTTLS", Error),
+    (696, "ERR_INVALIDMODEPARAM", Error),
+    (704, "RPL_HELPSTART", Info),
+    (705, "RPL_HELPTXT", Info),
+    (706, "RPL_ENDOFHELP", Info),
+    (723, "ERR_NOPRIVS", Error),
+    (728, "RPL_QUIETLIST", ModeList),
+    (729, "RPL_ENDOFQUIETLIST", ModeList),
+    (730, "RPL_MONONLINE", Monitor),
+    (731, "RPL_MONOFFLINE", Monitor),
+    (732, "RPL_MONLIST", Monitor),
+    (733, "RPL_ENDOFMONLIST", Monitor),
+    (734, "ERR_MONLISTFULL", Monitor),
+    (900, "RPL_LOGGEDIN", Sasl),
+    (901, "RPL_LOGGEDOUT", Sasl),
+    (902, "ERR_NICKLOCKED", Sasl),
+    (903, "RPL_SASLSUCCESS", Sasl),
+    (904, "ERR_SASLFAIL", Sasl),
+    (905, "ERR_SASLTOOLONG", Sasl),
+    (906, "ERR_SASLABORTED", Sasl),
+    (907, "ERR_SASLALREADY", Sasl),
+    (908, "RPL_SASLMECHS", Sasl),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numerics_sorted() {
+        for pair in NUMERICS.windows(2) {
+            assert!(pair[0].0 < pair[1].0, "{:?} {:?}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_numeric_lookup() {
+        let welcome = numeric(1).unwrap();
+        assert_eq!(welcome.name, "RPL_WELCOME");
+        assert_eq!(welcome.category, Welcome);
+        assert!(!welcome.is_error());
+
+        let nick_in_use = numeric(433).unwrap();
+        assert_eq!(nick_in_use.name, "ERR_NICKNAMEINUSE");
+        assert_eq!(nick_in_use.category, Error);
+        assert!(nick_in_use.is_error());
+
+        assert_eq!(numeric(908).unwrap().name, "RPL_SASLMECHS");
+        assert_eq!(numeric(999), None);
+
+        assert_eq!(numeric_by_name("RPL_BANLIST").unwrap().num, 367);
+        assert_eq!(numeric_by_name("RPL_FOO"), None);
+    }
+}
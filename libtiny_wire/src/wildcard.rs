@@ -0,0 +1,53 @@
+//! Matching strings against patterns with wildcards, like channel masks in LIST filters.
+
+/// Matching with `*` (any string) and `?` (any character) wildcards. Matching is case-sensitive,
+/// callers should casemap both strings for case-insensitive matching.
+pub fn wildcard_match(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p_idx, mut s_idx) = (0, 0);
+    // Position of the last '*' in the pattern, and the position in `s` it's currently matching up
+    // to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s_idx < s.len() {
+        if p_idx < pattern.len() && (pattern[p_idx] == '?' || pattern[p_idx] == s[s_idx]) {
+            p_idx += 1;
+            s_idx += 1;
+        } else if p_idx < pattern.len() && pattern[p_idx] == '*' {
+            backtrack = Some((p_idx, s_idx));
+            p_idx += 1;
+        } else if let Some((star_idx, star_s_idx)) = backtrack {
+            // Make the last '*' match one more character
+            p_idx = star_idx + 1;
+            s_idx = star_s_idx + 1;
+            backtrack = Some((star_idx, star_s_idx + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p_idx..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("#rust", "#rust"));
+        assert!(!wildcard_match("#rust", "#Rust"));
+        assert!(wildcard_match("#rust*", "#rust-beginners"));
+        assert!(wildcard_match("*rust*", "##rust-offtopic"));
+        assert!(wildcard_match("#r?st", "#rust"));
+        assert!(wildcard_match("*", ""));
+        assert!(!wildcard_match("#rust", "#rust-beginners"));
+        assert!(!wildcard_match("#r?st", "#roost"));
+        assert!(!wildcard_match("*rust", "#rusty"));
+        assert!(wildcard_match(
+            "USER tiny 8 * :*",
+            "USER tiny 8 * :tiny user"
+        ));
+        assert!(wildcard_match("a*b*c", "aXbYbZc"));
+        assert!(!wildcard_match("a*b*c", "aXbYbZ"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Prints log files, decrypting encrypted lines. See `Encryption` in `libtiny_logger`.
+//!
+//!     tiny-logcat -i ~/.config/tiny/key.txt ~/tiny_logs/irc.freenode.net_#tiny.txt | grep osa1
+//!     TINY_LOG_PASSPHRASE=... tiny-logcat -p ~/tiny_logs/irc.freenode.net_#tiny.txt
+
+use libtiny_logger::LogDecrypter;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+Usage: tiny-logcat (-p | -i IDENTITY_FILE) FILE...
+
+Prints log files, decrypting lines encrypted by tiny.
+
+    -p                  Decrypt logs encrypted with a passphrase. The passphrase is read from
+                        the TINY_LOG_PASSPHRASE environment variable, or the first line of stdin.
+    -i IDENTITY_FILE    Decrypt logs encrypted to recipients, using the private keys in the age
+                        identity file";
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut passphrase = false;
+    let mut identity_file: Option<PathBuf> = None;
+    let mut files: Vec<PathBuf> = vec![];
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" => passphrase = true,
+            "-i" => match args.next() {
+                None => exit_with_usage(),
+                Some(path) => identity_file = Some(PathBuf::from(path)),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        exit_with_usage();
+    }
+
+    let mut decrypter = match (passphrase, identity_file) {
+        (true, None) => LogDecrypter::with_passphrase(&read_passphrase()),
+        (false, Some(identity_file)) => match LogDecrypter::with_identity_file(&identity_file) {
+            Err(err) => exit_with_error(&format!("Can't read {:?}: {}", identity_file, err)),
+            Ok(decrypter) => decrypter,
+        },
+        _ => exit_with_usage(),
+    };
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for file in &files {
+        match decrypter.decrypt_file(file) {
+            Err(err) => exit_with_error(&format!("Can't decrypt {:?}: {}", file, err)),
+            Ok(log) => {
+                // Ignore errors, e.g. when piped to `head`
+                if stdout.write_all(log.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn read_passphrase() -> String {
+    if let Ok(passphrase) = std::env::var("TINY_LOG_PASSPHRASE") {
+        return passphrase;
+    }
+    let mut passphrase = String::new();
+    if let Err(err) = std::io::stdin().lock().read_line(&mut passphrase) {
+        exit_with_error(&format!("Can't read passphrase: {}", err));
+    }
+    passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned()
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+fn exit_with_error(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    std::process::exit(1);
+}
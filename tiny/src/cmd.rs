@@ -2,15 +2,24 @@
 #![allow(clippy::borrowed_box)]
 
 use crate::config;
+use crate::config::BanMaskStyle;
 use crate::utils;
-use libtiny_client::{Client, ServerInfo};
-use libtiny_ui::{MsgSource, MsgTarget, UI};
-use std::path::Path;
+use futures_util::stream::StreamExt;
+use libtiny_client::{wire, Client, ListFilter, ListMode, ServerInfo};
+use libtiny_logger::{LogDecrypter, LogLine, Passphrase, SearchQuery, SearchResult};
+use libtiny_ui::{
+    raw_tab_name, raw_tab_serv, MsgSource, MsgTags, MsgTarget, LOG_SEARCH_TAB, MENTIONS_TAB, UI,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use tokio::sync::mpsc;
 
 pub(crate) struct CmdArgs<'a> {
     pub args: &'a str,
     pub config_path: &'a Path,
     pub defaults: &'a config::Defaults,
+    pub log_search: &'a mut LogSearch,
     pub ui: &'a Box<dyn UI>,
     pub clients: &'a mut Vec<Client>,
     pub src: MsgSource,
@@ -87,24 +96,58 @@ fn find_client_idx(clients: &[Client], serv_name: &str) -> Option<usize> {
     None
 }
 
+/// Find client of a server. Raw protocol tabs are mapped to their servers, so commands in raw
+/// tabs run on the tab's server.
 fn find_client<'a>(clients: &'a mut Vec<Client>, serv_name: &str) -> Option<&'a mut Client> {
+    let serv_name = raw_tab_serv(serv_name).unwrap_or(serv_name);
     match find_client_idx(clients, serv_name) {
         None => None,
         Some(idx) => Some(&mut clients[idx]),
     }
 }
 
+/// Show an error in the current tab when a command can't be sent, e.g. because an argument has a
+/// newline in it.
+fn report_send_err(ui: &Box<dyn UI>, cmd: &str, ret: Result<(), wire::EncodeError>) {
+    if let Err(err) = ret {
+        ui.add_client_err_msg(
+            &format!("Can't send {}: {}", cmd, err),
+            &MsgTarget::CurrentTab,
+        );
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
-static CMDS: [&Cmd; 8] = [
+static CMDS: [&Cmd; 28] = [
     &AWAY_CMD,
+    &BAN_CMD,
+    &BANS_CMD,
     &CLOSE_CMD,
     &CONNECT_CMD,
+    &DEOP_CMD,
+    &DEVOICE_CMD,
+    &GREP_CMD,
+    &INVITE_CMD,
     &JOIN_CMD,
+    &KICK_CMD,
+    &KICKBAN_CMD,
+    &LIST_CMD,
+    &LOGSEARCH_CMD,
     &ME_CMD,
+    &MODE_CMD,
     &MSG_CMD,
     &NAMES_CMD,
     &NICK_CMD,
+    &OP_CMD,
+    &QUIET_CMD,
+    &QUOTE_CMD,
+    &RAW_CMD,
+    &REACT_CMD,
+    &REPLY_CMD,
+    &TOPIC_CMD,
+    &UNBAN_CMD,
+    &VOICE_CMD,
     // &RELOAD_CMD,
 ];
 
@@ -122,7 +165,422 @@ fn away(args: CmdArgs) {
         Some(args.args)
     };
     if let Some(client) = find_client(args.clients, args.src.serv_name()) {
-        client.away(msg);
+        report_send_err(args.ui, "AWAY", client.away(msg));
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static BAN_CMD: Cmd = Cmd {
+    name: "ban",
+    cmd_fn: ban,
+};
+
+static BANS_CMD: Cmd = Cmd {
+    name: "bans",
+    cmd_fn: bans,
+};
+
+static KICKBAN_CMD: Cmd = Cmd {
+    name: "kickban",
+    cmd_fn: kickban,
+};
+
+static QUIET_CMD: Cmd = Cmd {
+    name: "quiet",
+    cmd_fn: quiet,
+};
+
+static UNBAN_CMD: Cmd = Cmd {
+    name: "unban",
+    cmd_fn: unban,
+};
+
+/// Returns the channel of a channel tab. Shows an error when `src` is not a channel tab.
+fn chan_tab<'a>(ui: &Box<dyn UI>, src: &'a MsgSource, cmd: &str) -> Option<&'a str> {
+    match src {
+        MsgSource::Chan { chan, .. } => Some(chan),
+        _ => {
+            ui.add_client_err_msg(
+                &format!("/{} can only be used in channel tabs", cmd),
+                &MsgTarget::CurrentTab,
+            );
+            None
+        }
+    }
+}
+
+/// Generate a ban mask for a nick. `user_host` is the `user@host` of the nick, when known.
+fn ban_mask(style: BanMaskStyle, nick: &str, user_host: Option<&str>) -> String {
+    let (user, host) = match user_host.and_then(|user_host| {
+        let at_idx = user_host.rfind('@')?;
+        Some((&user_host[..at_idx], &user_host[at_idx + 1..]))
+    }) {
+        None => return format!("{}!*@*", nick),
+        Some(user_host) => user_host,
+    };
+
+    match style {
+        BanMaskStyle::Host => format!("*!*@{}", host),
+        BanMaskStyle::UserHost => format!("*!*{}@{}", user.trim_start_matches('~'), host),
+        BanMaskStyle::Domain => {
+            if host.parse::<std::net::Ipv4Addr>().is_ok() {
+                let net = &host[..host.rfind('.').unwrap()];
+                format!("*!*@{}.*", net)
+            } else if host.contains('/') || host.contains(':') || host.matches('.').count() < 2 {
+                // Cloak, IPv6 address, or a domain without a subdomain
+                format!("*!*@{}", host)
+            } else {
+                format!("*!*@*{}", &host[host.find('.').unwrap()..])
+            }
+        }
+        BanMaskStyle::Nick => format!("{}!*@*", nick),
+        BanMaskStyle::Exact => format!("{}!{}@{}", nick, user, host),
+    }
+}
+
+/// Mask for an argument of `/ban`, `/quiet` etc. Masks are used as-is, masks for nicks are
+/// generated using the configured ban mask style.
+fn arg_mask(ui: &Box<dyn UI>, client: &Client, style: BanMaskStyle, arg: &str) -> String {
+    if arg.contains(&['!', '@', '*', '$'][..]) {
+        return arg.to_owned();
+    }
+    let user_host = client.get_user_host(arg);
+    let mask = ban_mask(style, arg, user_host.as_deref());
+    if user_host.is_none() {
+        ui.add_client_msg(
+            &format!("Host of {} is not known, using mask {}", arg, mask),
+            &MsgTarget::CurrentTab,
+        );
+    }
+    mask
+}
+
+/// Implementation of `/ban`, `/unban` and `/quiet`
+fn set_list_mode(args: CmdArgs, cmd: &str, mode: ListMode, add: bool) {
+    let CmdArgs {
+        args,
+        defaults,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let targets: Vec<&str> = args.split_whitespace().collect();
+    if targets.is_empty() {
+        return ui.add_client_err_msg(
+            &format!("/{0} usage: /{0} <nick or mask> [<nick or mask>...]", cmd),
+            &MsgTarget::CurrentTab,
+        );
+    }
+    let chan = match chan_tab(ui, &src, cmd) {
+        None => return,
+        Some(chan) => chan,
+    };
+    let client = match find_client(clients, src.serv_name()) {
+        None => return,
+        Some(client) => client,
+    };
+
+    let masks: Vec<String> = targets
+        .into_iter()
+        .map(|target| arg_mask(ui, client, defaults.ban_mask, target))
+        .collect();
+    let masks: Vec<&str> = masks.iter().map(String::as_str).collect();
+    report_send_err(
+        ui,
+        "MODE",
+        client.set_modes(chan, add, mode.mode_char(), &masks),
+    );
+}
+
+fn ban(args: CmdArgs) {
+    set_list_mode(args, "ban", ListMode::Ban, true)
+}
+
+fn unban(args: CmdArgs) {
+    set_list_mode(args, "unban", ListMode::Ban, false)
+}
+
+fn quiet(args: CmdArgs) {
+    set_list_mode(args, "quiet", ListMode::Quiet, true)
+}
+
+fn bans(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+
+    let mut mode = ListMode::Ban;
+    let mut chan = None;
+    for word in args.split_whitespace() {
+        match word.chars().collect::<Vec<_>>().as_slice() {
+            [c] if ListMode::from_mode_char(*c).is_some() => {
+                mode = ListMode::from_mode_char(*c).unwrap();
+            }
+            ['#', ..] | ['&', ..] => chan = Some(word),
+            _ => {
+                return ui.add_client_err_msg(
+                    "/bans usage: /bans [b|q|e|I] [channel]",
+                    &MsgTarget::CurrentTab,
+                );
+            }
+        }
+    }
+    let chan = match chan {
+        Some(chan) => chan,
+        None => match chan_tab(ui, &src, "bans") {
+            None => return,
+            Some(chan) => chan,
+        },
+    };
+
+    match find_client(clients, src.serv_name()) {
+        Some(client) => report_send_err(ui, "MODE", client.get_mode_list(chan, mode)),
+        None => ui.add_client_err_msg(
+            &format!("Not connected to server {}", src.serv_name()),
+            &MsgTarget::CurrentTab,
+        ),
+    }
+}
+
+fn kickban(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        defaults,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let mut words = args.trim().splitn(2, char::is_whitespace);
+    let nick = words.next().unwrap_or("");
+    let reason = words.next().unwrap_or("").trim();
+    if nick.is_empty() {
+        return ui.add_client_err_msg(
+            "/kickban usage: /kickban <nick> [reason]",
+            &MsgTarget::CurrentTab,
+        );
+    }
+    let chan = match chan_tab(ui, &src, "kickban") {
+        None => return,
+        Some(chan) => chan,
+    };
+    let client = match find_client(clients, src.serv_name()) {
+        None => return,
+        Some(client) => client,
+    };
+
+    let mask = arg_mask(ui, client, defaults.ban_mask, nick);
+    if let Err(err) = client.mode(chan, "+b", &[&mask]) {
+        return ui.add_client_err_msg(&format!("Can't send MODE: {}", err), &MsgTarget::CurrentTab);
+    }
+    let reason = if reason.is_empty() {
+        None
+    } else {
+        Some(reason)
+    };
+    report_send_err(ui, "KICK", client.kick(chan, nick, reason));
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static KICK_CMD: Cmd = Cmd {
+    name: "kick",
+    cmd_fn: kick,
+};
+
+static MODE_CMD: Cmd = Cmd {
+    name: "mode",
+    cmd_fn: mode,
+};
+
+static OP_CMD: Cmd = Cmd {
+    name: "op",
+    cmd_fn: op,
+};
+
+static DEOP_CMD: Cmd = Cmd {
+    name: "deop",
+    cmd_fn: deop,
+};
+
+static VOICE_CMD: Cmd = Cmd {
+    name: "voice",
+    cmd_fn: voice,
+};
+
+static DEVOICE_CMD: Cmd = Cmd {
+    name: "devoice",
+    cmd_fn: devoice,
+};
+
+static TOPIC_CMD: Cmd = Cmd {
+    name: "topic",
+    cmd_fn: topic,
+};
+
+static INVITE_CMD: Cmd = Cmd {
+    name: "invite",
+    cmd_fn: invite,
+};
+
+fn is_nick(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(utils::is_nick_char)
+}
+
+fn kick(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let mut words = args.trim().splitn(2, char::is_whitespace);
+    let nick = words.next().unwrap_or("");
+    let reason = words.next().unwrap_or("").trim();
+    if !is_nick(nick) {
+        return ui.add_client_err_msg("/kick usage: /kick <nick> [reason]", &MsgTarget::CurrentTab);
+    }
+    let chan = match chan_tab(ui, &src, "kick") {
+        None => return,
+        Some(chan) => chan,
+    };
+    if let Some(client) = find_client(clients, src.serv_name()) {
+        let reason = if reason.is_empty() {
+            None
+        } else {
+            Some(reason)
+        };
+        report_send_err(ui, "KICK", client.kick(chan, nick, reason));
+    }
+}
+
+fn mode(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let client = match find_client(clients, src.serv_name()) {
+        None => return,
+        Some(client) => client,
+    };
+
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let is_modes = |word: &str| word.starts_with('+') || word.starts_with('-');
+    // Target is the current channel, or our nick in server and user tabs, when not given
+    let (target, words) = match words.first() {
+        Some(word) if !is_modes(word) => (word.to_string(), &words[1..]),
+        _ => match &src {
+            MsgSource::Chan { chan, .. } => (chan.to_owned(), &words[..]),
+            _ => (client.get_nick(), &words[..]),
+        },
+    };
+    match words.split_first() {
+        None => report_send_err(ui, "MODE", client.mode(&target, "", &[])),
+        Some((modes, params)) if is_modes(modes) => {
+            report_send_err(ui, "MODE", client.mode(&target, modes, params))
+        }
+        Some(_) => ui.add_client_err_msg(
+            "/mode usage: /mode [target] [(+|-)modes [params...]]",
+            &MsgTarget::CurrentTab,
+        ),
+    }
+}
+
+/// Implementation of `/op`, `/deop`, `/voice` and `/devoice`
+fn set_nick_mode(args: CmdArgs, cmd: &str, mode: char, add: bool) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let nicks: Vec<&str> = args.split_whitespace().collect();
+    if nicks.is_empty() || !nicks.iter().all(|nick| is_nick(nick)) {
+        return ui.add_client_err_msg(
+            &format!("/{0} usage: /{0} <nick> [<nick>...]", cmd),
+            &MsgTarget::CurrentTab,
+        );
+    }
+    let chan = match chan_tab(ui, &src, cmd) {
+        None => return,
+        Some(chan) => chan,
+    };
+    if let Some(client) = find_client(clients, src.serv_name()) {
+        report_send_err(ui, "MODE", client.set_modes(chan, add, mode, &nicks));
+    }
+}
+
+fn op(args: CmdArgs) {
+    set_nick_mode(args, "op", 'o', true)
+}
+
+fn deop(args: CmdArgs) {
+    set_nick_mode(args, "deop", 'o', false)
+}
+
+fn voice(args: CmdArgs) {
+    set_nick_mode(args, "voice", 'v', true)
+}
+
+fn devoice(args: CmdArgs) {
+    set_nick_mode(args, "devoice", 'v', false)
+}
+
+fn topic(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let chan = match chan_tab(ui, &src, "topic") {
+        None => return,
+        Some(chan) => chan,
+    };
+    let topic = args.trim();
+    if let Some(client) = find_client(clients, src.serv_name()) {
+        // Show the current topic when a new topic is not given
+        let topic = if topic.is_empty() { None } else { Some(topic) };
+        report_send_err(ui, "TOPIC", client.topic(chan, topic));
+    }
+}
+
+fn invite(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    let (nick, chan) = match words.as_slice() {
+        [nick] if is_nick(nick) => match chan_tab(ui, &src, "invite") {
+            None => return,
+            Some(chan) => (*nick, chan),
+        },
+        [nick, chan] if is_nick(nick) => (*nick, *chan),
+        _ => {
+            return ui.add_client_err_msg(
+                "/invite usage: /invite <nick> [channel]",
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+    if let Some(client) = find_client(clients, src.serv_name()) {
+        report_send_err(ui, "INVITE", client.invite(nick, chan));
     }
 }
 
@@ -138,11 +596,21 @@ fn close(args: CmdArgs) {
         ui, clients, src, ..
     } = args;
     match src {
-        MsgSource::Serv { ref serv } if serv == "mentions" => {
+        MsgSource::Serv { ref serv } if serv == MENTIONS_TAB => {
             // ignore
         }
+        MsgSource::Serv { ref serv } if serv == LOG_SEARCH_TAB => {
+            ui.close_server_tab(serv);
+        }
+        MsgSource::Serv { ref serv } if raw_tab_serv(serv).is_some() => {
+            ui.close_server_tab(serv);
+            if let Some(client) = find_client(clients, serv) {
+                client.set_raw_msg_events(false);
+            }
+        }
         MsgSource::Serv { serv } => {
             ui.close_server_tab(&serv);
+            ui.close_server_tab(&raw_tab_name(&serv));
             let client_idx = find_client_idx(&clients, &serv).unwrap();
             // TODO: this probably won't close the connection?
             let mut client = clients.remove(client_idx);
@@ -151,7 +619,7 @@ fn close(args: CmdArgs) {
         MsgSource::Chan { serv, chan } => {
             ui.close_chan_tab(&serv, &chan);
             let client_idx = find_client_idx(&clients, &serv).unwrap();
-            clients[client_idx].part(&chan);
+            report_send_err(ui, "PART", clients[client_idx].part(&chan));
         }
         MsgSource::User { serv, nick } => {
             ui.close_user_tab(&serv, &nick);
@@ -259,6 +727,12 @@ fn connect_(
         auto_join: defaults.join.clone(),
         nickserv_ident: None,
         sasl_auth: None,
+        encoding: None,
+        chan_encodings: vec![],
+        outgoing_encoding: None,
+        ping_interval: None,
+        websocket: None,
+        unix_socket: None,
     });
 
     // Spawn UI task
@@ -271,6 +745,275 @@ fn connect_(
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static GREP_CMD: Cmd = Cmd {
+    name: "grep",
+    cmd_fn: grep,
+};
+
+static LOGSEARCH_CMD: Cmd = Cmd {
+    name: "logsearch",
+    cmd_fn: grep,
+};
+
+/// Max. number of log search results to show. When there are more results the most recent ones
+/// are shown.
+const MAX_SEARCH_RESULTS: usize = 500;
+
+/// Number of lines to show before and after a search result when the result is selected.
+const SEARCH_CONTEXT_LINES: usize = 5;
+
+/// State of log searches: where the logs are and results of the last search. Results are updated
+/// by the task that waits for the search thread.
+pub(crate) struct LogSearch {
+    log_dir: Option<PathBuf>,
+    /// Passphrase of encrypted logs
+    passphrase: Option<Passphrase>,
+    results: Rc<RefCell<Vec<SearchResult>>>,
+}
+
+impl LogSearch {
+    pub(crate) fn new(log_dir: Option<PathBuf>, passphrase: Option<Passphrase>) -> LogSearch {
+        LogSearch {
+            log_dir,
+            passphrase,
+            results: Rc::new(RefCell::new(vec![])),
+        }
+    }
+}
+
+/// Decrypter for encrypted logs, used in search threads. Decrypters are created in the threads as
+/// they can't be sent to other threads.
+fn log_decrypter(passphrase: Option<Passphrase>) -> Option<LogDecrypter> {
+    passphrase.map(|passphrase| LogDecrypter::with_passphrase(&passphrase.0))
+}
+
+static GREP_USAGE: &str = "/grep usage: /grep [-s <server>] [-c <chan or nick>] [-n <sender>] \
+                           [-since <yyyy-mm-dd>] [-until <yyyy-mm-dd>] <regex>";
+
+/// Parse `/grep` arguments: options followed by a regex. The regex is the rest of the arguments so
+/// it can contain spaces.
+fn parse_grep_args(args: &str) -> Result<SearchQuery, String> {
+    let mut rest = args.trim_start();
+    let mut opts: Vec<(&str, &str)> = vec![];
+    while rest.starts_with('-') {
+        let mut words = rest.splitn(3, char::is_whitespace);
+        let opt = words.next().unwrap();
+        let val = match words.next() {
+            Some(val) if !val.is_empty() => val,
+            _ => return Err(format!("Missing value for {}", opt)),
+        };
+        opts.push((opt, val));
+        rest = words.next().unwrap_or("").trim_start();
+    }
+    if rest.is_empty() {
+        return Err(GREP_USAGE.to_owned());
+    }
+
+    let mut query = SearchQuery::new(rest).map_err(|err| format!("Invalid regex: {}", err))?;
+    for (opt, val) in opts {
+        let val = Some(val.to_owned());
+        match opt {
+            "-s" => query.serv = val,
+            "-c" => query.target = val,
+            "-n" => query.nick = val,
+            "-since" | "-until" => {
+                if time::strptime(val.as_ref().unwrap(), "%Y-%m-%d").is_err() {
+                    return Err(format!("Invalid date for {}, use yyyy-mm-dd", opt));
+                }
+                if opt == "-since" {
+                    query.since = val;
+                } else {
+                    query.until = val;
+                }
+            }
+            _ => return Err(format!("Unknown option: {}", opt)),
+        }
+    }
+    Ok(query)
+}
+
+/// Where a log line is from, e.g. "irc.freenode.net #tiny".
+fn search_result_location(result: &SearchResult) -> String {
+    match result.target {
+        None => result.serv.clone(),
+        Some(ref target) => format!("{} {}", result.serv, target),
+    }
+}
+
+fn grep(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        log_search,
+        ui,
+        ..
+    } = args;
+    let log_dir = match log_search.log_dir {
+        None => {
+            return ui.add_client_err_msg(
+                "Can't search logs: logging is not enabled (set `log_dir` in the config)",
+                &MsgTarget::CurrentTab,
+            );
+        }
+        Some(ref log_dir) => log_dir.clone(),
+    };
+    let query = match parse_grep_args(args) {
+        Err(err) => return ui.add_client_err_msg(&err, &MsgTarget::CurrentTab),
+        Ok(query) => query,
+    };
+
+    // Search in another thread to not block the UI, results are sent back to a UI task
+    let (mut snd_results, mut rcv_results) = mpsc::unbounded_channel();
+    let query_ = query.clone();
+    let passphrase = log_search.passphrase.clone();
+    std::thread::spawn(move || {
+        let mut decrypter = log_decrypter(passphrase);
+        let results =
+            libtiny_logger::search_logs(&log_dir, &query_, MAX_SEARCH_RESULTS, decrypter.as_mut())
+                .map_err(|err| err.to_string());
+        let _ = snd_results.try_send(results);
+    });
+    ui.add_client_msg("Searching logs...", &MsgTarget::CurrentTab);
+
+    let ui = libtiny_ui::clone_box(&**ui);
+    let search_results = log_search.results.clone();
+    tokio::runtime::current_thread::spawn(async move {
+        if let Some(results) = rcv_results.next().await {
+            show_search_results(&ui, &query, &results);
+            if let Ok(results) = results {
+                *search_results.borrow_mut() = results;
+            }
+            ui.draw();
+        }
+    });
+}
+
+fn show_search_results(
+    ui: &Box<dyn UI>,
+    query: &SearchQuery,
+    results: &Result<Vec<SearchResult>, String>,
+) {
+    let results = match results {
+        Err(err) => {
+            return ui.add_client_err_msg(
+                &format!("Can't search logs: {}", err),
+                &MsgTarget::CurrentTab,
+            );
+        }
+        Ok(results) => results,
+    };
+
+    // Show the results in a fresh tab
+    ui.close_server_tab(LOG_SEARCH_TAB);
+    ui.new_server_tab(LOG_SEARCH_TAB);
+    let target = MsgTarget::Server {
+        serv: LOG_SEARCH_TAB,
+    };
+    for (idx, result) in results.iter().enumerate() {
+        ui.add_msg(
+            &format!(
+                "[{}] {} {}: {}",
+                idx + 1,
+                time::strftime("%Y-%m-%d", &result.line.ts).unwrap(),
+                search_result_location(result),
+                result.line
+            ),
+            result.line.ts,
+            &target,
+        );
+    }
+    ui.add_client_msg(
+        &format!(
+            "{} results for `{}`. Enter a result number in this tab to see the lines around it.",
+            results.len(),
+            query.pattern
+        ),
+        &target,
+    );
+    if results.len() == MAX_SEARCH_RESULTS {
+        ui.add_client_msg(
+            "Only the most recent results are shown, use filters to narrow down the search.",
+            &target,
+        );
+    }
+    ui.add_client_msg(
+        &format!(
+            "{} results, see the \"{}\" tab",
+            results.len(),
+            LOG_SEARCH_TAB
+        ),
+        &MsgTarget::CurrentTab,
+    );
+}
+
+/// Handle input in the log search tab: show lines around the selected search result. The log is
+/// read in another thread, as `grep` does.
+pub(crate) fn show_search_context(ui: &Box<dyn UI>, log_search: &LogSearch, input: &str) {
+    let target = MsgTarget::Server {
+        serv: LOG_SEARCH_TAB,
+    };
+    let results = log_search.results.borrow();
+    let result = match input.trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= results.len() => results[n - 1].clone(),
+        _ => {
+            return ui.add_client_err_msg(
+                &format!(
+                    "Enter a result number (1-{}) to see the lines around it",
+                    results.len()
+                ),
+                &target,
+            );
+        }
+    };
+
+    let (mut snd_context, mut rcv_context) = mpsc::unbounded_channel();
+    let passphrase = log_search.passphrase.clone();
+    std::thread::spawn(move || {
+        let mut decrypter = log_decrypter(passphrase);
+        let context =
+            libtiny_logger::read_context(&result, SEARCH_CONTEXT_LINES, decrypter.as_mut())
+                .map_err(|err| err.to_string());
+        let _ = snd_context.try_send((result, context));
+    });
+
+    let ui = libtiny_ui::clone_box(&**ui);
+    tokio::runtime::current_thread::spawn(async move {
+        if let Some((result, context)) = rcv_context.next().await {
+            show_context_lines(&ui, &result, context);
+            ui.draw();
+        }
+    });
+}
+
+fn show_context_lines(
+    ui: &Box<dyn UI>,
+    result: &SearchResult,
+    context: Result<(Vec<LogLine>, usize), String>,
+) {
+    let target = MsgTarget::Server {
+        serv: LOG_SEARCH_TAB,
+    };
+    let (lines, result_idx) = match context {
+        Err(err) => {
+            return ui.add_client_err_msg(&format!("Can't read log: {}", err), &target);
+        }
+        Ok(context) => context,
+    };
+    ui.add_client_msg(
+        &format!(
+            "--- {}, {} ---",
+            search_result_location(result),
+            time::strftime("%Y-%m-%d", &result.line.ts).unwrap()
+        ),
+        &target,
+    );
+    for (idx, line) in lines.iter().enumerate() {
+        let marker = if idx == result_idx { ">" } else { " " };
+        ui.add_msg(&format!("{} {}", marker, line), line.ts, &target);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 static JOIN_CMD: Cmd = Cmd {
     name: "join",
     cmd_fn: join,
@@ -293,7 +1036,7 @@ fn join(args: CmdArgs) {
     }
 
     match find_client(clients, src.serv_name()) {
-        Some(client) => client.join(&words),
+        Some(client) => report_send_err(ui, "JOIN", client.join(&words)),
         None => ui.add_client_err_msg(
             &format!("Can't JOIN: Not connected to server {}", src.serv_name()),
             &MsgTarget::CurrentTab,
@@ -303,6 +1046,63 @@ fn join(args: CmdArgs) {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static LIST_CMD: Cmd = Cmd {
+    name: "list",
+    cmd_fn: list,
+};
+
+/// Parse `/list` arguments: channel masks, `>n` (more than n users) and `<n` (fewer than n users).
+fn parse_list_args(args: &str) -> Option<ListFilter> {
+    let mut filter = ListFilter::default();
+    for arg in args.split(|c: char| c == ',' || c.is_whitespace()) {
+        if arg.is_empty() {
+            continue;
+        }
+        if let Some(n) = arg.strip_prefix('>') {
+            filter.min_users = Some(n.parse().ok()?);
+        } else if let Some(n) = arg.strip_prefix('<') {
+            filter.max_users = Some(n.parse().ok()?);
+        } else {
+            filter.masks.push(arg.to_owned());
+        }
+    }
+    Some(filter)
+}
+
+fn list(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let filter = match parse_list_args(args) {
+        Some(filter) => filter,
+        None => {
+            return ui.add_client_err_msg(
+                "/list usage: /list [>min_users] [<max_users] [chan_mask...]",
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+
+    match find_client(clients, src.serv_name()) {
+        Some(client) => match client.list(&filter) {
+            Ok(()) => ui.add_client_msg("Requesting channel list...", &MsgTarget::CurrentTab),
+            Err(err) => {
+                ui.add_client_err_msg(&format!("Can't send LIST: {}", err), &MsgTarget::CurrentTab)
+            }
+        },
+        None => ui.add_client_err_msg(
+            &format!("Can't LIST: Not connected to server {}", src.serv_name()),
+            &MsgTarget::CurrentTab,
+        ),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 static ME_CMD: Cmd = Cmd {
     name: "me",
     cmd_fn: me,
@@ -452,7 +1252,7 @@ fn nick(args: CmdArgs) {
     if words.len() == 1 {
         if let Some(client) = find_client(clients, src.serv_name()) {
             let new_nick = words[0];
-            client.nick(new_nick);
+            report_send_err(ui, "NICK", client.nick(new_nick));
         }
     } else {
         ui.add_client_err_msg("/nick usage: /nick <nick>", &MsgTarget::CurrentTab);
@@ -461,6 +1261,202 @@ fn nick(args: CmdArgs) {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+static QUOTE_CMD: Cmd = Cmd {
+    name: "quote",
+    cmd_fn: quote,
+};
+
+fn quote(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    if args.trim().is_empty() {
+        return ui.add_client_err_msg("/quote usage: /quote <line>", &MsgTarget::CurrentTab);
+    }
+    match find_client(clients, src.serv_name()) {
+        Some(client) => client.raw_msg(args),
+        None => ui.add_client_err_msg(
+            &format!("Not connected to server {}", src.serv_name()),
+            &MsgTarget::CurrentTab,
+        ),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static RAW_CMD: Cmd = Cmd {
+    name: "raw",
+    cmd_fn: raw,
+};
+
+/// Toggle the raw protocol tab of the current server.
+fn raw(args: CmdArgs) {
+    let CmdArgs {
+        ui, clients, src, ..
+    } = args;
+    let client = match find_client(clients, src.serv_name()) {
+        Some(client) => client,
+        None => {
+            return ui.add_client_err_msg(
+                &format!("Not connected to server {}", src.serv_name()),
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+    let tab_name = raw_tab_name(client.get_serv_name());
+    if client.raw_msg_events() {
+        client.set_raw_msg_events(false);
+        ui.close_server_tab(&tab_name);
+    } else {
+        client.set_raw_msg_events(true);
+        ui.new_server_tab(&tab_name);
+        ui.add_client_msg(
+            "Messages sent to and received from the server will be shown here. \
+             Use `/raw` or `/close` to close this tab.",
+            &MsgTarget::Server { serv: &tab_name },
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static REACT_CMD: Cmd = Cmd {
+    name: "react",
+    cmd_fn: react,
+};
+
+/// Find `msgid` of the last message of `nick` in the channel or privmsg tab `src`. Shows an error
+/// and returns `None` when not found.
+fn find_last_msgid(
+    ui: &Box<dyn UI>,
+    client: &Client,
+    src: &MsgSource,
+    nick: &str,
+    cmd: &str,
+) -> Option<String> {
+    let target = match src {
+        MsgSource::Chan { chan, .. } => chan,
+        MsgSource::User { nick, .. } => nick,
+        MsgSource::Serv { .. } => {
+            ui.add_client_err_msg(
+                &format!("/{} only supported in chan and user tabs", cmd),
+                &MsgTarget::CurrentTab,
+            );
+            return None;
+        }
+    };
+    let msgid = client.get_last_msgid(target, nick);
+    if msgid.is_none() {
+        ui.add_client_err_msg(
+            &format!(
+                "/{}: Can't find a message from {} (server may not support message-tags)",
+                cmd, nick
+            ),
+            &MsgTarget::CurrentTab,
+        );
+    }
+    msgid
+}
+
+fn react(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let words: Vec<&str> = args.split_whitespace().collect();
+    if words.len() != 2 {
+        return ui.add_client_err_msg(
+            "/react usage: /react <nick> <reaction>",
+            &MsgTarget::CurrentTab,
+        );
+    }
+    let (nick, reaction) = (words[0], words[1]);
+
+    let client = match find_client(clients, src.serv_name()) {
+        None => {
+            return;
+        }
+        Some(client) => client,
+    };
+
+    if let Some(msgid) = find_last_msgid(ui, client, &src, nick, "react") {
+        if let Err(err) = client.react(src.visible_name(), &msgid, reaction) {
+            return ui.add_client_err_msg(
+                &format!("Can't send reaction: {}", err),
+                &MsgTarget::CurrentTab,
+            );
+        }
+        ui.add_reaction(
+            &client.get_nick(),
+            reaction,
+            &msgid,
+            time::now(),
+            &src.to_target(),
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+static REPLY_CMD: Cmd = Cmd {
+    name: "reply",
+    cmd_fn: reply,
+};
+
+fn reply(args: CmdArgs) {
+    let CmdArgs {
+        args,
+        ui,
+        clients,
+        src,
+        ..
+    } = args;
+    let (nick, msg) = match split_msg_args(args) {
+        Some((nick, msg)) if !msg.is_empty() => (nick, msg),
+        _ => {
+            return ui.add_client_err_msg(
+                "/reply usage: /reply <nick> <message>",
+                &MsgTarget::CurrentTab,
+            );
+        }
+    };
+
+    let client = match find_client(clients, src.serv_name()) {
+        None => {
+            return;
+        }
+        Some(client) => client,
+    };
+
+    if let Some(msgid) = find_last_msgid(ui, client, &src, nick, "reply") {
+        let target = src.visible_name();
+        let ui_target = src.to_target();
+        let tags = MsgTags {
+            reply_to: Some(&msgid),
+            ..MsgTags::default()
+        };
+        let ts = time::now();
+        for msg in client.split_privmsg(target.len(), msg) {
+            if let Err(err) = client.reply(target, &msgid, msg) {
+                return ui.add_client_err_msg(
+                    &format!("Can't send reply: {}", err),
+                    &MsgTarget::CurrentTab,
+                );
+            }
+            ui.add_privmsg(&client.get_nick(), msg, ts, &ui_target, false, false, tags);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
 /*
 static RELOAD_CMD: Cmd = Cmd {
     name: "reload",
@@ -514,6 +1510,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_list_args() {
+        assert_eq!(parse_list_args(""), Some(ListFilter::default()));
+        assert_eq!(
+            parse_list_args(">10 #rust*,#tiny"),
+            Some(ListFilter {
+                masks: vec!["#rust*".to_owned(), "#tiny".to_owned()],
+                min_users: Some(10),
+                max_users: None,
+            })
+        );
+        assert_eq!(parse_list_args("<foo"), None);
+    }
+
+    #[test]
+    fn test_grep_args() {
+        let query = parse_grep_args("-c #tiny -n bob -since 2019-10-01 https?://\\S+ x").unwrap();
+        assert_eq!(query.pattern.as_str(), "https?://\\S+ x");
+        assert_eq!(query.serv, None);
+        assert_eq!(query.target.as_deref(), Some("#tiny"));
+        assert_eq!(query.nick.as_deref(), Some("bob"));
+        assert_eq!(query.since.as_deref(), Some("2019-10-01"));
+        assert_eq!(query.until, None);
+
+        assert!(parse_grep_args("").is_err());
+        assert!(parse_grep_args("-c #tiny").is_err());
+        assert!(parse_grep_args("-since yesterday foo").is_err());
+        assert!(parse_grep_args("-x foo bar").is_err());
+        assert!(parse_grep_args("foo(").is_err());
+    }
+
+    #[test]
+    fn test_ban_mask() {
+        let user_host = Some("~osa1@host-1.example.com");
+        assert_eq!(
+            ban_mask(BanMaskStyle::Host, "osa1", user_host),
+            "*!*@host-1.example.com"
+        );
+        assert_eq!(
+            ban_mask(BanMaskStyle::UserHost, "osa1", user_host),
+            "*!*osa1@host-1.example.com"
+        );
+        assert_eq!(
+            ban_mask(BanMaskStyle::Domain, "osa1", user_host),
+            "*!*@*.example.com"
+        );
+        assert_eq!(
+            ban_mask(BanMaskStyle::Domain, "osa1", Some("osa1@192.168.1.5")),
+            "*!*@192.168.1.*"
+        );
+        assert_eq!(
+            ban_mask(BanMaskStyle::Domain, "osa1", Some("osa1@user/osa1")),
+            "*!*@user/osa1"
+        );
+        assert_eq!(ban_mask(BanMaskStyle::Nick, "osa1", user_host), "osa1!*@*");
+        assert_eq!(
+            ban_mask(BanMaskStyle::Exact, "osa1", user_host),
+            "osa1!~osa1@host-1.example.com"
+        );
+        assert_eq!(ban_mask(BanMaskStyle::Host, "osa1", None), "osa1!*@*");
+    }
+
     #[test]
     fn test_msg_args() {
         assert_eq!(split_msg_args("foo,bar"), Some(("foo", ",bar")));
@@ -1,4 +1,4 @@
-use std::{env::Args, path::PathBuf};
+use std::path::PathBuf;
 
 pub(crate) struct CmdLineArgs {
     /// Servers to connect to
@@ -6,25 +6,142 @@ pub(crate) struct CmdLineArgs {
 
     /// Path to config file
     pub(crate) config_path: Option<PathBuf>,
+
+    /// Arguments of `tiny log export`. When this is set tiny exports the log and exits.
+    pub(crate) log_export: Option<LogExportArgs>,
+}
+
+/// Arguments of `tiny log export`. See `export::export_log`.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct LogExportArgs {
+    pub(crate) serv: String,
+
+    /// Channel or nick
+    pub(crate) target: String,
+
+    /// Only export lines logged on or after this date (`2019-10-17`)
+    pub(crate) since: Option<String>,
+
+    /// Only export lines logged on or before this date (`2019-10-17`)
+    pub(crate) until: Option<String>,
+
+    /// Where to write the HTML page. Printed to stdout when not available.
+    pub(crate) output: Option<PathBuf>,
 }
 
-pub(crate) fn parse_cmd_line_args(mut args: Args) -> CmdLineArgs {
+pub(crate) static LOG_EXPORT_USAGE: &str = "\
+Usage: tiny [--config <path>] log export [--since <yyyy-mm-dd>] [--until <yyyy-mm-dd>] \
+[-o <file>] <server> <chan or nick>
+
+Exports the log of a channel or private conversation as an HTML page.";
+
+pub(crate) fn parse_cmd_line_args(
+    args: impl Iterator<Item = String>,
+) -> Result<CmdLineArgs, String> {
     let mut parsed_args = CmdLineArgs {
         servers: Vec::new(),
         config_path: None,
+        log_export: None,
     };
 
-    args.next(); // skip program name
+    let mut args = args.skip(1); // skip program name
+
+    // Positional arguments of `tiny log export`
+    let mut positional: Vec<String> = vec![];
 
     while let Some(arg) = args.next() {
         if arg == "--config" {
             if let Some(config_path) = args.next() {
                 parsed_args.config_path = Some(PathBuf::from(config_path));
             }
+        } else if arg == "log" && parsed_args.servers.is_empty() && parsed_args.log_export.is_none()
+        {
+            match args.next().as_deref() {
+                Some("export") => parsed_args.log_export = Some(LogExportArgs::default()),
+                _ => return Err(LOG_EXPORT_USAGE.to_owned()),
+            }
+        } else if let Some(ref mut export_args) = parsed_args.log_export {
+            match arg.as_str() {
+                "--since" | "--until" => {
+                    let date = match args.next() {
+                        Some(date) if time::strptime(&date, "%Y-%m-%d").is_ok() => date,
+                        _ => return Err(format!("Invalid date for {}, use yyyy-mm-dd", arg)),
+                    };
+                    if arg == "--since" {
+                        export_args.since = Some(date);
+                    } else {
+                        export_args.until = Some(date);
+                    }
+                }
+                "-o" => match args.next() {
+                    None => return Err(LOG_EXPORT_USAGE.to_owned()),
+                    Some(output) => export_args.output = Some(PathBuf::from(output)),
+                },
+                _ => positional.push(arg),
+            }
         } else {
             parsed_args.servers.push(arg);
         }
     }
 
-    parsed_args
+    if let Some(ref mut export_args) = parsed_args.log_export {
+        if positional.len() != 2 {
+            return Err(LOG_EXPORT_USAGE.to_owned());
+        }
+        export_args.target = positional.pop().unwrap();
+        export_args.serv = positional.pop().unwrap();
+    }
+
+    Ok(parsed_args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CmdLineArgs, String> {
+        parse_cmd_line_args(
+            std::iter::once("tiny")
+                .chain(args.iter().cloned())
+                .map(str::to_owned),
+        )
+    }
+
+    #[test]
+    fn test_parse_cmd_line_args() {
+        let args = parse(&["--config", "tiny.yml", "freenode", "oftc"]).unwrap();
+        assert_eq!(args.servers, vec!["freenode", "oftc"]);
+        assert_eq!(args.config_path, Some(PathBuf::from("tiny.yml")));
+        assert_eq!(args.log_export, None);
+
+        let args = parse(&[
+            "--config",
+            "tiny.yml",
+            "log",
+            "export",
+            "--since",
+            "2019-10-17",
+            "irc.freenode.net",
+            "#tiny",
+            "-o",
+            "tiny.html",
+        ])
+        .unwrap();
+        assert!(args.servers.is_empty());
+        assert_eq!(args.config_path, Some(PathBuf::from("tiny.yml")));
+        assert_eq!(
+            args.log_export,
+            Some(LogExportArgs {
+                serv: "irc.freenode.net".to_owned(),
+                target: "#tiny".to_owned(),
+                since: Some("2019-10-17".to_owned()),
+                until: None,
+                output: Some(PathBuf::from("tiny.html")),
+            })
+        );
+
+        assert!(parse(&["log", "export", "irc.freenode.net"]).is_err());
+        assert!(parse(&["log", "export", "--until", "yesterday", "serv", "#tiny"]).is_err());
+        assert!(parse(&["log", "cat"]).is_err());
+    }
 }
@@ -1,7 +1,9 @@
 //! To see how color numbers map to actual colors in your terminal run
 //! `cargo run --example colors`. Use tab to swap fg/bg colors.
-use serde::Deserialize;
+use libtiny_client::Encoding;
+use serde::{Deserialize, Deserializer};
 use serde_yaml;
+use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
 use std::io::{Read, Write};
@@ -46,6 +48,71 @@ pub(crate) struct Server {
     /// Authenication method
     #[serde(rename = "sasl")]
     pub(crate) sasl_auth: Option<SASLAuth>,
+
+    /// Encoding to use when a message is not valid UTF-8 (e.g. `latin1`, `cp1251`)
+    #[serde(default, deserialize_with = "deserialize_encoding")]
+    pub(crate) encoding: Option<&'static Encoding>,
+
+    /// Per-channel `encoding`s
+    #[serde(default, deserialize_with = "deserialize_chan_encodings")]
+    pub(crate) chan_encodings: Vec<(String, &'static Encoding)>,
+
+    /// Encoding of messages sent to the server. UTF-8 when not set.
+    #[serde(default, deserialize_with = "deserialize_encoding")]
+    pub(crate) outgoing_encoding: Option<&'static Encoding>,
+
+    /// Connect over WebSocket, to the given path (e.g. `/webirc`). `wss://` is used when `tls` is
+    /// set.
+    #[serde(default)]
+    pub(crate) websocket: Option<String>,
+
+    /// Connect to a Unix domain socket at this path instead of `addr` and `port` (e.g. a local
+    /// bouncer). `addr` is still used as the server name.
+    #[serde(default)]
+    pub(crate) unix_socket: Option<PathBuf>,
+
+    /// Log this server. Only used when `log_dir` is set.
+    #[serde(default = "default_log")]
+    pub(crate) log: bool,
+
+    /// Channels and nicks to log, as glob patterns (e.g. `#tiny*`). Everything is logged when
+    /// empty.
+    #[serde(default)]
+    pub(crate) log_allow: Vec<String>,
+
+    /// Channels and nicks to never log, as glob patterns. Takes precedence over `log_allow`.
+    #[serde(default)]
+    pub(crate) log_deny: Vec<String>,
+}
+
+fn default_log() -> bool {
+    true
+}
+
+fn parse_encoding<E: serde::de::Error>(label: &str) -> Result<&'static Encoding, E> {
+    Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| E::custom(format!("unknown encoding: {}", label)))
+}
+
+fn deserialize_encoding<'de, D>(deserializer: D) -> Result<Option<&'static Encoding>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let label: Option<String> = Deserialize::deserialize(deserializer)?;
+    label.map(|label| parse_encoding(&label)).transpose()
+}
+
+fn deserialize_chan_encodings<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(String, &'static Encoding)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let labels: HashMap<String, String> = Deserialize::deserialize(deserializer)?;
+    labels
+        .into_iter()
+        .map(|(chan, label)| Ok((chan, parse_encoding(&label)?)))
+        .collect()
 }
 
 /// Similar to `Server`, but used when connecting via the `/connect` command.
@@ -57,6 +124,27 @@ pub(crate) struct Defaults {
     pub(crate) join: Vec<String>,
     #[serde(default)]
     pub(crate) tls: bool,
+
+    /// Mask style used by `/ban`, `/quiet` etc. when given a nick. Used in all servers.
+    #[serde(default)]
+    pub(crate) ban_mask: BanMaskStyle,
+}
+
+/// How to generate a ban mask from a nick's `nick!user@host`.
+#[derive(Clone, Copy, Default, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BanMaskStyle {
+    /// `*!*@host`
+    #[default]
+    Host,
+    /// `*!*user@host`
+    UserHost,
+    /// `*!*@*.example.com`, or `*!*@1.2.3.*` for IPv4 addresses. Same as `Host` for cloaks.
+    Domain,
+    /// `nick!*@*`
+    Nick,
+    /// `nick!user@host`
+    Exact,
 }
 
 #[derive(Deserialize)]
@@ -66,6 +154,8 @@ pub(crate) struct Config {
     #[serde(default)]
     pub(crate) colors: libtiny_tui::Colors,
     pub(crate) log_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub(crate) logger: libtiny_logger::Config,
     pub(crate) tsmsg: bool,
 }
 
@@ -174,4 +264,48 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_ban_mask() {
+        let defaults = |ban_mask: &str| -> Result<Defaults, serde_yaml::Error> {
+            serde_yaml::from_str(&format!("nicks: [tiny]\nrealname: tiny\n{}", ban_mask))
+        };
+        assert_eq!(defaults("").unwrap().ban_mask, BanMaskStyle::Host);
+        assert_eq!(
+            defaults("ban_mask: user_host").unwrap().ban_mask,
+            BanMaskStyle::UserHost
+        );
+        assert!(defaults("ban_mask: foo").is_err());
+    }
+
+    #[test]
+    fn parse_encodings() {
+        let server = |encodings: &str| -> Result<Server, serde_yaml::Error> {
+            serde_yaml::from_str(&format!(
+                "addr: irc.example.com\nport: 6667\nrealname: tiny\nnicks: [tiny]\n{}",
+                encodings
+            ))
+        };
+
+        let serv = server("").unwrap();
+        assert_eq!(serv.encoding, None);
+        assert!(serv.chan_encodings.is_empty());
+        assert_eq!(serv.outgoing_encoding, None);
+
+        let serv = server(
+            "encoding: latin1\nchan_encodings:\n    '#russian': cp1251\noutgoing_encoding: utf-8",
+        )
+        .unwrap();
+        assert_eq!(serv.encoding, Encoding::for_label(b"windows-1252"));
+        assert_eq!(
+            serv.chan_encodings,
+            vec![(
+                "#russian".to_owned(),
+                Encoding::for_label(b"windows-1251").unwrap()
+            )]
+        );
+        assert_eq!(serv.outgoing_encoding, Encoding::for_label(b"utf-8"));
+
+        assert!(server("encoding: foo").is_err());
+    }
 }
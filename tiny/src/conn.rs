@@ -5,7 +5,7 @@
 
 use futures_util::stream::StreamExt;
 use libtiny_client::Client;
-use libtiny_ui::{MsgTarget, TabStyle, UI};
+use libtiny_ui::{ChanEvent, ChanListEntry, MsgTags, MsgTarget, TabStyle, UI};
 use libtiny_wire as wire;
 use std::error::Error;
 use tokio::sync::mpsc;
@@ -42,6 +42,14 @@ fn handle_conn_ev(ui: &dyn UI, client: &Client, ev: libtiny_client::Event) -> bo
                 },
             );
         }
+        ConnectingUnix(path) => {
+            ui.add_client_msg(
+                &format!("Connecting to {}", path.display()),
+                &MsgTarget::AllServTabs {
+                    serv: client.get_serv_name(),
+                },
+            );
+        }
         Connected => {
             ui.add_msg(
                 "Connected.",
@@ -102,6 +110,39 @@ fn handle_conn_ev(ui: &dyn UI, client: &Client, ev: libtiny_client::Event) -> bo
         NickChange(new_nick) => {
             ui.set_nick(client.get_serv_name(), &new_nick);
         }
+        ChanList(chans) => {
+            let chans: Vec<ChanListEntry> = chans
+                .into_iter()
+                .map(|chan| ChanListEntry {
+                    chan: chan.chan,
+                    n_users: chan.n_users,
+                    topic: chan.topic,
+                })
+                .collect();
+            ui.show_chan_list(client.get_serv_name(), &chans);
+        }
+        ModeList {
+            chan,
+            mode,
+            entries,
+        } => {
+            let serv = client.get_serv_name();
+            let target = chan_or_serv_target(client, serv, &chan);
+            if entries.is_empty() {
+                ui.add_client_msg(&format!("{} {} is empty", chan, mode.name()), &target);
+            } else {
+                ui.add_client_msg(
+                    &format!("{} {} ({} entries):", chan, mode.name(), entries.len()),
+                    &target,
+                );
+                for entry in entries {
+                    ui.add_client_msg(&mode_list_entry_text(&entry), &target);
+                }
+            }
+        }
+        RawMsg { msg, outgoing } => {
+            ui.add_raw_msg(client.get_serv_name(), &msg, time::now(), outgoing);
+        }
         Msg(msg) => {
             handle_irc_msg(ui, client, msg);
         }
@@ -116,12 +157,12 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
     use wire::Cmd::*;
     use wire::Pfx::*;
 
-    let wire::Msg { pfx, cmd } = msg;
-    let ts = time::now();
+    let wire::Msg { tags, pfx, cmd } = msg;
+    let ts = msg_ts(&tags);
     let serv = client.get_serv_name();
     match cmd {
         PRIVMSG {
-            target,
+            targets,
             msg,
             is_notice,
             ctcp,
@@ -154,53 +195,102 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             }
 
             let is_action = ctcp == Some(wire::CTCP::Action);
+            let msg_tags = MsgTags {
+                msgid: wire::get_tag(&tags, "msgid"),
+                reply_to: wire::get_tag(&tags, "+draft/reply"),
+                is_notice,
+                peer: None,
+            };
 
-            match target {
-                wire::MsgTarget::Chan(chan) => {
-                    let ui_msg_target = MsgTarget::Chan { serv, chan: &chan };
-                    // highlight the message if it mentions us
-                    if msg.find(&client.get_nick()).is_some() {
-                        ui.add_privmsg(origin, &msg, ts, &ui_msg_target, true, is_action);
-                        ui.set_tab_style(TabStyle::Highlight, &ui_msg_target);
-                        let mentions_target = MsgTarget::Server { serv: "mentions" };
-                        ui.add_msg(
-                            &format!("{} in {}:{}: {}", origin, serv, chan, msg),
-                            ts,
-                            &mentions_target,
-                        );
-                        ui.set_tab_style(TabStyle::Highlight, &mentions_target);
-                    } else {
-                        ui.add_privmsg(origin, &msg, ts, &ui_msg_target, false, is_action);
-                        ui.set_tab_style(TabStyle::NewMsg, &ui_msg_target);
+            for target in targets {
+                match target {
+                    wire::MsgTarget::Chan(chan) => {
+                        let ui_msg_target = MsgTarget::Chan { serv, chan: &chan };
+                        // highlight the message if it mentions us
+                        if msg.find(&client.get_nick()).is_some() {
+                            ui.add_privmsg(
+                                origin,
+                                &msg,
+                                ts,
+                                &ui_msg_target,
+                                true,
+                                is_action,
+                                msg_tags,
+                            );
+                            ui.set_tab_style(TabStyle::Highlight, &ui_msg_target);
+                            ui.add_mention(origin, &msg, ts, serv, &chan);
+                        } else {
+                            ui.add_privmsg(
+                                origin,
+                                &msg,
+                                ts,
+                                &ui_msg_target,
+                                false,
+                                is_action,
+                                msg_tags,
+                            );
+                            ui.set_tab_style(TabStyle::NewMsg, &ui_msg_target);
+                        }
                     }
-                }
-                wire::MsgTarget::User(target) => {
-                    let msg_target = {
-                        match pfx {
-                            Server(_) => MsgTarget::Server { serv },
-                            User { ref nick, .. } => {
-                                // show NOTICE messages in server tabs if we don't have a tab
-                                // for the sender already (see #21)
-                                if is_notice && !ui.user_tab_exists(serv, nick) {
-                                    MsgTarget::Server { serv }
-                                } else {
-                                    MsgTarget::User { serv, nick }
+                    wire::MsgTarget::User(target) => {
+                        let mut msg_tags = msg_tags;
+                        let msg_target = {
+                            match pfx {
+                                Server(_) => MsgTarget::Server { serv },
+                                User { ref nick, .. } => {
+                                    // show NOTICE messages in server tabs if we don't have a tab
+                                    // for the sender already (see #21)
+                                    if is_notice && !ui.user_tab_exists(serv, nick) {
+                                        msg_tags.peer = Some(nick);
+                                        MsgTarget::Server { serv }
+                                    } else {
+                                        ui.new_user_tab(serv, nick);
+                                        MsgTarget::User { serv, nick }
+                                    }
                                 }
                             }
+                        };
+                        ui.add_privmsg(origin, &msg, ts, &msg_target, false, is_action, msg_tags);
+                        if target == client.get_nick() {
+                            ui.set_tab_style(TabStyle::Highlight, &msg_target);
+                        } else {
+                            // not sure if this case can happen
+                            ui.set_tab_style(TabStyle::NewMsg, &msg_target);
                         }
-                    };
-                    ui.add_privmsg(origin, &msg, ts, &msg_target, false, is_action);
-                    if target == client.get_nick() {
-                        ui.set_tab_style(TabStyle::Highlight, &msg_target);
-                    } else {
-                        // not sure if this case can happen
-                        ui.set_tab_style(TabStyle::NewMsg, &msg_target);
                     }
                 }
             }
         }
 
-        JOIN { chan } => {
+        TAGMSG { target } => {
+            let nick = match pfx {
+                Some(User { nick, .. }) => nick,
+                _ => {
+                    // TODO: log this?
+                    return;
+                }
+            };
+            // Only reactions are supported for now
+            let (reaction, msgid) = match (
+                wire::get_tag(&tags, "+draft/react"),
+                wire::get_tag(&tags, "+draft/reply"),
+            ) {
+                (Some(reaction), Some(msgid)) => (reaction, msgid),
+                _ => {
+                    return;
+                }
+            };
+            let msg_target = match target {
+                wire::MsgTarget::Chan(ref chan) => MsgTarget::Chan { serv, chan },
+                wire::MsgTarget::User(_) => {
+                    ui.new_user_tab(serv, &nick);
+                    MsgTarget::User { serv, nick: &nick }
+                }
+            };
+            ui.add_reaction(&nick, reaction, msgid, ts, &msg_target);
+        }
+
+        JOIN { chans, .. } => {
             let nick = match pfx {
                 Some(User { nick, .. }) => nick,
                 _ => {
@@ -210,11 +300,15 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             };
 
             if nick == client.get_nick() {
-                ui.new_chan_tab(serv, &chan);
+                for chan in &chans {
+                    ui.new_chan_tab(serv, chan);
+                }
             } else {
                 let nick = wire::drop_nick_prefix(&nick);
-                let ts = Some(time::now());
-                ui.add_nick(nick, ts, &MsgTarget::Chan { serv, chan: &chan });
+                let ts = Some(ts);
+                for chan in &chans {
+                    ui.add_nick(nick, ts, &MsgTarget::Chan { serv, chan });
+                }
                 // Also update the private message tab if it exists
                 // Nothing will be shown if the user already known to be online by the tab
                 if ui.user_tab_exists(serv, nick) {
@@ -223,7 +317,7 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             }
         }
 
-        PART { chan, .. } => {
+        PART { chans, .. } => {
             let nick = match pfx {
                 Some(User { nick, .. }) => nick,
                 _ => {
@@ -232,11 +326,9 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
                 }
             };
             if nick != client.get_nick() {
-                ui.remove_nick(
-                    &nick,
-                    Some(time::now()),
-                    &MsgTarget::Chan { serv, chan: &chan },
-                );
+                for chan in &chans {
+                    ui.remove_nick(&nick, Some(ts), &MsgTarget::Chan { serv, chan });
+                }
             }
         }
 
@@ -250,10 +342,10 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             };
 
             for chan in &chans {
-                ui.remove_nick(nick, Some(time::now()), &MsgTarget::Chan { serv, chan });
+                ui.remove_nick(nick, Some(ts), &MsgTarget::Chan { serv, chan });
             }
             if ui.user_tab_exists(serv, nick) {
-                ui.remove_nick(nick, Some(time::now()), &MsgTarget::User { serv, nick });
+                ui.remove_nick(nick, Some(ts), &MsgTarget::User { serv, nick });
             }
         }
 
@@ -267,18 +359,13 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             };
 
             for chan in &chans {
-                ui.rename_nick(
-                    &old_nick,
-                    &nick,
-                    time::now(),
-                    &MsgTarget::Chan { serv, chan },
-                );
+                ui.rename_nick(&old_nick, &nick, ts, &MsgTarget::Chan { serv, chan });
             }
             if ui.user_tab_exists(serv, &old_nick) {
                 ui.rename_nick(
                     &old_nick,
                     &nick,
-                    time::now(),
+                    ts,
                     &MsgTarget::User {
                         serv,
                         nick: &old_nick,
@@ -293,7 +380,7 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
                 // Nick change request from user failed. Just show an error message.
                 ui.add_err_msg(
                     "Nickname is already in use",
-                    time::now(),
+                    ts,
                     &MsgTarget::AllServTabs { serv },
                 );
             }
@@ -304,11 +391,18 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
         }
 
         ERROR { msg } => {
-            ui.add_err_msg(&msg, time::now(), &MsgTarget::AllServTabs { serv });
+            ui.add_err_msg(&msg, ts, &MsgTarget::AllServTabs { serv });
         }
 
         TOPIC { chan, topic } => {
-            ui.set_topic(&topic, time::now(), serv, &chan);
+            ui.set_topic(&topic, ts, serv, &chan);
+            if let Some(User { nick, .. }) = pfx {
+                let event = ChanEvent::TopicSetBy {
+                    setter: &nick,
+                    set_at: None,
+                };
+                ui.add_chan_event(event, ts, serv, &chan);
+            }
         }
 
         CAP {
@@ -322,17 +416,17 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
                         let msg_target = MsgTarget::Server { serv };
                         ui.add_err_msg(
                             "Server rejected using SASL authenication capability",
-                            time::now(),
+                            ts,
                             &msg_target,
                         );
                     }
                 }
                 "LS" => {
-                    if !params.iter().any(|cap| cap.as_str() == "sasl") {
+                    if client.has_sasl_auth() && !params.iter().any(|cap| cap.as_str() == "sasl") {
                         let msg_target = MsgTarget::Server { serv };
                         ui.add_err_msg(
                             "Server does not support SASL authenication",
-                            time::now(),
+                            ts,
                             &msg_target,
                         );
                     }
@@ -350,115 +444,295 @@ fn handle_irc_msg(ui: &dyn UI, client: &Client, msg: wire::Msg) {
             // Ignore
         }
 
-        Reply { num: n, params } => {
-            if n <= 003 /* RPL_WELCOME, RPL_YOURHOST, RPL_CREATED */
-                    || n == 251 /* RPL_LUSERCLIENT */
-                    || n == 255 /* RPL_LUSERME */
-                    || n == 372 /* RPL_MOTD */
-                    || n == 375 /* RPL_MOTDSTART */
-                    || n == 376
-            /* RPL_ENDOFMOTD */
-            {
-                debug_assert_eq!(params.len(), 2);
-                let msg = &params[1];
-                ui.add_msg(msg, time::now(), &MsgTarget::Server { serv });
-            } else if n == 4 // RPL_MYINFO
-                    || n == 5 // RPL_BOUNCE
-                    || (n >= 252 && n <= 254)
-            /* RPL_LUSEROP, RPL_LUSERUNKNOWN, */
-            /* RPL_LUSERCHANNELS */
-            {
-                let msg = params.into_iter().collect::<Vec<String>>().join(" ");
-                ui.add_msg(&msg, time::now(), &MsgTarget::Server { serv });
-            } else if n == 265 || n == 266 || n == 250 {
-                let msg = &params[params.len() - 1];
-                ui.add_msg(msg, time::now(), &MsgTarget::Server { serv });
+        Reply { num, params } => {
+            handle_reply(ui, client, num, params, ts);
+        }
+
+        Other {
+            ref cmd,
+            ref params,
+        } if cmd == "KICK" && params.len() >= 2 => {
+            let event = ChanEvent::Kick {
+                kicker: pfx_name(&pfx, serv),
+                nick: &params[1],
+                reason: params.get(2).map(String::as_str),
+            };
+            ui.add_chan_event(event, ts, serv, &params[0]);
+        }
+
+        // Only channel modes are shown
+        Other {
+            ref cmd,
+            ref params,
+        } if cmd == "MODE" && params.len() >= 2 && client.is_on_chan(&params[0]) => {
+            let modes = params[1..].join(" ");
+            let event = ChanEvent::Mode {
+                setter: pfx_name(&pfx, serv),
+                modes: &modes,
+            };
+            ui.add_chan_event(event, ts, serv, &params[0]);
+        }
+
+        Other { cmd: _, params } => match pfx {
+            Some(Server(msg_serv)) => {
+                let msg_target = MsgTarget::Server { serv };
+                ui.add_privmsg(
+                    &msg_serv,
+                    &params.join(" "),
+                    ts,
+                    &msg_target,
+                    false,
+                    false,
+                    MsgTags::default(),
+                );
+                ui.set_tab_style(TabStyle::NewMsg, &msg_target);
             }
+            _pfx => {
+                // self.logger.get_debug_logs().write_line(format_args!(
+                //     "Ignoring msg:\nPfx: {:?}, msg: {} :{}",
+                //     pfx,
+                //     cmd,
+                //     params.join(" "),
+                // ));
+            }
+        },
+    }
+}
+
+/// Nick or server name in a message prefix. `serv` is used for messages without a prefix.
+fn pfx_name<'a>(pfx: &'a Option<wire::Pfx>, serv: &'a str) -> &'a str {
+    match pfx {
+        Some(wire::Pfx::User { nick, .. }) => nick,
+        Some(wire::Pfx::Server(msg_serv)) => msg_serv,
+        None => serv,
+    }
+}
+
+/// Time of a message: the `time` tag (IRCv3 `server-time`) when available, current time otherwise.
+/// `time` tags look like `2019-10-17T18:30:00.000Z`.
+fn msg_ts(tags: &[wire::Tag]) -> time::Tm {
+    wire::get_tag(tags, "time")
+        .and_then(|ts| time::strptime(ts.get(..19)?, "%Y-%m-%dT%H:%M:%S").ok())
+        .map(|tm| time::at(tm.to_timespec()))
+        .unwrap_or_else(time::now)
+}
+
+/// Show a numeric reply. Where and how the reply is shown depends on the numeric's category and
+/// whether it's an error. See `wire::numeric`. `ts` is the time of the reply (`msg_ts`).
+fn handle_reply(ui: &dyn UI, client: &Client, num: u16, params: Vec<String>, ts: time::Tm) {
+    use wire::NumericCategory::*;
+
+    let serv = client.get_serv_name();
+    let numeric = wire::numeric(num);
+    let is_error = numeric.map(|numeric| numeric.is_error()).unwrap_or(false);
+    let category = numeric.map(|numeric| numeric.category);
+
+    // Second parameter, after our nick. Usually the channel or nick the reply is about.
+    let subject = params.get(1).map(String::as_str).unwrap_or("");
+
+    let msg_target = match category {
+        Some(Topic) => match num {
             // RPL_TOPIC
-            else if n == 332 {
+            332 if params.len() >= 2 => {
                 // FIXME: RFC 2812 says this will have 2 arguments, but freenode
                 // sends 3 arguments (extra one being our nick).
-                assert!(params.len() == 3 || params.len() == 2);
                 let chan = &params[params.len() - 2];
                 let topic = &params[params.len() - 1];
-                ui.set_topic(topic, time::now(), serv, chan);
+                ui.set_topic(topic, ts, serv, chan);
+                return;
             }
+            // RPL_TOPICWHOTIME
+            333 if params.len() >= 4 => {
+                let set_at = params[3]
+                    .parse::<i64>()
+                    .map(|secs| time::at(time::Timespec::new(secs, 0)))
+                    .ok();
+                let event = ChanEvent::TopicSetBy {
+                    setter: &params[2],
+                    set_at,
+                };
+                ui.add_chan_event(event, ts, serv, subject);
+                return;
+            }
+            _ => chan_or_serv_target(client, serv, subject),
+        },
+
+        Some(Names) => {
             // RPL_NAMREPLY: List of users in a channel
-            else if n == 353 {
+            if num == 353 && params.len() >= 4 {
                 let chan = &params[2];
                 let chan_target = MsgTarget::Chan { serv, chan };
-
                 for nick in params[3].split_whitespace() {
                     ui.add_nick(wire::drop_nick_prefix(nick), None, &chan_target);
                 }
             }
-            // RPL_ENDOFNAMES: End of NAMES list
-            else if n == 366 {
-            }
-            // RPL_UNAWAY or RPL_NOWAWAY
-            else if n == 305 || n == 306 {
-                let msg = &params[1];
-                ui.add_client_msg(msg, &MsgTarget::AllServTabs { serv });
-            }
-            // ERR_NOSUCHNICK
-            else if n == 401 {
-                let nick = &params[1];
-                let msg = &params[2];
-                ui.add_client_msg(msg, &MsgTarget::User { serv, nick });
+            // RPL_ENDOFNAMES is ignored
+            return;
+        }
+
+        Some(Away) => match num {
             // RPL_AWAY
-            } else if n == 301 {
+            301 if params.len() >= 3 => {
                 let nick = &params[1];
                 let msg = &params[2];
+                ui.new_user_tab(serv, nick);
                 ui.add_client_msg(
                     &format!("{} is away: {}", nick, msg),
                     &MsgTarget::User { serv, nick },
                 );
-            } else {
-                match pfx {
-                    Some(Server(msg_serv)) => {
-                        let msg_target = MsgTarget::Server { serv };
-                        ui.add_privmsg(
-                            &msg_serv,
-                            &params.join(" "),
-                            time::now(),
-                            &msg_target,
-                            false,
-                            false,
-                        );
-                        ui.set_tab_style(TabStyle::NewMsg, &msg_target);
-                    }
-                    _pfx => {
-                        // add everything else to debug file
-                        // self.logger.get_debug_logs().write_line(format_args!(
-                        //     "Ignoring numeric reply msg:\nPfx: {:?}, num: {:?}, args: {:?}",
-                        //     pfx, n, params
-                        // ));
-                    }
+                return;
+            }
+            // RPL_UNAWAY, RPL_NOWAWAY
+            _ => {
+                if let Some(msg) = params.last() {
+                    ui.add_client_msg(msg, &MsgTarget::AllServTabs { serv });
                 }
+                return;
             }
-        }
+        },
 
-        Other { cmd: _, params } => match pfx {
-            Some(Server(msg_serv)) => {
-                let msg_target = MsgTarget::Server { serv };
-                ui.add_privmsg(
-                    &msg_serv,
-                    &params.join(" "),
-                    time::now(),
-                    &msg_target,
-                    false,
-                    false,
-                );
-                ui.set_tab_style(TabStyle::NewMsg, &msg_target);
+        // LIST replies are collected by the client, shown on `ChanList` event
+        Some(List) => return,
+
+        Some(Whois) | Some(Who) => user_or_serv_target(ui, serv, subject),
+
+        Some(Chan) => chan_or_serv_target(client, serv, subject),
+
+        // Mode lists are collected by the client, shown on `ModeList` event
+        Some(ModeList) => return,
+
+        // ERR_NOSUCHNICK: usually a reply to a privmsg, show it in the user tab
+        Some(Error) if num == 401 && !subject.is_empty() => {
+            ui.new_user_tab(serv, subject);
+            MsgTarget::User {
+                serv,
+                nick: subject,
             }
-            _pfx => {
-                // self.logger.get_debug_logs().write_line(format_args!(
-                //     "Ignoring msg:\nPfx: {:?}, msg: {} :{}",
-                //     pfx,
-                //     cmd,
-                //     params.join(" "),
-                // ));
+        }
+
+        Some(Error) => {
+            // Some errors about channel operations have the channel as the third parameter, e.g.
+            // ERR_USERNOTINCHANNEL (441): `<client> <nick> <channel> :...`
+            let chan = params.get(2).map(String::as_str).unwrap_or("");
+            if client.is_on_chan(subject) {
+                MsgTarget::Chan {
+                    serv,
+                    chan: subject,
+                }
+            } else if params.len() > 3 && client.is_on_chan(chan) {
+                MsgTarget::Chan { serv, chan }
+            } else {
+                user_or_serv_target(ui, serv, subject)
             }
-        },
+        }
+
+        Some(Welcome) | Some(Motd) | Some(Stats) | Some(Info) | Some(Monitor) | Some(Sasl)
+        | None => MsgTarget::Server { serv },
+    };
+
+    let msg = reply_text(&params, is_error);
+    if is_error {
+        ui.add_err_msg(&msg, ts, &msg_target);
+    } else {
+        ui.add_msg(&msg, ts, &msg_target);
+    }
+
+    // Replies shown on connection registration are not interesting enough to highlight the tab
+    match category {
+        Some(Welcome) | Some(Motd) | Some(Stats) => {}
+        _ => ui.set_tab_style(TabStyle::NewMsg, &msg_target),
+    }
+}
+
+/// Text of a reply to show in the UI. The first parameter (our nick) is skipped. In errors the
+/// parameters are separated from the error message with a colon: "#chan: You're not channel
+/// operator".
+fn reply_text(params: &[String], is_error: bool) -> String {
+    let params = params.get(1..).unwrap_or(&[]);
+    match params.split_last() {
+        Some((msg, args)) if is_error && !args.is_empty() => {
+            format!("{}: {}", args.join(" "), msg)
+        }
+        _ => params.join(" "),
+    }
+}
+
+fn mode_list_entry_text(entry: &libtiny_client::ModeListEntry) -> String {
+    let set_at = entry
+        .set_at
+        .map(|secs| time::at(time::Timespec::new(secs, 0)));
+    match (&entry.set_by, set_at) {
+        (None, _) => entry.mask.clone(),
+        (Some(set_by), None) => format!("{} (set by {})", entry.mask, set_by),
+        (Some(set_by), Some(set_at)) => format!(
+            "{} (set by {} on {})",
+            entry.mask,
+            set_by,
+            set_at.strftime("%F %T").unwrap()
+        ),
+    }
+}
+
+fn chan_or_serv_target<'a>(client: &Client, serv: &'a str, chan: &'a str) -> MsgTarget<'a> {
+    if client.is_on_chan(chan) {
+        MsgTarget::Chan { serv, chan }
+    } else {
+        MsgTarget::Server { serv }
+    }
+}
+
+fn user_or_serv_target<'a>(ui: &dyn UI, serv: &'a str, nick: &'a str) -> MsgTarget<'a> {
+    if !nick.is_empty() && ui.user_tab_exists(serv, nick) {
+        MsgTarget::User { serv, nick }
+    } else {
+        MsgTarget::Server { serv }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(params: &[&str]) -> Vec<String> {
+        params.iter().map(|param| (*param).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_reply_text() {
+        assert_eq!(
+            reply_text(&strs(&["tiny", "Welcome to the network tiny"]), false),
+            "Welcome to the network tiny"
+        );
+        assert_eq!(
+            reply_text(
+                &strs(&["tiny", "CHANTYPES=#", "NICKLEN=30", "are supported"]),
+                false
+            ),
+            "CHANTYPES=# NICKLEN=30 are supported"
+        );
+        assert_eq!(
+            reply_text(
+                &strs(&["tiny", "#tiny", "You're not channel operator"]),
+                true
+            ),
+            "#tiny: You're not channel operator"
+        );
+        assert_eq!(
+            reply_text(&strs(&["tiny", "You have not registered"]), true),
+            "You have not registered"
+        );
+        assert_eq!(reply_text(&[], false), "");
+    }
+
+    #[test]
+    fn test_msg_ts() {
+        let tags = vec![wire::Tag {
+            key: "time".to_owned(),
+            value: "2019-10-17T18:30:00.123Z".to_owned(),
+        }];
+        assert_eq!(
+            msg_ts(&tags).to_timespec(),
+            time::Timespec::new(1571337000, 0)
+        );
     }
 }
@@ -0,0 +1,173 @@
+//! `tiny log export`: exporting logs as self-contained HTML pages.
+
+use libtiny_logger::{search_logs, Config, EventType, LogLine, SearchQuery};
+use libtiny_tui::html::{irc_to_html, nick_color, termbox_color};
+use libtiny_tui::Colors;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::cmd_line_args::LogExportArgs;
+
+/// Export a log as an HTML page, to the output file or stdout.
+pub(crate) fn export_log(
+    args: &LogExportArgs,
+    log_dir: &Path,
+    config: &Config,
+    colors: &Colors,
+) -> Result<(), String> {
+    // Empty pattern matches all lines
+    let mut query = SearchQuery::new("").unwrap();
+    query.serv = Some(args.serv.clone());
+    query.target = Some(args.target.clone());
+    query.since = args.since.clone();
+    query.until = args.until.clone();
+
+    let mut decrypter = config.decrypter();
+    let lines: Vec<LogLine> = search_logs(log_dir, &query, usize::MAX, decrypter.as_mut())
+        .map_err(|err| format!("Can't read logs: {}", err))?
+        .into_iter()
+        .map(|result| result.line)
+        .collect();
+    if lines.is_empty() {
+        return Err(format!(
+            "Can't find logs of {} in {}",
+            args.target, args.serv
+        ));
+    }
+
+    let title = format!("{} on {}", args.target, args.serv);
+    let html = render_html(&title, &lines, colors);
+    match args.output {
+        None => io::stdout().write_all(html.as_bytes()),
+        Some(ref output) => fs::write(output, html),
+    }
+    .map_err(|err| format!("Can't write HTML: {}", err))
+}
+
+/// Render lines of a log as an HTML page, in the colors of the TUI. The page has the default
+/// colors of xterm, as the default TUI colors are for dark backgrounds.
+fn render_html(title: &str, lines: &[LogLine], colors: &Colors) -> String {
+    let faded = termbox_color(colors.faded.fg).unwrap_or_else(|| "gray".to_owned());
+    let timestamp = termbox_color(colors.timestamp.fg).unwrap_or_else(|| "gray".to_owned());
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>{title}</title>\n\
+         <style>\n\
+         body {{ font-family: monospace; background-color: #000000; color: #e5e5e5; }}\n\
+         .line {{ white-space: pre-wrap; }}\n\
+         .date {{ font-weight: bold; margin: 1em 0; }}\n\
+         .ts {{ color: {timestamp}; }}\n\
+         .event {{ color: {faded}; }}\n\
+         .highlight {{ font-weight: bold; }}\n\
+         </style>\n\
+         </head>\n\
+         <body>\n\
+         <h1>{title}</h1>\n",
+        title = irc_to_html(title),
+        timestamp = timestamp,
+        faded = faded,
+    );
+
+    let mut last_date: Option<String> = None;
+    for line in lines {
+        let date = time::strftime("%Y-%m-%d", &line.ts).unwrap();
+        if last_date.as_ref() != Some(&date) {
+            html.push_str(&format!("<div class=\"date\">{}</div>\n", date));
+            last_date = Some(date);
+        }
+        html.push_str(&format!(
+            "<div class=\"line\"><span class=\"ts\">{}</span> {}</div>\n",
+            time::strftime("%H:%M:%S", &line.ts).unwrap(),
+            render_line(line, colors)
+        ));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_line(line: &LogLine, colors: &Colors) -> String {
+    let sender = match (line.ty, &line.sender) {
+        (EventType::Privmsg, Some(sender)) | (EventType::Notice, Some(sender)) => sender,
+        _ => {
+            return format!(
+                "<span class=\"event\">{}</span>",
+                irc_to_html(&line.to_string())
+            )
+        }
+    };
+
+    let nick = match nick_color(colors, sender) {
+        None => irc_to_html(sender),
+        Some(color) => format!(
+            "<span style=\"color: {}\">{}</span>",
+            color,
+            irc_to_html(sender)
+        ),
+    };
+    let text = if line.highlight {
+        format!(
+            "<span class=\"highlight\">{}</span>",
+            irc_to_html(&line.text)
+        )
+    } else {
+        irc_to_html(&line.text)
+    };
+
+    if line.ty == EventType::Notice {
+        format!("-{}- {}", nick, text)
+    } else if line.is_action {
+        format!("** {} {}", nick, text)
+    } else {
+        format!("{}: {}", nick, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_html() {
+        let colors = Colors::default();
+        let ts = time::strptime("2019-10-17 18:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let line = |ty, sender: Option<&str>, text: &str| LogLine {
+            ts,
+            ty,
+            sender: sender.map(str::to_owned),
+            text: text.to_owned(),
+            is_action: false,
+            highlight: false,
+            msgid: None,
+        };
+        let lines = vec![
+            line(EventType::Join, Some("osa1"), ""),
+            line(EventType::Privmsg, Some("osa1"), "see \x02<this>\x02"),
+            LogLine {
+                is_action: true,
+                ..line(EventType::Privmsg, Some("osa1"), "waves")
+            },
+        ];
+        let html = render_html("#tiny on irc.freenode.net", &lines, &colors);
+
+        let osa1 = format!(
+            "<span style=\"color: {}\">osa1</span>",
+            nick_color(&colors, "osa1").unwrap()
+        );
+        assert!(html.contains("<title>#tiny on irc.freenode.net</title>"));
+        assert!(html.contains("<div class=\"date\">2019-10-17</div>"));
+        assert!(html.contains(
+            "<span class=\"ts\">18:30:00</span> <span class=\"event\">osa1 joined the channel.</span>"
+        ));
+        assert!(html.contains(&format!(
+            "{}: see <span style=\"font-weight: bold\">&lt;this&gt;</span></div>",
+            osa1
+        )));
+        assert!(html.contains(&format!("** {} waves</div>", osa1)));
+    }
+}
@@ -7,21 +7,32 @@ mod cmd;
 mod cmd_line_args;
 mod config;
 mod conn;
+mod export;
 mod ui;
 mod utils;
 
 use cmd_line_args::{parse_cmd_line_args, CmdLineArgs};
+use futures_util::stream::StreamExt;
 use libtiny_client::{Client, ServerInfo};
-use libtiny_logger::Logger;
+use libtiny_logger::{LogEnd, Logger};
 use libtiny_tui::{Colors, MsgTarget, TUI};
-use libtiny_ui::UI;
+use libtiny_ui::{MENTIONS_TAB, UI};
+use std::io::{self, Write};
 use std::path::PathBuf;
+use time::Tm;
 
 fn main() {
     let CmdLineArgs {
         servers: server_args,
         config_path,
-    } = parse_cmd_line_args(std::env::args());
+        log_export,
+    } = match parse_cmd_line_args(std::env::args()) {
+        Err(err) => {
+            eprintln!("{}", err);
+            ::std::process::exit(1);
+        }
+        Ok(args) => args,
+    };
     let config_path = config_path.unwrap_or_else(config::get_config_path);
     if config_path.is_dir() {
         println!("The config path is a directory.");
@@ -40,8 +51,38 @@ fn main() {
                 defaults,
                 colors,
                 log_dir,
+                mut logger,
                 tsmsg,
             }) => {
+                if log_dir.is_some()
+                    && logger.encryption == Some(libtiny_logger::Encryption::Passphrase)
+                {
+                    match read_log_passphrase() {
+                        Err(err) => {
+                            eprintln!("Can't read log passphrase: {}", err);
+                            ::std::process::exit(1);
+                        }
+                        Ok(passphrase) => {
+                            logger.passphrase = Some(libtiny_logger::Passphrase(passphrase));
+                        }
+                    }
+                }
+
+                if let Some(log_export) = log_export {
+                    let log_dir = match log_dir {
+                        None => {
+                            eprintln!("Can't export logs: `log_dir` is not set in the config");
+                            ::std::process::exit(1);
+                        }
+                        Some(log_dir) => log_dir,
+                    };
+                    if let Err(err) = export::export_log(&log_export, &log_dir, &logger, &colors) {
+                        eprintln!("{}", err);
+                        ::std::process::exit(1);
+                    }
+                    return;
+                }
+
                 let servers = if !server_args.is_empty() {
                     // connect only to servers that match at least one of
                     // the given patterns
@@ -59,7 +100,15 @@ fn main() {
                 } else {
                     servers
                 };
-                run(servers, defaults, colors, config_path, log_dir, tsmsg)
+                run(
+                    servers,
+                    defaults,
+                    colors,
+                    config_path,
+                    log_dir,
+                    logger,
+                    tsmsg,
+                )
             }
         }
     }
@@ -71,6 +120,7 @@ fn run(
     colors: Colors,
     config_path: PathBuf,
     log_dir: Option<PathBuf>,
+    logger_config: libtiny_logger::Config,
     tsmsg: bool,
 ) {
     env_logger::builder()
@@ -86,13 +136,93 @@ fn run(
 
     // Init "mentions" tab. This needs to happen before initializing the logger as otherwise we
     // won't have a tab to show errors when something goes wrong during initialization.
-    tui.new_server_tab("mentions");
+    tui.new_server_tab(MENTIONS_TAB);
     tui.add_client_msg(
         "Any mentions to you will be listed here.",
-        &MsgTarget::Server { serv: "mentions" },
+        &MsgTarget::Server { serv: MENTIONS_TAB },
     );
     tui.draw();
 
+    // Restore scrollback of new tabs from the logs. Logs are read in another thread to not block
+    // the UI, lines are added to the tabs when they're read.
+    let restore_scrollback = match logger_config.encryption {
+        Some(libtiny_logger::Encryption::Recipients(_)) if log_dir.is_some() => {
+            if logger_config.restore_scrollback.is_some() {
+                tui.add_client_err_msg(
+                    "Can't restore scrollback: logs are encrypted to recipients, and tiny doesn't \
+                     have the private keys to decrypt them",
+                    &MsgTarget::Server { serv: MENTIONS_TAB },
+                );
+            }
+            None
+        }
+        _ => logger_config.restore_scrollback,
+    };
+    if let (Some(log_dir), Some(limit)) = (&log_dir, restore_scrollback) {
+        let (snd_req, rcv_req) = std::sync::mpsc::channel::<(String, String, io::Result<LogEnd>)>();
+        let (mut snd_lines, mut rcv_lines) = tokio::sync::mpsc::unbounded_channel();
+        {
+            let log_dir = log_dir.clone();
+            let config = logger_config.clone();
+            std::thread::spawn(move || {
+                // Decrypted keys are cached in the decrypter, so use one for all tabs
+                let mut decrypter = config.decrypter();
+                for (serv, target, end) in rcv_req {
+                    let lines: io::Result<Vec<(Tm, String)>> = end
+                        .and_then(|end| {
+                            libtiny_logger::read_log(
+                                &log_dir,
+                                &config,
+                                &serv,
+                                Some(&target),
+                                limit,
+                                Some(&end),
+                                decrypter.as_mut(),
+                            )
+                        })
+                        .map(|lines| {
+                            lines
+                                .into_iter()
+                                .map(|line| (line.ts, line.to_string()))
+                                .collect()
+                        });
+                    if snd_lines.try_send((serv, target, lines)).is_err() {
+                        // UI task returned
+                        return;
+                    }
+                }
+            });
+        }
+        {
+            // Lines logged after the tab is created are shown in the tab, so only lines before the
+            // current end of the log are restored. The loader is called before the logger opens
+            // the log file of the new tab, as the TUI is the first UI in `libtiny_ui::combine`.
+            let log_dir = log_dir.clone();
+            let config = logger_config.clone();
+            tui.set_scrollback_loader(Box::new(move |serv, target| {
+                let end = libtiny_logger::log_end(&log_dir, &config, serv, Some(target));
+                let _ = snd_req.send((serv.to_owned(), target.to_owned(), end));
+            }));
+        }
+        let tui = tui.clone();
+        executor.spawn(async move {
+            while let Some((serv, target, lines)) = rcv_lines.next().await {
+                match lines {
+                    Err(err) => tui.add_restore_err(
+                        &serv,
+                        &target,
+                        &format!("Can't restore scrollback: {}", err),
+                    ),
+                    Ok(lines) => tui.add_restored_lines(&serv, &target, &lines),
+                }
+                tui.draw();
+            }
+        });
+    }
+
+    // Passed to the UI task for decrypting logs in `/grep`
+    let log_passphrase = logger_config.passphrase.clone();
+
     // Create logger
     let report_logger_error = {
         let tui_clone = tui.clone();
@@ -100,12 +230,12 @@ fn run(
             // Somehwat hacky -- only tab we have is "mentions" so we show the error there
             tui_clone.add_client_err_msg(
                 &format!("Logger error: {}", err),
-                &MsgTarget::Server { serv: "mentions" },
+                &MsgTarget::Server { serv: MENTIONS_TAB },
             )
         })
     };
-    let logger: Option<Logger> =
-        log_dir.and_then(|log_dir| match Logger::new(log_dir, report_logger_error) {
+    let logger: Option<Logger> = log_dir.clone().and_then(|log_dir| {
+        match Logger::new(log_dir, logger_config, report_logger_error) {
             Err(err) => {
                 tui.add_client_err_msg(
                     &format!("Can't create logger: {}", err),
@@ -116,10 +246,21 @@ fn run(
             Ok(logger) => {
                 // Create "mentions" log file manually -- the tab is already created in the TUI so
                 // we won't be creating a "mentions" file in the logger without this.
-                logger.new_server_tab("mentions");
+                logger.new_server_tab(MENTIONS_TAB);
+                for server in &servers {
+                    logger.set_rules(
+                        &server.addr,
+                        libtiny_logger::LogRules {
+                            log: server.log,
+                            allow: server.log_allow.clone(),
+                            deny: server.log_deny.clone(),
+                        },
+                    );
+                }
                 Some(logger)
             }
-        });
+        }
+    });
 
     let tui: Box<dyn UI> = match logger {
         None => Box::new(tui) as Box<dyn UI>,
@@ -145,6 +286,12 @@ fn run(
                     username: auth.username,
                     password: auth.password,
                 }),
+                encoding: server.encoding,
+                chan_encodings: server.chan_encodings,
+                outgoing_encoding: server.outgoing_encoding,
+                ping_interval: None,
+                websocket: server.websocket,
+                unix_socket: server.unix_socket,
             };
 
             let (client, rcv_conn_ev) = Client::new(server_info);
@@ -163,6 +310,8 @@ fn run(
         tokio::runtime::current_thread::spawn(ui::task(
             config_path,
             defaults,
+            log_dir,
+            log_passphrase,
             tui,
             clients,
             rcv_tui_ev,
@@ -171,3 +320,38 @@ fn run(
 
     executor.run().unwrap(); // unwraps RunError
 }
+
+/// Read the log encryption passphrase from the `TINY_LOG_PASSPHRASE` environment variable, or ask
+/// for it in the terminal.
+fn read_log_passphrase() -> io::Result<String> {
+    if let Ok(passphrase) = std::env::var("TINY_LOG_PASSPHRASE") {
+        return Ok(passphrase);
+    }
+    print!("Log encryption passphrase: ");
+    io::stdout().flush()?;
+    let echo_disabled = set_stdin_echo(false);
+    let mut passphrase = String::new();
+    let ret = io::stdin().read_line(&mut passphrase);
+    if echo_disabled {
+        set_stdin_echo(true);
+        println!();
+    }
+    ret?;
+    Ok(passphrase.trim_end_matches(&['\r', '\n'][..]).to_owned())
+}
+
+/// Enable or disable echoing of input in the terminal. Returns `false` if stdin is not a terminal.
+fn set_stdin_echo(echo: bool) -> bool {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+            return false;
+        }
+        if echo {
+            termios.c_lflag |= libc::ECHO;
+        } else {
+            termios.c_lflag &= !libc::ECHO;
+        }
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) == 0
+    }
+}
@@ -3,23 +3,34 @@
 
 //! UI event handling
 
-use crate::cmd::{parse_cmd, CmdArgs, ParseCmdResult};
+use crate::cmd::{parse_cmd, show_search_context, CmdArgs, LogSearch, ParseCmdResult};
 use crate::config;
 use futures_util::stream::StreamExt;
 use libtiny_client::Client;
-use libtiny_ui::{MsgSource, MsgTarget, UI};
+use libtiny_logger::Passphrase;
+use libtiny_ui::{MsgSource, MsgTags, MsgTarget, LOG_SEARCH_TAB, MENTIONS_TAB, UI};
 use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
 
 pub(crate) async fn task(
     config_path: PathBuf,
     defaults: config::Defaults,
+    log_dir: Option<PathBuf>,
+    log_passphrase: Option<Passphrase>,
     ui: Box<dyn UI>,
     mut clients: Vec<Client>,
     mut rcv_ev: mpsc::Receiver<libtiny_ui::Event>,
 ) {
+    let mut log_search = LogSearch::new(log_dir, log_passphrase);
     while let Some(ev) = rcv_ev.next().await {
-        if handle_input_ev(&config_path, &defaults, &ui, &mut clients, ev) {
+        if handle_input_ev(
+            &config_path,
+            &defaults,
+            &mut log_search,
+            &ui,
+            &mut clients,
+            ev,
+        ) {
             return;
         }
         ui.draw();
@@ -29,6 +40,7 @@ pub(crate) async fn task(
 fn handle_input_ev(
     config_path: &Path,
     defaults: &config::Defaults,
+    log_search: &mut LogSearch,
     ui: &Box<dyn UI>,
     clients: &mut Vec<Client>,
     ev: libtiny_ui::Event,
@@ -42,14 +54,26 @@ fn handle_input_ev(
             return true; // abort
         }
         Msg { msg, source } => {
-            send_msg(&**ui, clients, &source, msg, false);
+            if source.serv_name() == LOG_SEARCH_TAB {
+                show_search_context(ui, log_search, &msg);
+            } else {
+                send_msg(&**ui, clients, &source, msg, false);
+            }
         }
         Lines { lines, source } => {
+            if source.serv_name() == LOG_SEARCH_TAB {
+                for line in lines.iter() {
+                    show_search_context(ui, log_search, line);
+                }
+                return false;
+            }
             for line in lines.into_iter() {
                 send_msg(&**ui, clients, &source, line, false)
             }
         }
-        Cmd { cmd, source } => handle_cmd(config_path, defaults, ui, clients, source, &cmd),
+        Cmd { cmd, source } => {
+            handle_cmd(config_path, defaults, log_search, ui, clients, source, &cmd)
+        }
     }
 
     false // continue
@@ -58,6 +82,7 @@ fn handle_input_ev(
 fn handle_cmd(
     config_path: &Path,
     defaults: &config::Defaults,
+    log_search: &mut LogSearch,
     ui: &Box<dyn UI>,
     clients: &mut Vec<Client>,
     src: MsgSource,
@@ -69,6 +94,7 @@ fn handle_cmd(
                 args: rest,
                 config_path,
                 defaults,
+                log_search,
                 ui,
                 clients,
                 src,
@@ -100,7 +126,7 @@ pub(crate) fn send_msg(
     msg: String,
     is_action: bool,
 ) {
-    if src.serv_name() == "mentions" {
+    if src.serv_name() == MENTIONS_TAB {
         ui.add_client_err_msg(
             "Use `/connect <server>` to connect to a server",
             &MsgTarget::CurrentTab,
@@ -108,9 +134,11 @@ pub(crate) fn send_msg(
         return;
     }
 
+    // Messages in raw protocol tabs are sent to the tab's server
+    let serv_name = libtiny_ui::raw_tab_serv(src.serv_name()).unwrap_or(src.serv_name());
     let client = clients
         .iter_mut()
-        .find(|client| client.get_serv_name() == src.serv_name())
+        .find(|client| client.get_serv_name() == serv_name)
         .unwrap();
 
     // TODO: For errors:
@@ -138,6 +166,7 @@ pub(crate) fn send_msg(
                 {
                     MsgTarget::Server { serv }
                 } else {
+                    ui.new_user_tab(serv, nick);
                     MsgTarget::User { serv, nick }
                 };
                 (msg_target, nick)
@@ -145,6 +174,15 @@ pub(crate) fn send_msg(
         }
     };
 
+    // Messages to NickServ and ChanServ are shown in the server tab
+    let tags = match ui_target {
+        MsgTarget::Server { .. } => MsgTags {
+            peer: Some(msg_target),
+            ..MsgTags::default()
+        },
+        _ => MsgTags::default(),
+    };
+
     let ts = time::now();
     let extra_len = msg_target.len()
         + if is_action {
@@ -153,7 +191,18 @@ pub(crate) fn send_msg(
             0
         };
     for msg in client.split_privmsg(extra_len, &msg) {
-        client.privmsg(msg_target, msg, is_action);
-        ui.add_privmsg(&client.get_nick(), msg, ts, &ui_target, false, is_action);
+        if let Err(err) = client.privmsg(msg_target, msg, is_action) {
+            ui.add_client_err_msg(&format!("Can't send message: {}", err), &ui_target);
+            return;
+        }
+        ui.add_privmsg(
+            &client.get_nick(),
+            msg,
+            ts,
+            &ui_target,
+            false,
+            is_action,
+            tags,
+        );
     }
 }